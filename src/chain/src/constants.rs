@@ -8,6 +8,10 @@ pub const SEQUENCE_LOCKTIME_DISABLE_FLAG: u32 = 1u32 << 31;
 // disables nLockTime.
 pub const SEQUENCE_FINAL: u32 = 0xffffffff;
 
+/// Sequence numbers up to this value signal that transaction may be replaced
+/// by one paying higher fee (BIP 125)
+pub const SEQUENCE_MAX_REPLACEABLE: u32 = 0xfffffffd;
+
 // If CTxIn::nSequence encodes a relative lock-time and this flag
 // is set, the relative lock-time has units of 512 seconds,
 // otherwise it specifies blocks with a granularity of 1.
@@ -12,6 +12,7 @@ mod block;
 mod block_header;
 mod merkle_root;
 mod transaction;
+mod psbt;
 
 /// `IndexedBlock` extension
 mod read_and_hash;
@@ -30,6 +31,7 @@ pub use block::Block;
 pub use block_header::BlockHeader;
 pub use merkle_root::{merkle_root, merkle_node_hash};
 pub use transaction::{Transaction, TransactionInput, TransactionOutput, OutPoint};
+pub use psbt::{Psbt, PsbtInput, PsbtOutput, PsbtError};
 
 pub use read_and_hash::{ReadAndHash, HashedData};
 pub use indexed_block::IndexedBlock;
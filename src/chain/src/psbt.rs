@@ -0,0 +1,516 @@
+//! Partially signed bitcoin transaction
+//!
+//! Unsigned transaction together with everything its signers need: previous outputs, scripts
+//! and signatures made so far. Every participant adds what it knows, then complete input
+//! scripts are built and signed transaction is extracted.
+//!
+//! https://github.com/bitcoin/bips/blob/master/bip-0174.mediawiki
+
+use std::{fmt, io};
+use std::str::FromStr;
+use rustc_serialize::base64::{ToBase64, FromBase64, STANDARD};
+use bytes::Bytes;
+use ser::{Serializable, Deserializable, Stream, Reader, Error, serialize, serialize_list, serialize_with_flags,
+	deserialize, SERIALIZE_TRANSACTION_WITNESS};
+use transaction::{Transaction, TransactionOutput};
+
+/// "psbt" followed by 0xff separator
+const PSBT_MAGIC: [u8; 5] = [0x70, 0x73, 0x62, 0x74, 0xff];
+/// Zero length key which ends every map
+const PSBT_SEPARATOR: u8 = 0x00;
+
+const PSBT_GLOBAL_UNSIGNED_TX: u8 = 0x00;
+
+const PSBT_IN_NON_WITNESS_UTXO: u8 = 0x00;
+const PSBT_IN_WITNESS_UTXO: u8 = 0x01;
+const PSBT_IN_PARTIAL_SIG: u8 = 0x02;
+const PSBT_IN_SIGHASH_TYPE: u8 = 0x03;
+const PSBT_IN_REDEEM_SCRIPT: u8 = 0x04;
+const PSBT_IN_WITNESS_SCRIPT: u8 = 0x05;
+const PSBT_IN_FINAL_SCRIPTSIG: u8 = 0x07;
+const PSBT_IN_FINAL_SCRIPTWITNESS: u8 = 0x08;
+
+const PSBT_OUT_REDEEM_SCRIPT: u8 = 0x00;
+const PSBT_OUT_WITNESS_SCRIPT: u8 = 0x01;
+
+#[derive(Debug, PartialEq)]
+pub enum PsbtError {
+	InvalidBase64,
+	Deserialize(Error),
+	/// Transaction given as unsigned already has script sigs or witnesses
+	SignedTransaction,
+	/// Combined PSBTs belong to different transactions
+	DifferentTransactions,
+	/// Some inputs don't have final scripts yet
+	NotFinalized,
+}
+
+impl From<Error> for PsbtError {
+	fn from(err: Error) -> Self {
+		PsbtError::Deserialize(err)
+	}
+}
+
+/// What is known about spending of a single input
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct PsbtInput {
+	/// Whole transaction which created spent output
+	pub non_witness_utxo: Option<Transaction>,
+	/// Spent output. Enough to sign witness inputs
+	pub witness_utxo: Option<TransactionOutput>,
+	/// Signatures with sighash type byte, paired with public keys which made them
+	pub partial_signatures: Vec<(Bytes, Bytes)>,
+	/// Sighash type signers must use. SIGHASH_ALL if not set
+	pub sighash_type: Option<u32>,
+	pub redeem_script: Option<Bytes>,
+	pub witness_script: Option<Bytes>,
+	pub final_script_sig: Option<Bytes>,
+	pub final_script_witness: Option<Vec<Bytes>>,
+	/// Pairs of types this implementation doesn't interpret. Kept to be passed along
+	pub unknown: Vec<(Bytes, Bytes)>,
+}
+
+impl PsbtInput {
+	pub fn is_finalized(&self) -> bool {
+		self.final_script_sig.is_some() || self.final_script_witness.is_some()
+	}
+
+	/// Sets complete input scripts and drops data which was needed only to build them
+	pub fn finalize(&mut self, final_script_sig: Option<Bytes>, final_script_witness: Option<Vec<Bytes>>) {
+		self.final_script_sig = final_script_sig;
+		self.final_script_witness = final_script_witness;
+		self.partial_signatures.clear();
+		self.sighash_type = None;
+		self.redeem_script = None;
+		self.witness_script = None;
+	}
+
+	/// Adds signature of `public` unless input already has one
+	pub fn add_partial_signature(&mut self, public: Bytes, signature: Bytes) {
+		if self.partial_signature(&public).is_none() {
+			self.partial_signatures.push((public, signature));
+		}
+	}
+
+	pub fn partial_signature(&self, public: &[u8]) -> Option<&Bytes> {
+		self.partial_signatures.iter()
+			.find(|&&(ref key, _)| &**key == public)
+			.map(|&(_, ref signature)| signature)
+	}
+
+	fn combine(&mut self, other: PsbtInput) {
+		self.non_witness_utxo = self.non_witness_utxo.take().or(other.non_witness_utxo);
+		self.witness_utxo = self.witness_utxo.take().or(other.witness_utxo);
+		self.sighash_type = self.sighash_type.take().or(other.sighash_type);
+		self.redeem_script = self.redeem_script.take().or(other.redeem_script);
+		self.witness_script = self.witness_script.take().or(other.witness_script);
+		self.final_script_sig = self.final_script_sig.take().or(other.final_script_sig);
+		self.final_script_witness = self.final_script_witness.take().or(other.final_script_witness);
+		merge_pairs(&mut self.partial_signatures, other.partial_signatures);
+		merge_pairs(&mut self.unknown, other.unknown);
+	}
+}
+
+/// What is known about a single output
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct PsbtOutput {
+	pub redeem_script: Option<Bytes>,
+	pub witness_script: Option<Bytes>,
+	/// Pairs of types this implementation doesn't interpret. Kept to be passed along
+	pub unknown: Vec<(Bytes, Bytes)>,
+}
+
+impl PsbtOutput {
+	fn combine(&mut self, other: PsbtOutput) {
+		self.redeem_script = self.redeem_script.take().or(other.redeem_script);
+		self.witness_script = self.witness_script.take().or(other.witness_script);
+		merge_pairs(&mut self.unknown, other.unknown);
+	}
+}
+
+/// Partially signed bitcoin transaction
+#[derive(Debug, PartialEq, Clone)]
+pub struct Psbt {
+	/// Transaction with empty script sigs and witnesses
+	pub unsigned_transaction: Transaction,
+	/// Global pairs of types this implementation doesn't interpret
+	pub unknown: Vec<(Bytes, Bytes)>,
+	/// One per transaction input
+	pub inputs: Vec<PsbtInput>,
+	/// One per transaction output
+	pub outputs: Vec<PsbtOutput>,
+}
+
+impl Psbt {
+	/// Creates PSBT without any data about inputs and outputs
+	pub fn from_unsigned_transaction(transaction: Transaction) -> Result<Self, PsbtError> {
+		if !is_unsigned(&transaction) {
+			return Err(PsbtError::SignedTransaction);
+		}
+
+		Ok(Psbt {
+			inputs: transaction.inputs.iter().map(|_| PsbtInput::default()).collect(),
+			outputs: transaction.outputs.iter().map(|_| PsbtOutput::default()).collect(),
+			unsigned_transaction: transaction,
+			unknown: vec![],
+		})
+	}
+
+	/// Output spent by input at `index`, taken either from witness UTXO or from whole previous transaction
+	pub fn prevout(&self, index: usize) -> Option<TransactionOutput> {
+		let input = &self.inputs[index];
+		if let Some(ref output) = input.witness_utxo {
+			return Some(output.clone());
+		}
+
+		let previous_output = &self.unsigned_transaction.inputs[index].previous_output;
+		match input.non_witness_utxo {
+			Some(ref transaction) if transaction.hash() == previous_output.hash =>
+				transaction.outputs.get(previous_output.index as usize).cloned(),
+			_ => None,
+		}
+	}
+
+	/// Merges data of `other` PSBT for the same transaction. Values already present are kept
+	pub fn combine(&mut self, other: Psbt) -> Result<(), PsbtError> {
+		if self.unsigned_transaction.hash() != other.unsigned_transaction.hash() {
+			return Err(PsbtError::DifferentTransactions);
+		}
+
+		merge_pairs(&mut self.unknown, other.unknown);
+		for (input, other) in self.inputs.iter_mut().zip(other.inputs.into_iter()) {
+			input.combine(other);
+		}
+		for (output, other) in self.outputs.iter_mut().zip(other.outputs.into_iter()) {
+			output.combine(other);
+		}
+		Ok(())
+	}
+
+	pub fn is_finalized(&self) -> bool {
+		self.inputs.iter().all(PsbtInput::is_finalized)
+	}
+
+	/// Signed transaction built from final scripts of every input
+	pub fn extract_transaction(&self) -> Result<Transaction, PsbtError> {
+		if !self.is_finalized() {
+			return Err(PsbtError::NotFinalized);
+		}
+
+		let mut transaction = self.unsigned_transaction.clone();
+		for (input, psbt_input) in transaction.inputs.iter_mut().zip(self.inputs.iter()) {
+			input.script_sig = psbt_input.final_script_sig.clone().unwrap_or_default();
+			input.script_witness = psbt_input.final_script_witness.clone().unwrap_or_default();
+		}
+		Ok(transaction)
+	}
+}
+
+fn is_unsigned(transaction: &Transaction) -> bool {
+	transaction.inputs.iter().all(|input| input.script_sig.is_empty() && input.script_witness.is_empty())
+}
+
+/// Adds pairs of `other` with keys not present in `pairs`
+fn merge_pairs(pairs: &mut Vec<(Bytes, Bytes)>, other: Vec<(Bytes, Bytes)>) {
+	for (key, value) in other {
+		if !pairs.iter().any(|&(ref present, _)| *present == key) {
+			pairs.push((key, value));
+		}
+	}
+}
+
+fn append_pair(stream: &mut Stream, key_type: u8, key_data: &[u8], value: &[u8]) {
+	let mut key = vec![key_type];
+	key.extend_from_slice(key_data);
+	stream
+		.append(&Bytes::from(key))
+		.append(&Bytes::from(value));
+}
+
+fn append_unknown(stream: &mut Stream, pairs: &[(Bytes, Bytes)]) {
+	for &(ref key, ref value) in pairs {
+		stream.append(key).append(value);
+	}
+}
+
+/// Reads key-value pairs up to separator. Keys must be unique within map
+fn read_map<T>(reader: &mut Reader<T>) -> Result<Vec<(Bytes, Bytes)>, Error> where T: io::Read {
+	let mut pairs: Vec<(Bytes, Bytes)> = vec![];
+	loop {
+		let key: Bytes = reader.read()?;
+		if key.is_empty() {
+			return Ok(pairs);
+		}
+
+		let value: Bytes = reader.read()?;
+		if pairs.iter().any(|&(ref present, _)| *present == key) {
+			return Err(Error::MalformedData);
+		}
+		pairs.push((key, value));
+	}
+}
+
+/// Fails if value is already set, since keys of known types can appear only once
+fn set_once<T>(field: &mut Option<T>, value: T) -> Result<(), Error> {
+	if field.is_some() {
+		return Err(Error::MalformedData);
+	}
+	*field = Some(value);
+	Ok(())
+}
+
+fn read_witness(value: &[u8]) -> Result<Vec<Bytes>, Error> {
+	let mut reader = Reader::new(value);
+	let witness = reader.read_list()?;
+	if !reader.is_finished() {
+		return Err(Error::UnreadData);
+	}
+	Ok(witness)
+}
+
+impl Serializable for PsbtInput {
+	fn serialize(&self, stream: &mut Stream) {
+		if let Some(ref transaction) = self.non_witness_utxo {
+			append_pair(stream, PSBT_IN_NON_WITNESS_UTXO, &[], &serialize_with_flags(transaction, SERIALIZE_TRANSACTION_WITNESS));
+		}
+		if let Some(ref output) = self.witness_utxo {
+			append_pair(stream, PSBT_IN_WITNESS_UTXO, &[], &serialize(output));
+		}
+		for &(ref public, ref signature) in &self.partial_signatures {
+			append_pair(stream, PSBT_IN_PARTIAL_SIG, public, signature);
+		}
+		if let Some(sighash_type) = self.sighash_type {
+			append_pair(stream, PSBT_IN_SIGHASH_TYPE, &[], &serialize(&sighash_type));
+		}
+		if let Some(ref script) = self.redeem_script {
+			append_pair(stream, PSBT_IN_REDEEM_SCRIPT, &[], script);
+		}
+		if let Some(ref script) = self.witness_script {
+			append_pair(stream, PSBT_IN_WITNESS_SCRIPT, &[], script);
+		}
+		if let Some(ref script) = self.final_script_sig {
+			append_pair(stream, PSBT_IN_FINAL_SCRIPTSIG, &[], script);
+		}
+		if let Some(ref witness) = self.final_script_witness {
+			append_pair(stream, PSBT_IN_FINAL_SCRIPTWITNESS, &[], &serialize_list::<Bytes, Bytes>(witness));
+		}
+		append_unknown(stream, &self.unknown);
+		stream.append(&PSBT_SEPARATOR);
+	}
+}
+
+impl Deserializable for PsbtInput {
+	fn deserialize<T>(reader: &mut Reader<T>) -> Result<Self, Error> where Self: Sized, T: io::Read {
+		let mut input = PsbtInput::default();
+		for (key, value) in read_map(reader)? {
+			match (key[0], key.len()) {
+				(PSBT_IN_NON_WITNESS_UTXO, 1) => set_once(&mut input.non_witness_utxo, deserialize(&value[..])?)?,
+				(PSBT_IN_WITNESS_UTXO, 1) => set_once(&mut input.witness_utxo, deserialize(&value[..])?)?,
+				(PSBT_IN_PARTIAL_SIG, _) => input.partial_signatures.push((key[1..].into(), value)),
+				(PSBT_IN_SIGHASH_TYPE, 1) => set_once(&mut input.sighash_type, deserialize(&value[..])?)?,
+				(PSBT_IN_REDEEM_SCRIPT, 1) => set_once(&mut input.redeem_script, value)?,
+				(PSBT_IN_WITNESS_SCRIPT, 1) => set_once(&mut input.witness_script, value)?,
+				(PSBT_IN_FINAL_SCRIPTSIG, 1) => set_once(&mut input.final_script_sig, value)?,
+				(PSBT_IN_FINAL_SCRIPTWITNESS, 1) => set_once(&mut input.final_script_witness, read_witness(&value)?)?,
+				(PSBT_IN_NON_WITNESS_UTXO, _) | (PSBT_IN_WITNESS_UTXO, _) | (PSBT_IN_SIGHASH_TYPE, _) |
+				(PSBT_IN_REDEEM_SCRIPT, _) | (PSBT_IN_WITNESS_SCRIPT, _) | (PSBT_IN_FINAL_SCRIPTSIG, _) |
+				(PSBT_IN_FINAL_SCRIPTWITNESS, _) => return Err(Error::MalformedData),
+				_ => input.unknown.push((key, value)),
+			}
+		}
+		Ok(input)
+	}
+}
+
+impl Serializable for PsbtOutput {
+	fn serialize(&self, stream: &mut Stream) {
+		if let Some(ref script) = self.redeem_script {
+			append_pair(stream, PSBT_OUT_REDEEM_SCRIPT, &[], script);
+		}
+		if let Some(ref script) = self.witness_script {
+			append_pair(stream, PSBT_OUT_WITNESS_SCRIPT, &[], script);
+		}
+		append_unknown(stream, &self.unknown);
+		stream.append(&PSBT_SEPARATOR);
+	}
+}
+
+impl Deserializable for PsbtOutput {
+	fn deserialize<T>(reader: &mut Reader<T>) -> Result<Self, Error> where Self: Sized, T: io::Read {
+		let mut output = PsbtOutput::default();
+		for (key, value) in read_map(reader)? {
+			match (key[0], key.len()) {
+				(PSBT_OUT_REDEEM_SCRIPT, 1) => set_once(&mut output.redeem_script, value)?,
+				(PSBT_OUT_WITNESS_SCRIPT, 1) => set_once(&mut output.witness_script, value)?,
+				(PSBT_OUT_REDEEM_SCRIPT, _) | (PSBT_OUT_WITNESS_SCRIPT, _) => return Err(Error::MalformedData),
+				_ => output.unknown.push((key, value)),
+			}
+		}
+		Ok(output)
+	}
+}
+
+impl Serializable for Psbt {
+	fn serialize(&self, stream: &mut Stream) {
+		stream.append_slice(&PSBT_MAGIC);
+		append_pair(stream, PSBT_GLOBAL_UNSIGNED_TX, &[], &serialize(&self.unsigned_transaction));
+		append_unknown(stream, &self.unknown);
+		stream.append(&PSBT_SEPARATOR);
+		for input in &self.inputs {
+			stream.append(input);
+		}
+		for output in &self.outputs {
+			stream.append(output);
+		}
+	}
+}
+
+impl Deserializable for Psbt {
+	fn deserialize<T>(reader: &mut Reader<T>) -> Result<Self, Error> where Self: Sized, T: io::Read {
+		let mut magic = [0u8; 5];
+		reader.read_slice(&mut magic)?;
+		if magic != PSBT_MAGIC {
+			return Err(Error::MalformedData);
+		}
+
+		let mut unsigned_transaction = None;
+		let mut unknown = vec![];
+		for (key, value) in read_map(reader)? {
+			match (key[0], key.len()) {
+				(PSBT_GLOBAL_UNSIGNED_TX, 1) => set_once(&mut unsigned_transaction, deserialize(&value[..])?)?,
+				(PSBT_GLOBAL_UNSIGNED_TX, _) => return Err(Error::MalformedData),
+				_ => unknown.push((key, value)),
+			}
+		}
+
+		let unsigned_transaction: Transaction = match unsigned_transaction {
+			Some(transaction) => transaction,
+			None => return Err(Error::MalformedData),
+		};
+		if !is_unsigned(&unsigned_transaction) {
+			return Err(Error::MalformedData);
+		}
+
+		let mut inputs = Vec::with_capacity(unsigned_transaction.inputs.len());
+		for _ in 0..unsigned_transaction.inputs.len() {
+			inputs.push(reader.read()?);
+		}
+		let mut outputs = Vec::with_capacity(unsigned_transaction.outputs.len());
+		for _ in 0..unsigned_transaction.outputs.len() {
+			outputs.push(reader.read()?);
+		}
+
+		Ok(Psbt {
+			unsigned_transaction,
+			unknown,
+			inputs,
+			outputs,
+		})
+	}
+}
+
+impl fmt::Display for Psbt {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		serialize(self).to_base64(STANDARD).fmt(f)
+	}
+}
+
+impl FromStr for Psbt {
+	type Err = PsbtError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let data = s.from_base64().map_err(|_| PsbtError::InvalidBase64)?;
+		Ok(deserialize(&data[..])?)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use bytes::Bytes;
+	use ser::{serialize, deserialize, Error};
+	use transaction::{Transaction, TransactionInput, TransactionOutput, OutPoint};
+	use super::{Psbt, PsbtInput, PsbtError};
+
+	fn unsigned_transaction() -> Transaction {
+		Transaction {
+			version: 2,
+			inputs: vec![TransactionInput {
+				previous_output: OutPoint {
+					hash: "c3a1b5a1ee01ee3a7f0f48d4d5b7ec6d0e8c9a3bc5a0d3a3e6f8e4f1ad0b2e46".into(),
+					index: 1,
+				},
+				script_sig: Bytes::new(),
+				sequence: 0xffffffff,
+				script_witness: vec![],
+			}],
+			outputs: vec![TransactionOutput {
+				value: 50000,
+				script_pubkey: "0014751e76e8199196d454941c45d1b3a323f1433bd6".into(),
+			}],
+			lock_time: 0,
+		}
+	}
+
+	#[test]
+	fn test_psbt_from_str() {
+		let psbt: Psbt = "cHNidP8BAFICAAAAAcOhtaHuAe46fw9I1NW37G0OjJo7xaDTo+b45PGtCy5GAQAAAAD/////AVDDAAAAAAAAFgAUdR526BmRltRUlBxF0bOjI/FDO9YAAAAAAAAA".parse().unwrap();
+		assert_eq!(psbt, Psbt::from_unsigned_transaction(unsigned_transaction()).unwrap());
+		assert_eq!(psbt.to_string(), "cHNidP8BAFICAAAAAcOhtaHuAe46fw9I1NW37G0OjJo7xaDTo+b45PGtCy5GAQAAAAD/////AVDDAAAAAAAAFgAUdR526BmRltRUlBxF0bOjI/FDO9YAAAAAAAAA");
+	}
+
+	#[test]
+	fn test_psbt_serialization_roundtrip() {
+		let mut psbt = Psbt::from_unsigned_transaction(unsigned_transaction()).unwrap();
+		psbt.inputs[0] = PsbtInput {
+			witness_utxo: Some(TransactionOutput {
+				value: 60000,
+				script_pubkey: "0020e6d4f3a2c5b1a0f9e8d7c6b5a493827160504f3e2d1c0b0a09f8e7d6c5b4a392".into(),
+			}),
+			partial_signatures: vec![("02aabb".into(), "3001".into())],
+			sighash_type: Some(1),
+			witness_script: Some("5121aabb51ae".into()),
+			unknown: vec![("0601".into(), "ff".into())],
+			..Default::default()
+		};
+		psbt.outputs[0].witness_script = Some("51".into());
+
+		let serialized = serialize(&psbt);
+		assert_eq!(&serialized[..5], b"psbt\xff");
+		assert_eq!(deserialize::<_, Psbt>(&serialized[..]), Ok(psbt));
+	}
+
+	#[test]
+	fn test_psbt_rejects_signed_transaction() {
+		let mut transaction = unsigned_transaction();
+		transaction.inputs[0].script_witness = vec!["01".into()];
+		assert_eq!(Psbt::from_unsigned_transaction(transaction), Err(PsbtError::SignedTransaction));
+
+		let mut serialized = serialize(&Psbt::from_unsigned_transaction(unsigned_transaction()).unwrap());
+		serialized[0] = b'x';
+		assert_eq!(deserialize::<_, Psbt>(&serialized[..]), Err(Error::MalformedData));
+	}
+
+	#[test]
+	fn test_psbt_combine_and_extract() {
+		let mut first = Psbt::from_unsigned_transaction(unsigned_transaction()).unwrap();
+		first.inputs[0].partial_signatures.push(("02aa".into(), "3001".into()));
+		let mut second = first.clone();
+		second.inputs[0].partial_signatures.push(("03bb".into(), "3002".into()));
+		second.inputs[0].witness_script = Some("52ae".into());
+
+		first.combine(second).unwrap();
+		assert_eq!(first.inputs[0].partial_signatures.len(), 2);
+		assert_eq!(first.inputs[0].witness_script, Some("52ae".into()));
+		assert_eq!(first.extract_transaction(), Err(PsbtError::NotFinalized));
+
+		first.inputs[0].finalize(None, Some(vec![Bytes::new(), "3001".into(), "3002".into(), "52ae".into()]));
+		assert!(first.inputs[0].partial_signatures.is_empty());
+		let transaction = first.extract_transaction().unwrap();
+		assert_eq!(transaction.inputs[0].script_witness.len(), 4);
+		assert_eq!(transaction.hash(), unsigned_transaction().hash());
+
+		let mut other = Psbt::from_unsigned_transaction(unsigned_transaction()).unwrap();
+		other.unsigned_transaction.lock_time = 1;
+		assert_eq!(first.combine(other), Err(PsbtError::DifferentTransactions));
+	}
+}
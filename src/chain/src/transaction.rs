@@ -8,7 +8,7 @@ use bytes::Bytes;
 use ser::{deserialize, serialize, serialize_with_flags, SERIALIZE_TRANSACTION_WITNESS};
 use crypto::dhash256;
 use hash::H256;
-use constants::{SEQUENCE_FINAL, LOCKTIME_THRESHOLD};
+use constants::{SEQUENCE_FINAL, SEQUENCE_MAX_REPLACEABLE, LOCKTIME_THRESHOLD};
 use ser::{Error, Serializable, Deserializable, Stream, Reader};
 
 /// Must be zero.
@@ -16,7 +16,7 @@ const WITNESS_MARKER: u8 = 0;
 /// Must be nonzero.
 const WITNESS_FLAG: u8 = 1;
 
-#[derive(Debug, PartialEq, Eq, Clone, Default, Serializable, Deserializable)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Default, Serializable, Deserializable)]
 pub struct OutPoint {
 	pub hash: H256,
 	pub index: u32,
@@ -149,6 +149,11 @@ impl Transaction {
 		self.inputs.iter().all(TransactionInput::is_final)
 	}
 
+	/// True if any input opts in to replacement by transaction paying higher fee (BIP 125)
+	pub fn signals_replacement(&self) -> bool {
+		self.inputs.iter().any(|input| input.sequence <= SEQUENCE_MAX_REPLACEABLE)
+	}
+
 	pub fn is_final_in_block(&self, block_height: u32, block_time: u32) -> bool {
 		if self.lock_time == 0 {
 			return true;
@@ -291,6 +296,16 @@ mod tests {
 		assert_eq!(t.hash(), hash);
 	}
 
+	#[test]
+	fn test_transaction_signals_replacement() {
+		let mut t: Transaction = "0100000001a6b97044d03da79c005b20ea9c0e1a6d9dc12d9f7b91a5911c9030a439eed8f5000000004948304502206e21798a42fae0e854281abd38bacd1aeed3ee3738d9e1446618c4571d1090db022100e2ac980643b0b82c0e88ffdfec6b64e3e6ba35e7ba5fdd7d5d6cc8d25c6b241501ffffffff0100f2052a010000001976a914404371705fa9bd789a2fcd52d2c580b65d35549d88ac00000000".into();
+		assert!(!t.signals_replacement());
+		t.inputs[0].sequence = 0xfffffffe;
+		assert!(!t.signals_replacement());
+		t.inputs[0].sequence = 0xfffffffd;
+		assert!(t.signals_replacement());
+	}
+
 	#[test]
 	fn test_transaction_serialized_len() {
 		let raw_tx: &'static str = "0100000001a6b97044d03da79c005b20ea9c0e1a6d9dc12d9f7b91a5911c9030a439eed8f5000000004948304502206e21798a42fae0e854281abd38bacd1aeed3ee3738d9e1446618c4571d1090db022100e2ac980643b0b82c0e88ffdfec6b64e3e6ba35e7ba5fdd7d5d6cc8d25c6b241501ffffffff0100f2052a010000001976a914404371705fa9bd789a2fcd52d2c580b65d35549d88ac00000000";
@@ -3,12 +3,19 @@ extern crate primitives;
 extern crate siphasher;
 
 pub use rcrypto::digest::Digest;
+pub use rcrypto::symmetriccipher::SymmetricCipherError;
 use std::hash::Hasher;
 use rcrypto::sha1::Sha1;
-use rcrypto::sha2::Sha256;
+use rcrypto::sha2::{Sha256, Sha512};
 use rcrypto::ripemd160::Ripemd160;
+use rcrypto::hmac::Hmac;
+use rcrypto::mac::Mac;
+use rcrypto::pbkdf2::pbkdf2;
+use rcrypto::aes::{self, KeySize};
+use rcrypto::blockmodes::PkcsPadding;
+use rcrypto::buffer::{RefReadBuffer, RefWriteBuffer, ReadBuffer, WriteBuffer, BufferResult};
 use siphasher::sip::SipHasher24;
-use primitives::hash::{H32, H160, H256};
+use primitives::hash::{H32, H160, H256, H512};
 
 pub struct DHash160 {
 	sha256: Sha256,
@@ -171,10 +178,69 @@ pub fn checksum(data: &[u8]) -> H32 {
 	result
 }
 
+/// HMAC-SHA512
+#[inline]
+pub fn hmac_sha512(key: &[u8], data: &[u8]) -> H512 {
+	let mut result = H512::default();
+	let mut mac = Hmac::new(Sha512::new(), key);
+	mac.input(data);
+	mac.raw_result(&mut *result);
+	result
+}
+
+/// PBKDF2 key stretching with HMAC-SHA256 as pseudorandom function
+pub fn pbkdf2_sha256(password: &[u8], salt: &[u8], iterations: u32, out: &mut [u8]) {
+	let mut mac = Hmac::new(Sha256::new(), password);
+	pbkdf2(&mut mac, salt, iterations, out);
+}
+
+/// PBKDF2 key stretching with HMAC-SHA512 as pseudorandom function
+pub fn pbkdf2_sha512(password: &[u8], salt: &[u8], iterations: u32, out: &mut [u8]) {
+	let mut mac = Hmac::new(Sha512::new(), password);
+	pbkdf2(&mut mac, salt, iterations, out);
+}
+
+/// AES-256 in CBC mode with PKCS#7 padding
+pub fn aes256_cbc_encrypt(key: &[u8], iv: &[u8], data: &[u8]) -> Result<Vec<u8>, SymmetricCipherError> {
+	let mut encryptor = aes::cbc_encryptor(KeySize::KeySize256, key, iv, PkcsPadding);
+	let mut result = Vec::with_capacity(data.len() + 16);
+	let mut input = RefReadBuffer::new(data);
+	let mut buffer = [0u8; 4096];
+	let mut output = RefWriteBuffer::new(&mut buffer);
+	loop {
+		let status = try!(encryptor.encrypt(&mut input, &mut output, true));
+		result.extend_from_slice(output.take_read_buffer().take_remaining());
+		match status {
+			BufferResult::BufferUnderflow => break,
+			BufferResult::BufferOverflow => {},
+		}
+	}
+	Ok(result)
+}
+
+/// Reverse of `aes256_cbc_encrypt`. Fails on wrong key most of the time because of padding mismatch
+pub fn aes256_cbc_decrypt(key: &[u8], iv: &[u8], data: &[u8]) -> Result<Vec<u8>, SymmetricCipherError> {
+	let mut decryptor = aes::cbc_decryptor(KeySize::KeySize256, key, iv, PkcsPadding);
+	let mut result = Vec::with_capacity(data.len());
+	let mut input = RefReadBuffer::new(data);
+	let mut buffer = [0u8; 4096];
+	let mut output = RefWriteBuffer::new(&mut buffer);
+	loop {
+		let status = try!(decryptor.decrypt(&mut input, &mut output, true));
+		result.extend_from_slice(output.take_read_buffer().take_remaining());
+		match status {
+			BufferResult::BufferUnderflow => break,
+			BufferResult::BufferOverflow => {},
+		}
+	}
+	Ok(result)
+}
+
 #[cfg(test)]
 mod tests {
 	use primitives::bytes::Bytes;
 	use super::{ripemd160, sha1, sha256, dhash160, dhash256, siphash24, checksum};
+	use super::{hmac_sha512, pbkdf2_sha256, pbkdf2_sha512, aes256_cbc_encrypt, aes256_cbc_decrypt};
 
 	#[test]
 	fn test_ripemd160() {
@@ -227,4 +293,39 @@ mod tests {
 	fn test_checksum() {
 		assert_eq!(checksum(b"hello"), "9595c9df".into());
 	}
+
+	#[test]
+	fn test_hmac_sha512() {
+		// RFC 4231, test case 2
+		let expected = "164b7a7bfcf819e2e395fbe73b56e0a387bd64222e831fd610270cd7ea2505549758bf75c05a994a6d034f65f8f0e6fdcaeab1a34d4a6b4b636e070a38bce737".into();
+		let result = hmac_sha512(b"Jefe", b"what do ya want for nothing?");
+		assert_eq!(result, expected);
+	}
+
+	#[test]
+	fn test_pbkdf2_sha256() {
+		// RFC 7914, section 11
+		let expected: Bytes = "55ac046e56e3089fec1691c22544b605f94185216dde0465e68b9d57c20dacbc49ca9cccf179b645991664b39d77ef317c71b845b1e30bd509112041d3a19783".into();
+		let mut result = [0u8; 64];
+		pbkdf2_sha256(b"passwd", b"salt", 1, &mut result);
+		assert_eq!(&result[..], &*expected);
+	}
+
+	#[test]
+	fn test_pbkdf2_sha512() {
+		let expected: Bytes = "867f70cf1ade02cff3752599a3a53dc4af34c7a669815ae5d513554e1c8cf252c02d470a285a0501bad999bfe943c08f050235d7d68b1da55e63f73b60a57fce".into();
+		let mut result = [0u8; 64];
+		pbkdf2_sha512(b"password", b"salt", 1, &mut result);
+		assert_eq!(&result[..], &*expected);
+	}
+
+	#[test]
+	fn test_aes256_cbc_roundtrip() {
+		let key = sha256(b"key");
+		let iv = [7u8; 16];
+		let data = b"Very secret message which is longer than one block";
+		let encrypted = aes256_cbc_encrypt(&*key, &iv, data).unwrap();
+		assert_eq!(encrypted.len(), 64);
+		assert_eq!(&aes256_cbc_decrypt(&*key, &iv, &encrypted).unwrap()[..], &data[..]);
+	}
 }
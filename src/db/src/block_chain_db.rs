@@ -29,9 +29,23 @@ use script::Script;
 
 const KEY_BEST_BLOCK_NUMBER: &'static str = "best_block_number";
 const KEY_BEST_BLOCK_HASH: &'static str = "best_block_hash";
+const KEY_ADDRESS_INDEX: &'static str = "address_index";
+
+/// Version of address index stored under `KEY_ADDRESS_INDEX`. Index of any other version is rebuilt on open
+const ADDRESS_INDEX_VERSION: u32 = 1;
 
 const MAX_FORK_ROUTE_PRESET: usize = 2048;
 
+/// Hashes of addresses `output` pays to
+fn output_address_hashes(output: &TransactionOutput) -> Vec<H160> {
+	let script: Script = output.script_pubkey.clone().into();
+	script.extract_destinations()
+		.unwrap_or_default()
+		.into_iter()
+		.map(|address| address.hash)
+		.collect()
+}
+
 pub struct BlockChainDatabase<T> where T: KeyValueDatabase {
 	best_block: RwLock<BestBlock>,
 	db: T,
@@ -67,7 +81,7 @@ impl BlockChainDatabase<CacheDatabase<AutoFlushingOverlayDatabase<DiskDatabase>>
 		cfg.bloom_filters.insert(Some(COL_TRANSACTIONS_META), 32);
 
 		match DiskDatabase::open(cfg, path) {
-			Ok(db) => Ok(Self::open_with_cache(db)),
+			Ok(db) => Self::open_with_cache(db),
 			Err(err) => Err(Error::DatabaseError(err))
 		}
 	}
@@ -75,7 +89,7 @@ impl BlockChainDatabase<CacheDatabase<AutoFlushingOverlayDatabase<DiskDatabase>>
 
 impl BlockChainDatabase<MemoryDatabase> {
 	pub fn init_test_chain(blocks: Vec<IndexedBlock>) -> Self {
-		let store = BlockChainDatabase::open(MemoryDatabase::default());
+		let store = BlockChainDatabase::open(MemoryDatabase::default()).unwrap();
 
 		for block in blocks {
 			let hash = block.hash().clone();
@@ -87,13 +101,15 @@ impl BlockChainDatabase<MemoryDatabase> {
 }
 
 impl<T> BlockChainDatabase<CacheDatabase<AutoFlushingOverlayDatabase<T>>> where T: KeyValueDatabase {
-	pub fn open_with_cache(db: T) -> Self {
+	pub fn open_with_cache(db: T) -> Result<Self, Error> {
 		let db = CacheDatabase::new(AutoFlushingOverlayDatabase::new(db, 5));
 		let best_block = Self::read_best_block(&db).unwrap_or_default();
-		BlockChainDatabase {
+		let store = BlockChainDatabase {
 			best_block: RwLock::new(best_block),
 			db: db,
-		}
+		};
+		store.build_address_index()?;
+		Ok(store)
 	}
 }
 
@@ -112,12 +128,46 @@ impl<T> BlockChainDatabase<T> where T: KeyValueDatabase {
 		}
 	}
 
-	pub fn open(db: T) -> Self {
+	pub fn open(db: T) -> Result<Self, Error> {
 		let best_block = Self::read_best_block(&db).unwrap_or_default();
-		BlockChainDatabase {
+		let store = BlockChainDatabase {
 			best_block: RwLock::new(best_block),
 			db: db,
+		};
+		store.build_address_index()?;
+		Ok(store)
+	}
+
+	/// Builds address index from unspent outputs of canon chain if database was created before the index existed
+	fn build_address_index(&self) -> Result<(), Error> {
+		let version = self.db.get(&Key::Meta(KEY_ADDRESS_INDEX))
+			.map_err(Error::DatabaseError)?
+			.into_option()
+			.and_then(Value::as_meta)
+			.and_then(|version| deserialize::<_, u32>(&*version).ok());
+		if version == Some(ADDRESS_INDEX_VERSION) {
+			return Ok(());
+		}
+
+		let best_block = self.best_block();
+		let mut modified_utxos = HashMap::new();
+		if !best_block.hash.is_zero() {
+			for number in 0..best_block.number + 1 {
+				for tx in self.indexed_block_transactions(BlockRef::Number(number)) {
+					for (index, output) in tx.raw.outputs.iter().enumerate() {
+						let out_point = OutPoint { hash: tx.hash.clone(), index: index as u32 };
+						if !self.is_spent(&out_point) {
+							self.update_address_utxos(&mut modified_utxos, output, |utxos| utxos.push(out_point.clone()));
+						}
+					}
+				}
+			}
 		}
+
+		let mut update = DBTransaction::new();
+		Self::write_address_utxos(&mut update, modified_utxos);
+		update.insert(KeyValue::Meta(KEY_ADDRESS_INDEX, serialize(&ADDRESS_INDEX_VERSION)));
+		self.db.write(update).map_err(Error::DatabaseError)
 	}
 
 	pub fn best_block(&self) -> BestBlock {
@@ -125,7 +175,7 @@ impl<T> BlockChainDatabase<T> where T: KeyValueDatabase {
 	}
 
 	pub fn fork(&self, side_chain: SideChainOrigin) -> Result<ForkChainDatabase<T>, Error> {
-		let overlay = BlockChainDatabase::open(OverlayDatabase::new(&self.db));
+		let overlay = BlockChainDatabase::open(OverlayDatabase::new(&self.db))?;
 
 		for hash in side_chain.decanonized_route.into_iter().rev() {
 			let decanonized_hash = overlay.decanonize()?;
@@ -309,6 +359,26 @@ impl<T> BlockChainDatabase<T> where T: KeyValueDatabase {
 			update.insert(KeyValue::TransactionMeta(hash, meta));
 		}
 
+		let mut modified_utxos = HashMap::new();
+		for (tx_index, tx) in block.transactions.iter().enumerate() {
+			// coinbase input does not spend anything
+			if tx_index != 0 {
+				for input in &tx.raw.inputs {
+					let prevout = &input.previous_output;
+					let output = self.transaction(&prevout.hash)
+						.and_then(|tx| tx.outputs.into_iter().nth(prevout.index as usize))
+						.ok_or(Error::CannotCanonize)?;
+					self.update_address_utxos(&mut modified_utxos, &output, |utxos| utxos.retain(|utxo| utxo != prevout));
+				}
+			}
+
+			for (index, output) in tx.raw.outputs.iter().enumerate() {
+				let out_point = OutPoint { hash: tx.hash.clone(), index: index as u32 };
+				self.update_address_utxos(&mut modified_utxos, output, |utxos| utxos.push(out_point.clone()));
+			}
+		}
+		Self::write_address_utxos(&mut update, modified_utxos);
+
 		self.db.write(update).map_err(Error::DatabaseError)?;
 		*best_block = new_best_block;
 		Ok(())
@@ -365,6 +435,25 @@ impl<T> BlockChainDatabase<T> where T: KeyValueDatabase {
 			update.insert(KeyValue::TransactionMeta(hash, meta));
 		}
 
+		let mut modified_utxos = HashMap::new();
+		for (tx_index, tx) in block.transactions.iter().enumerate().rev() {
+			for (index, output) in tx.raw.outputs.iter().enumerate() {
+				let out_point = OutPoint { hash: tx.hash.clone(), index: index as u32 };
+				self.update_address_utxos(&mut modified_utxos, output, |utxos| utxos.retain(|utxo| *utxo != out_point));
+			}
+
+			if tx_index != 0 {
+				for input in &tx.raw.inputs {
+					let prevout = &input.previous_output;
+					let output = self.transaction(&prevout.hash)
+						.and_then(|tx| tx.outputs.into_iter().nth(prevout.index as usize))
+						.ok_or(Error::CannotCanonize)?;
+					self.update_address_utxos(&mut modified_utxos, &output, |utxos| utxos.push(prevout.clone()));
+				}
+			}
+		}
+		Self::write_address_utxos(&mut update, modified_utxos);
+
 		for tx in block.transactions {
 			update.delete(Key::TransactionMeta(tx.hash));
 		}
@@ -374,6 +463,32 @@ impl<T> BlockChainDatabase<T> where T: KeyValueDatabase {
 		Ok(block_hash)
 	}
 
+	fn address_utxos(&self, address: &H160) -> Vec<OutPoint> {
+		self.get(Key::AddressUtxos(address.clone()))
+			.and_then(Value::as_address_utxos)
+			.map(List::into)
+			.unwrap_or_default()
+	}
+
+	/// Applies `update` to unspent outputs of every address `output` pays to
+	fn update_address_utxos<F>(&self, modified: &mut HashMap<H160, Vec<OutPoint>>, output: &TransactionOutput, update: F)
+		where F: Fn(&mut Vec<OutPoint>) {
+		for address in output_address_hashes(output) {
+			let utxos = modified.entry(address.clone()).or_insert_with(|| self.address_utxos(&address));
+			update(utxos);
+		}
+	}
+
+	fn write_address_utxos(update: &mut DBTransaction, modified: HashMap<H160, Vec<OutPoint>>) {
+		for (address, utxos) in modified {
+			if utxos.is_empty() {
+				update.delete(Key::AddressUtxos(address));
+			} else {
+				update.insert(KeyValue::AddressUtxos(address, List::from(utxos)));
+			}
+		}
+	}
+
 	fn get(&self, key: Key) -> Option<Value> {
 		self.db.get(&key).expect("db value to be fine").into_option()
 	}
@@ -483,53 +598,9 @@ impl<T> TransactionMetaProvider for BlockChainDatabase<T> where T: KeyValueDatab
 	}
 }
 
-impl<T> BlockChainDatabase<T> where T: KeyValueDatabase {
-	fn transaction_utxo_for_block(&self, block_height: u32, address: &H160) -> Vec<OutPoint> {
-		let block = BlockRef::Number(block_height);
-		let transaction_hashes = self.block_transaction_hashes(block);
-		let address_bytes = address.clone().take();
-		let mut outputs: Vec<OutPoint> = vec![];
-		for hash in transaction_hashes.iter()	//TODO maybe rewrite it in iterator style
-		{
-			let meta = self.transaction_meta(&hash).unwrap();
-			if !meta.is_fully_spent()
-			{
-				let transaction = self.transaction(&hash).unwrap();
-				for (index, is_spent) in meta.bits.iter().skip(1).enumerate() //skip coinbase state bit
-				{
-					if !is_spent
-					{
-						//TODO somehow transaction meta contains redundant bits after being loaded from disk
-						if transaction.outputs.len() > index
-						{
-							let output = &transaction.outputs[index];
-							//TODO maybe using script here is redundant and its enough to compare plain bytes?
-							let ref script_bytes = output.script_pubkey;
-							let script: Script = script_bytes.clone().into();
-							let script_addresses = script.extract_destinations().unwrap_or(vec![]);
-							if script_addresses.iter().any(|address| address.hash[..] == address_bytes)
-							{
-								outputs.push( OutPoint { hash: hash.clone(), index: index as u32 } );
-							}
-						}
-					}
-				}
-			}
-		}
-		outputs
-	}
-}
-
-impl<T> TransactionUtxoProvider for BlockChainDatabase<T> where T: KeyValueDatabase {	
+impl<T> TransactionUtxoProvider for BlockChainDatabase<T> where T: KeyValueDatabase {
 	fn transaction_with_output_address(&self, address: &H160) -> Vec<OutPoint> {
-		let best_block = self.best_block();
-		let mut out_points: Vec<OutPoint> = vec![];
-		for block_number in (0..best_block.number + 1).rev() //add +1 instead of inclusive range, because they are considered unstable
-		{
-			let block_outpoints = self.transaction_utxo_for_block(block_number, address);
-			out_points.extend(block_outpoints);
-		}
-		out_points
+		self.address_utxos(address)
 	}
 }
 
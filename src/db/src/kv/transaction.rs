@@ -1,7 +1,7 @@
 use bytes::Bytes;
-use hash::H256;
+use hash::{H160, H256};
 use ser::{serialize, List, deserialize};
-use chain::{Transaction as ChainTransaction, BlockHeader};
+use chain::{Transaction as ChainTransaction, BlockHeader, OutPoint};
 use {TransactionMeta};
 
 pub const COL_COUNT: u32 = 10;
@@ -13,6 +13,7 @@ pub const COL_TRANSACTIONS: u32 = 4;
 pub const COL_TRANSACTIONS_META: u32 = 5;
 pub const COL_BLOCK_NUMBERS: u32 = 6;
 pub const COL_CONFIGURATION: u32 = 7;
+pub const COL_ADDRESS_UTXOS: u32 = 8;
 
 #[derive(Debug)]
 pub enum Operation {
@@ -30,6 +31,7 @@ pub enum KeyValue {
 	TransactionMeta(H256, TransactionMeta),
 	BlockNumber(H256, u32),
 	Configuration(&'static str, Bytes),
+	AddressUtxos(H160, List<OutPoint>),
 }
 
 #[derive(Debug)]
@@ -42,6 +44,7 @@ pub enum Key {
 	TransactionMeta(H256),
 	BlockNumber(H256),
 	Configuration(&'static str),
+	AddressUtxos(H160),
 }
 
 #[derive(Debug, Clone)]
@@ -54,6 +57,7 @@ pub enum Value {
 	TransactionMeta(TransactionMeta),
 	BlockNumber(u32),
 	Configuration(Bytes),
+	AddressUtxos(List<OutPoint>),
 }
 
 impl Value {
@@ -67,6 +71,7 @@ impl Value {
 			Key::TransactionMeta(_) => deserialize(bytes).map(Value::TransactionMeta),
 			Key::BlockNumber(_) => deserialize(bytes).map(Value::BlockNumber),
 			Key::Configuration(_) => deserialize(bytes).map(Value::Configuration),
+			Key::AddressUtxos(_) => deserialize(bytes).map(Value::AddressUtxos),
 		}.map_err(|e| format!("{:?}", e))
 	}
 
@@ -125,6 +130,13 @@ impl Value {
 			_ => None,
 		}
 	}
+
+	pub fn as_address_utxos(self) -> Option<List<OutPoint>> {
+		match self {
+			Value::AddressUtxos(list) => Some(list),
+			_ => None,
+		}
+	}
 }
 
 #[derive(Debug, Clone)]
@@ -228,6 +240,7 @@ impl<'a> From<&'a KeyValue> for RawKeyValue {
 			KeyValue::TransactionMeta(ref key, ref value) => (COL_TRANSACTIONS_META, serialize(key), serialize(value)),
 			KeyValue::BlockNumber(ref key, ref value) => (COL_BLOCK_NUMBERS, serialize(key), serialize(value)),
 			KeyValue::Configuration(ref key, ref value) => (COL_CONFIGURATION, serialize(key), serialize(value)),
+			KeyValue::AddressUtxos(ref key, ref value) => (COL_ADDRESS_UTXOS, serialize(key), serialize(value)),
 		};
 
 		RawKeyValue {
@@ -263,6 +276,7 @@ impl<'a> From<&'a Key> for RawKey {
 			Key::TransactionMeta(ref key) => (COL_TRANSACTIONS_META, serialize(key)),
 			Key::BlockNumber(ref key) => (COL_BLOCK_NUMBERS, serialize(key)),
 			Key::Configuration(ref key) => (COL_CONFIGURATION, serialize(key)),
+			Key::AddressUtxos(ref key) => (COL_ADDRESS_UTXOS, serialize(key)),
 		};
 
 		RawKey {
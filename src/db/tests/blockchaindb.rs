@@ -1,14 +1,16 @@
 extern crate chain;
 extern crate db;
 extern crate chain_builder;
+extern crate primitives;
 
-use chain::IndexedBlock;
+use chain::{IndexedBlock, OutPoint};
 use db::kv::{MemoryDatabase, SharedMemoryDatabase};
-use db::{BlockChainDatabase, BlockProvider, SideChainOrigin, ForkChain};
+use db::{BlockChainDatabase, BlockProvider, SideChainOrigin, ForkChain, TransactionUtxoProvider};
+use primitives::hash::H160;
 
 #[test]
 fn insert_block() {
-	let store = BlockChainDatabase::open(MemoryDatabase::default());
+	let store = BlockChainDatabase::open(MemoryDatabase::default()).unwrap();
 	let b0: IndexedBlock = chain_builder::block_h0().into();
 	let b1: IndexedBlock = chain_builder::block_h1().into();
 	let b2: IndexedBlock = chain_builder::block_h2().into();
@@ -54,7 +56,7 @@ fn reopen_db() {
 	let b2: IndexedBlock = chain_builder::block_h2().into();
 
 	{
-		let store = BlockChainDatabase::open(shared_database.clone());
+		let store = BlockChainDatabase::open(shared_database.clone()).unwrap();
 		store.insert(b0.clone()).unwrap();
 		store.insert(b1.clone()).unwrap();
 		store.insert(b2.clone()).unwrap();
@@ -66,7 +68,7 @@ fn reopen_db() {
 		store.decanonize().unwrap();
 	}
 	{
-		let store = BlockChainDatabase::open(shared_database);
+		let store = BlockChainDatabase::open(shared_database).unwrap();
 		assert_eq!(b0.hash(), &store.block_hash(0).unwrap());
 		assert_eq!(1, store.best_block().number);
 		assert_eq!(b1.hash(), &store.best_block().hash);
@@ -75,7 +77,7 @@ fn reopen_db() {
 
 #[test]
 fn switch_to_simple_fork() {
-	let store = BlockChainDatabase::open(MemoryDatabase::default());
+	let store = BlockChainDatabase::open(MemoryDatabase::default()).unwrap();
 	let b0: IndexedBlock = chain_builder::block_h0().into();
 	let b1: IndexedBlock = chain_builder::block_h1().into();
 	let b2: IndexedBlock = chain_builder::block_h2().into();
@@ -126,3 +128,48 @@ fn switch_to_simple_fork() {
 	assert_eq!(store.best_block().hash, store.block_hash(2).unwrap());
 
 }
+
+#[test]
+fn address_utxo_index() {
+	let store = BlockChainDatabase::open(MemoryDatabase::default()).unwrap();
+	let address1: H160 = "0000000000000000000000000000000000000001".into();
+	let address2: H160 = "0000000000000000000000000000000000000002".into();
+
+	let b0: IndexedBlock = chain_builder::block_builder()
+		.transaction().coinbase()
+			.output().value(50).script_pubkey("76a914000000000000000000000000000000000000000188ac").build()
+			.build()
+		.merkled_header().build()
+		.build()
+		.into();
+	let tx0 = b0.transactions[0].hash.clone();
+
+	let b1: IndexedBlock = chain_builder::block_builder()
+		.transaction().coinbase()
+			.output().value(50).build()
+			.build()
+		.transaction()
+			.input().hash(tx0.clone()).index(0).build()
+			.output().value(30).script_pubkey("76a914000000000000000000000000000000000000000288ac").build()
+			.output().value(20).script_pubkey("00140000000000000000000000000000000000000001").build()
+			.build()
+		.merkled_header().parent(b0.hash().clone()).build()
+		.build()
+		.into();
+	let tx1 = b1.transactions[1].hash.clone();
+
+	store.insert(b0.clone()).unwrap();
+	store.insert(b1.clone()).unwrap();
+
+	store.canonize(b0.hash()).unwrap();
+	assert_eq!(store.transaction_with_output_address(&address1), vec![OutPoint { hash: tx0.clone(), index: 0 }]);
+	assert!(store.transaction_with_output_address(&address2).is_empty());
+
+	store.canonize(b1.hash()).unwrap();
+	assert_eq!(store.transaction_with_output_address(&address1), vec![OutPoint { hash: tx1.clone(), index: 1 }]);
+	assert_eq!(store.transaction_with_output_address(&address2), vec![OutPoint { hash: tx1.clone(), index: 0 }]);
+
+	store.decanonize().unwrap();
+	assert_eq!(store.transaction_with_output_address(&address1), vec![OutPoint { hash: tx0, index: 0 }]);
+	assert!(store.transaction_with_output_address(&address2).is_empty());
+}
@@ -12,6 +12,9 @@ pub enum Error {
 	InvalidPrivate,
 	InvalidAddress,
 	FailedKeyGeneration,
+	InvalidExtendedKey,
+	InvalidDerivationPath,
+	InvalidMnemonic,
 }
 
 impl fmt::Display for Error {
@@ -26,6 +29,9 @@ impl fmt::Display for Error {
 			Error::InvalidPrivate => "Invalid Private",
 			Error::InvalidAddress => "Invalid Address",
 			Error::FailedKeyGeneration => "Key generation failed",
+			Error::InvalidExtendedKey => "Invalid Extended Key",
+			Error::InvalidDerivationPath => "Invalid Derivation Path",
+			Error::InvalidMnemonic => "Invalid Mnemonic",
 		};
 
 		msg.fmt(f)
@@ -55,6 +61,9 @@ impl error::Error for Error {
 			Error::InvalidPrivate => "Invalid Private",
 			Error::InvalidAddress => "Invalid Address",
 			Error::FailedKeyGeneration => "Key generation failed",
+			Error::InvalidExtendedKey => "Invalid Extended Key",
+			Error::InvalidDerivationPath => "Invalid Derivation Path",
+			Error::InvalidMnemonic => "Invalid Mnemonic",
 		}
 	}
 }
\ No newline at end of file
@@ -0,0 +1,558 @@
+//! Hierarchical deterministic keys.
+//!
+//! https://github.com/bitcoin/bips/blob/master/bip-0032.mediawiki
+
+use std::fmt;
+use std::str::FromStr;
+use secp256k1::key;
+use base58::{ToBase58, FromBase58};
+use crypto::{checksum, dhash160, hmac_sha512};
+use hash::{H32, H256, H264};
+use network::Network;
+use {DisplayLayout, Error, KeyPair, Private, Public, Secret, SECP256K1};
+
+/// Child numbers starting with this one are hardened
+pub const HARDENED_OFFSET: u32 = 0x8000_0000;
+
+/// Serialization version bytes of mainnet and testnet extended keys
+const XPRV_VERSION: u32 = 0x0488_ade4;
+const XPUB_VERSION: u32 = 0x0488_b21e;
+const TPRV_VERSION: u32 = 0x0435_8394;
+const TPUB_VERSION: u32 = 0x0435_87cf;
+
+/// Key used to derive master key from seed
+const MASTER_KEY_SALT: &'static [u8] = b"Bitcoin seed";
+
+/// Index of a child key
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ChildNumber {
+	/// Child which can be derived from parent public key
+	Normal(u32),
+	/// Child which can be derived only from parent private key
+	Hardened(u32),
+}
+
+impl ChildNumber {
+	pub fn is_hardened(&self) -> bool {
+		match *self {
+			ChildNumber::Normal(_) => false,
+			ChildNumber::Hardened(_) => true,
+		}
+	}
+
+	pub fn from_u32(number: u32) -> Self {
+		if number & HARDENED_OFFSET != 0 {
+			ChildNumber::Hardened(number ^ HARDENED_OFFSET)
+		} else {
+			ChildNumber::Normal(number)
+		}
+	}
+
+	pub fn to_u32(&self) -> u32 {
+		match *self {
+			ChildNumber::Normal(index) => index,
+			ChildNumber::Hardened(index) => index | HARDENED_OFFSET,
+		}
+	}
+}
+
+impl fmt::Display for ChildNumber {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			ChildNumber::Normal(index) => write!(f, "{}", index),
+			ChildNumber::Hardened(index) => write!(f, "{}'", index),
+		}
+	}
+}
+
+impl FromStr for ChildNumber {
+	type Err = Error;
+
+	fn from_str(s: &str) -> Result<Self, Error> {
+		let (index, hardened) = match s.chars().last() {
+			Some('\'') | Some('h') | Some('H') => (&s[..s.len() - 1], true),
+			_ => (s, false),
+		};
+
+		let index: u32 = try!(index.parse().map_err(|_| Error::InvalidDerivationPath));
+		match (index & HARDENED_OFFSET, hardened) {
+			(0, true) => Ok(ChildNumber::Hardened(index)),
+			(0, false) => Ok(ChildNumber::Normal(index)),
+			_ => Err(Error::InvalidDerivationPath),
+		}
+	}
+}
+
+/// Sequence of child numbers leading from master key to the derived one, eg: m/0'/1/2'
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct DerivationPath(Vec<ChildNumber>);
+
+impl DerivationPath {
+	pub fn child(&self, child: ChildNumber) -> DerivationPath {
+		let mut path = self.0.clone();
+		path.push(child);
+		DerivationPath(path)
+	}
+
+	pub fn as_slice(&self) -> &[ChildNumber] {
+		&self.0
+	}
+}
+
+impl From<Vec<ChildNumber>> for DerivationPath {
+	fn from(path: Vec<ChildNumber>) -> Self {
+		DerivationPath(path)
+	}
+}
+
+impl fmt::Display for DerivationPath {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		try!(f.write_str("m"));
+		for child in &self.0 {
+			try!(write!(f, "/{}", child));
+		}
+		Ok(())
+	}
+}
+
+impl FromStr for DerivationPath {
+	type Err = Error;
+
+	fn from_str(s: &str) -> Result<Self, Error> {
+		let mut parts = s.split('/');
+		if parts.next() != Some("m") {
+			return Err(Error::InvalidDerivationPath);
+		}
+
+		let path: Result<Vec<_>, _> = parts.map(str::parse).collect();
+		path.map(DerivationPath)
+	}
+}
+
+impl From<&'static str> for DerivationPath {
+	fn from(s: &'static str) -> Self {
+		s.parse().unwrap()
+	}
+}
+
+/// Extended private key
+#[derive(PartialEq, Clone)]
+pub struct ExtendedPrivate {
+	/// The network on which this key should be used.
+	pub network: Network,
+	/// Number of derivations made from master key.
+	pub depth: u8,
+	/// First 4 bytes of parent public key hash.
+	pub parent_fingerprint: H32,
+	/// Index of this key in parent's children.
+	pub child_number: ChildNumber,
+	/// Entropy added to every derivation.
+	pub chain_code: H256,
+	/// ECDSA key.
+	pub secret: Secret,
+}
+
+/// Extended public key
+#[derive(PartialEq, Clone)]
+pub struct ExtendedPublic {
+	/// The network on which this key should be used.
+	pub network: Network,
+	/// Number of derivations made from master key.
+	pub depth: u8,
+	/// First 4 bytes of parent public key hash.
+	pub parent_fingerprint: H32,
+	/// Index of this key in parent's children.
+	pub child_number: ChildNumber,
+	/// Entropy added to every derivation.
+	pub chain_code: H256,
+	/// Compressed public key.
+	pub public: H264,
+}
+
+/// Splits HMAC-SHA512 result into tweak and chain code
+fn derivation_hmac(key: &[u8], data: &[u8]) -> Result<(key::SecretKey, H256), Error> {
+	let context = &SECP256K1;
+	let result = hmac_sha512(key, data);
+	let tweak = try!(key::SecretKey::from_slice(context, &result[0..32]));
+	let mut chain_code = H256::default();
+	chain_code.copy_from_slice(&result[32..64]);
+	Ok((tweak, chain_code))
+}
+
+fn compressed_public(secret: &key::SecretKey) -> Result<H264, Error> {
+	let context = &SECP256K1;
+	let public = try!(key::PublicKey::from_secret_key(context, secret));
+	let mut result = H264::default();
+	result.copy_from_slice(&public.serialize_vec(context, true)[0..33]);
+	Ok(result)
+}
+
+fn fingerprint(public: &H264) -> H32 {
+	let mut result = H32::default();
+	result.copy_from_slice(&dhash160(&**public)[0..4]);
+	result
+}
+
+impl ExtendedPrivate {
+	/// Creates master key from seed
+	pub fn from_seed(seed: &[u8], network: Network) -> Result<Self, Error> {
+		let (secret, chain_code) = try!(derivation_hmac(MASTER_KEY_SALT, seed));
+		let mut master_secret = Secret::default();
+		master_secret.copy_from_slice(&secret[0..32]);
+
+		Ok(ExtendedPrivate {
+			network: network,
+			depth: 0,
+			parent_fingerprint: H32::default(),
+			child_number: ChildNumber::Normal(0),
+			chain_code: chain_code,
+			secret: master_secret,
+		})
+	}
+
+	pub fn derive_child(&self, child: ChildNumber) -> Result<Self, Error> {
+		let context = &SECP256K1;
+		let mut secret = try!(key::SecretKey::from_slice(context, &*self.secret));
+		let public = try!(compressed_public(&secret));
+
+		let mut data = Vec::with_capacity(37);
+		match child {
+			ChildNumber::Hardened(_) => {
+				data.push(0);
+				data.extend_from_slice(&*self.secret);
+			},
+			ChildNumber::Normal(_) => data.extend_from_slice(&*public),
+		}
+		data.extend_from_slice(&u32_to_be(child.to_u32()));
+
+		let (tweak, chain_code) = try!(derivation_hmac(&*self.chain_code, &data));
+		try!(secret.add_assign(context, &tweak));
+		let mut child_secret = Secret::default();
+		child_secret.copy_from_slice(&secret[0..32]);
+
+		Ok(ExtendedPrivate {
+			network: self.network,
+			depth: try!(self.depth.checked_add(1).ok_or(Error::InvalidDerivationPath)),
+			parent_fingerprint: fingerprint(&public),
+			child_number: child,
+			chain_code: chain_code,
+			secret: child_secret,
+		})
+	}
+
+	pub fn derive_path(&self, path: &DerivationPath) -> Result<Self, Error> {
+		path.as_slice().iter().fold(Ok(self.clone()), |key, child| key.and_then(|key| key.derive_child(*child)))
+	}
+
+	pub fn extended_public(&self) -> Result<ExtendedPublic, Error> {
+		let secret = try!(key::SecretKey::from_slice(&SECP256K1, &*self.secret));
+		Ok(ExtendedPublic {
+			network: self.network,
+			depth: self.depth,
+			parent_fingerprint: self.parent_fingerprint.clone(),
+			child_number: self.child_number,
+			chain_code: self.chain_code.clone(),
+			public: try!(compressed_public(&secret)),
+		})
+	}
+
+	pub fn private(&self) -> Private {
+		Private {
+			network: self.network,
+			secret: self.secret.clone(),
+			compressed: true,
+		}
+	}
+
+	pub fn keypair(&self) -> Result<KeyPair, Error> {
+		KeyPair::from_private(self.private())
+	}
+}
+
+impl ExtendedPublic {
+	/// Derives public child key. Hardened children can't be derived this way
+	pub fn derive_child(&self, child: ChildNumber) -> Result<Self, Error> {
+		if child.is_hardened() {
+			return Err(Error::InvalidDerivationPath);
+		}
+
+		let context = &SECP256K1;
+		let mut data = Vec::with_capacity(37);
+		data.extend_from_slice(&*self.public);
+		data.extend_from_slice(&u32_to_be(child.to_u32()));
+
+		let (tweak, chain_code) = try!(derivation_hmac(&*self.chain_code, &data));
+		let mut public = try!(key::PublicKey::from_slice(context, &*self.public));
+		try!(public.add_exp_assign(context, &tweak));
+		let mut child_public = H264::default();
+		child_public.copy_from_slice(&public.serialize_vec(context, true)[0..33]);
+
+		Ok(ExtendedPublic {
+			network: self.network,
+			depth: try!(self.depth.checked_add(1).ok_or(Error::InvalidDerivationPath)),
+			parent_fingerprint: fingerprint(&self.public),
+			child_number: child,
+			chain_code: chain_code,
+			public: child_public,
+		})
+	}
+
+	pub fn derive_path(&self, path: &DerivationPath) -> Result<Self, Error> {
+		path.as_slice().iter().fold(Ok(self.clone()), |key, child| key.and_then(|key| key.derive_child(*child)))
+	}
+
+	pub fn public(&self) -> Public {
+		Public::Compressed(self.public.clone())
+	}
+}
+
+fn u32_to_be(value: u32) -> [u8; 4] {
+	[(value >> 24) as u8, (value >> 16) as u8, (value >> 8) as u8, value as u8]
+}
+
+fn be_to_u32(data: &[u8]) -> u32 {
+	(data[0] as u32) << 24 | (data[1] as u32) << 16 | (data[2] as u32) << 8 | data[3] as u32
+}
+
+/// Serializes common part of extended keys. Key data is appended by caller
+fn layout_header(version: u32, depth: u8, parent_fingerprint: &H32, child_number: ChildNumber, chain_code: &H256) -> Vec<u8> {
+	let mut result = Vec::with_capacity(82);
+	result.extend_from_slice(&u32_to_be(version));
+	result.push(depth);
+	result.extend_from_slice(&**parent_fingerprint);
+	result.extend_from_slice(&u32_to_be(child_number.to_u32()));
+	result.extend_from_slice(&**chain_code);
+	result
+}
+
+/// Checks length and checksum of serialized extended key
+fn check_layout(data: &[u8]) -> Result<(), Error> {
+	if data.len() != 82 {
+		return Err(Error::InvalidExtendedKey);
+	}
+
+	let cs = checksum(&data[0..78]);
+	if &data[78..] != &*cs {
+		return Err(Error::InvalidChecksum);
+	}
+
+	Ok(())
+}
+
+impl DisplayLayout for ExtendedPrivate {
+	type Target = Vec<u8>;
+
+	fn layout(&self) -> Self::Target {
+		let version = match self.network {
+			Network::Mainnet => XPRV_VERSION,
+			Network::Testnet => TPRV_VERSION,
+		};
+
+		let mut result = layout_header(version, self.depth, &self.parent_fingerprint, self.child_number, &self.chain_code);
+		result.push(0);
+		result.extend_from_slice(&*self.secret);
+		let cs = checksum(&result);
+		result.extend_from_slice(&*cs);
+		result
+	}
+
+	fn from_layout(data: &[u8]) -> Result<Self, Error> where Self: Sized {
+		try!(check_layout(data));
+
+		let network = match be_to_u32(&data[0..4]) {
+			XPRV_VERSION => Network::Mainnet,
+			TPRV_VERSION => Network::Testnet,
+			_ => return Err(Error::InvalidExtendedKey),
+		};
+
+		if data[45] != 0 {
+			return Err(Error::InvalidExtendedKey);
+		}
+
+		let mut secret = Secret::default();
+		secret.copy_from_slice(&data[46..78]);
+		try!(key::SecretKey::from_slice(&SECP256K1, &*secret));
+
+		let mut parent_fingerprint = H32::default();
+		parent_fingerprint.copy_from_slice(&data[5..9]);
+		let mut chain_code = H256::default();
+		chain_code.copy_from_slice(&data[13..45]);
+
+		Ok(ExtendedPrivate {
+			network: network,
+			depth: data[4],
+			parent_fingerprint: parent_fingerprint,
+			child_number: ChildNumber::from_u32(be_to_u32(&data[9..13])),
+			chain_code: chain_code,
+			secret: secret,
+		})
+	}
+}
+
+impl DisplayLayout for ExtendedPublic {
+	type Target = Vec<u8>;
+
+	fn layout(&self) -> Self::Target {
+		let version = match self.network {
+			Network::Mainnet => XPUB_VERSION,
+			Network::Testnet => TPUB_VERSION,
+		};
+
+		let mut result = layout_header(version, self.depth, &self.parent_fingerprint, self.child_number, &self.chain_code);
+		result.extend_from_slice(&*self.public);
+		let cs = checksum(&result);
+		result.extend_from_slice(&*cs);
+		result
+	}
+
+	fn from_layout(data: &[u8]) -> Result<Self, Error> where Self: Sized {
+		try!(check_layout(data));
+
+		let network = match be_to_u32(&data[0..4]) {
+			XPUB_VERSION => Network::Mainnet,
+			TPUB_VERSION => Network::Testnet,
+			_ => return Err(Error::InvalidExtendedKey),
+		};
+
+		let mut public = H264::default();
+		public.copy_from_slice(&data[45..78]);
+		try!(key::PublicKey::from_slice(&SECP256K1, &*public));
+
+		let mut parent_fingerprint = H32::default();
+		parent_fingerprint.copy_from_slice(&data[5..9]);
+		let mut chain_code = H256::default();
+		chain_code.copy_from_slice(&data[13..45]);
+
+		Ok(ExtendedPublic {
+			network: network,
+			depth: data[4],
+			parent_fingerprint: parent_fingerprint,
+			child_number: ChildNumber::from_u32(be_to_u32(&data[9..13])),
+			chain_code: chain_code,
+			public: public,
+		})
+	}
+}
+
+impl fmt::Debug for ExtendedPrivate {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		try!(writeln!(f, "network: {:?}", self.network));
+		try!(writeln!(f, "depth: {}", self.depth));
+		writeln!(f, "child number: {}", self.child_number)
+	}
+}
+
+impl fmt::Debug for ExtendedPublic {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		self.layout().to_base58().fmt(f)
+	}
+}
+
+impl fmt::Display for ExtendedPrivate {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		self.layout().to_base58().fmt(f)
+	}
+}
+
+impl fmt::Display for ExtendedPublic {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		self.layout().to_base58().fmt(f)
+	}
+}
+
+impl FromStr for ExtendedPrivate {
+	type Err = Error;
+
+	fn from_str(s: &str) -> Result<Self, Error> where Self: Sized {
+		let data = try!(s.from_base58().map_err(|_| Error::InvalidExtendedKey));
+		ExtendedPrivate::from_layout(&data)
+	}
+}
+
+impl FromStr for ExtendedPublic {
+	type Err = Error;
+
+	fn from_str(s: &str) -> Result<Self, Error> where Self: Sized {
+		let data = try!(s.from_base58().map_err(|_| Error::InvalidExtendedKey));
+		ExtendedPublic::from_layout(&data)
+	}
+}
+
+impl From<&'static str> for ExtendedPrivate {
+	fn from(s: &'static str) -> Self {
+		s.parse().unwrap()
+	}
+}
+
+impl From<&'static str> for ExtendedPublic {
+	fn from(s: &'static str) -> Self {
+		s.parse().unwrap()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use bytes::Bytes;
+	use network::Network;
+	use super::{ExtendedPrivate, ExtendedPublic, DerivationPath, ChildNumber};
+
+	/// Test vector 1 from:
+	/// https://github.com/bitcoin/bips/blob/master/bip-0032.mediawiki#test-vectors
+	const SEED: &'static str = "000102030405060708090a0b0c0d0e0f";
+	const XPRV_M: &'static str = "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi";
+	const XPUB_M: &'static str = "xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8";
+	const XPRV_0H: &'static str = "xprv9uHRZZhk6KAJC1avXpDAp4MDc3sQKNxDiPvvkX8Br5ngLNv1TxvUxt4cV1rGL5hj6KCesnDYUhd7oWgT11eZG7XnxHrnYeSvkzY7d2bhkJ7";
+	const XPUB_0H: &'static str = "xpub68Gmy5EdvgibQVfPdqkBBCHxA5htiqg55crXYuXoQRKfDBFA1WEjWgP6LHhwBZeNK1VTsfTFUHCdrfp1bgwQ9xv5ski8PX9rL2dZXvgGDnw";
+	const XPUB_0H_1: &'static str = "xpub6ASuArnXKPbfEwhqN6e3mwBcDTgzisQN1wXN9BJcM47sSikHjJf3UFHKkNAWbWMiGj7Wf5uMash7SyYq527Hqck2AxYysAA7xmALppuCkwQ";
+	const XPRV_LAST: &'static str = "xprvA41z7zogVVwxVSgdKUHDy1SKmdb533PjDz7J6N6mV6uS3ze1ai8FHa8kmHScGpWmj4WggLyQjgPie1rFSruoUihUZREPSL39UNdE3BBDu76";
+	const XPUB_LAST: &'static str = "xpub6H1LXWLaKsWFhvm6RVpEL9P4KfRZSW7abD2ttkWP3SSQvnyA8FSVqNTEcYFgJS2UaFcxupHiYkro49S8yGasTvXEYBVPamhGW6cFJodrTHy";
+
+	fn master() -> ExtendedPrivate {
+		let seed: Bytes = SEED.into();
+		ExtendedPrivate::from_seed(&seed, Network::Mainnet).unwrap()
+	}
+
+	#[test]
+	fn test_master_from_seed() {
+		let master = master();
+		assert_eq!(master.to_string(), XPRV_M);
+		assert_eq!(master.extended_public().unwrap().to_string(), XPUB_M);
+	}
+
+	#[test]
+	fn test_extended_keys_from_str() {
+		assert_eq!(ExtendedPrivate::from(XPRV_M), master());
+		assert_eq!(ExtendedPublic::from(XPUB_0H).to_string(), XPUB_0H);
+		assert!("xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet9".parse::<ExtendedPublic>().is_err());
+	}
+
+	#[test]
+	fn test_derive_hardened_child() {
+		let child = master().derive_child(ChildNumber::Hardened(0)).unwrap();
+		assert_eq!(child.to_string(), XPRV_0H);
+		assert_eq!(child.extended_public().unwrap().to_string(), XPUB_0H);
+	}
+
+	#[test]
+	fn test_derive_path() {
+		let key = master().derive_path(&"m/0'/1/2'/2/1000000000".into()).unwrap();
+		assert_eq!(key.to_string(), XPRV_LAST);
+		assert_eq!(key.extended_public().unwrap().to_string(), XPUB_LAST);
+	}
+
+	#[test]
+	fn test_derive_public_child() {
+		let public = ExtendedPublic::from(XPUB_0H);
+		assert_eq!(public.derive_child(ChildNumber::Normal(1)).unwrap().to_string(), XPUB_0H_1);
+		assert!(public.derive_child(ChildNumber::Hardened(1)).is_err());
+	}
+
+	#[test]
+	fn test_derivation_path_from_str() {
+		let path: DerivationPath = "m/0'/1/2h".parse().unwrap();
+		assert_eq!(path, vec![ChildNumber::Hardened(0), ChildNumber::Normal(1), ChildNumber::Hardened(2)].into());
+		assert_eq!(path.to_string(), "m/0'/1/2'");
+		assert!("0/1".parse::<DerivationPath>().is_err());
+		assert!("m/2147483648".parse::<DerivationPath>().is_err());
+	}
+}
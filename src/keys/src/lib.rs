@@ -14,9 +14,13 @@ mod address;
 mod display;
 mod keypair;
 mod error;
+mod extended;
+mod message;
+mod mnemonic;
 pub mod network;
 mod private;
 mod public;
+mod segwit_address;
 mod signature;
 
 pub use rustc_serialize::hex;
@@ -25,9 +29,13 @@ pub use primitives::{hash, bytes};
 pub use address::{Type, Address};
 pub use display::DisplayLayout;
 pub use keypair::KeyPair;
+pub use message::{message_hash, sign_message, verify_message};
+pub use mnemonic::Mnemonic;
 pub use error::Error;
+pub use extended::{ExtendedPrivate, ExtendedPublic, ChildNumber, DerivationPath, HARDENED_OFFSET};
 pub use private::Private;
 pub use public::Public;
+pub use segwit_address::SegwitAddress;
 pub use signature::{Signature, CompactSignature};
 pub use network::Network;
 
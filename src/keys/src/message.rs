@@ -0,0 +1,91 @@
+//! Signed messages proving ownership of address
+//!
+//! Text is prefixed with magic string, the same way "Bitcoin Signed Message" does, so
+//! signature can't be reused as transaction signature. Double sha256 of the result is
+//! signed with recoverable signature, which is shown in base64. Verifier recovers public
+//! key from signature and compares its hash with address.
+
+use rustc_serialize::base64::{ToBase64, FromBase64, STANDARD};
+use crypto::dhash256;
+use hash::H520;
+use {Address, CompactSignature, Error, Message, Private, Public, Type};
+
+const MESSAGE_MAGIC: &'static str = "Rustheus Signed Message:\n";
+
+/// Appends length of `data` as compact integer followed by `data` itself
+fn append_with_length(buffer: &mut Vec<u8>, data: &[u8]) {
+	let len = data.len();
+	if len < 0xfd {
+		buffer.push(len as u8);
+	} else if len <= 0xffff {
+		buffer.push(0xfd);
+		buffer.extend_from_slice(&[len as u8, (len >> 8) as u8]);
+	} else {
+		buffer.push(0xfe);
+		buffer.extend_from_slice(&[len as u8, (len >> 8) as u8, (len >> 16) as u8, (len >> 24) as u8]);
+	}
+	buffer.extend_from_slice(data);
+}
+
+/// Hash which is signed to prove ownership of address
+pub fn message_hash(message: &str) -> Message {
+	let mut data = vec![];
+	append_with_length(&mut data, MESSAGE_MAGIC.as_bytes());
+	append_with_length(&mut data, message.as_bytes());
+	dhash256(&data)
+}
+
+/// Signs `message` with `private` key. Returns base64 encoded compact signature
+pub fn sign_message(private: &Private, message: &str) -> Result<String, Error> {
+	let signature = try!(private.sign_compact(&message_hash(message)));
+	Ok(signature.to_base64(STANDARD))
+}
+
+/// Checks that base64 encoded `signature` of `message` is made by key of P2PKH `address`
+pub fn verify_message(address: &Address, signature: &str, message: &str) -> Result<bool, Error> {
+	if address.kind != Type::P2PKH {
+		return Err(Error::InvalidAddress);
+	}
+
+	let signature = try!(signature.from_base64().map_err(|_| Error::InvalidSignature));
+	// header is 27 + recovery id, plus 4 for compressed public key
+	if signature.len() != 65 || signature[0] < 27 || signature[0] > 34 {
+		return Err(Error::InvalidSignature);
+	}
+
+	let signature: CompactSignature = H520::from(&signature[..]).into();
+	match Public::recover_compact(&message_hash(message), &signature) {
+		Ok(public) => Ok(public.address_hash() == address.hash),
+		Err(_) => Ok(false),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use {Address, Error, KeyPair};
+	use super::{message_hash, sign_message, verify_message};
+
+	#[test]
+	fn test_message_hash() {
+		assert_eq!(message_hash("hello"), "69d8f131f1990ba41e8491ea193134cf5699358e3f6fef3223d0815d62402c97".into());
+	}
+
+	#[test]
+	fn test_sign_and_verify_message() {
+		let keypair = KeyPair::from_private("KwDiBf89QgGbjEhKnhXJuH7LrciVrZi3qYjgd9M7rFU73sVHnoWn".into()).unwrap();
+		let address = keypair.address();
+		let other: Address = "1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa".into();
+
+		let signature = sign_message(keypair.private(), "I own this address").unwrap();
+		assert_eq!(verify_message(&address, &signature, "I own this address"), Ok(true));
+		assert_eq!(verify_message(&address, &signature, "I own that address"), Ok(false));
+		assert_eq!(verify_message(&other, &signature, "I own this address"), Ok(false));
+	}
+
+	#[test]
+	fn test_verify_message_malformed_signature() {
+		let address: Address = "1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa".into();
+		assert_eq!(verify_message(&address, "not base64!", "text"), Err(Error::InvalidSignature));
+		assert_eq!(verify_message(&address, "AAAA", "text"), Err(Error::InvalidSignature));
+	}
+}
@@ -0,0 +1,178 @@
+//! Mnemonic code for generating deterministic keys.
+//!
+//! https://github.com/bitcoin/bips/blob/master/bip-0039.mediawiki
+
+use std::fmt;
+use std::str::FromStr;
+use crypto::{sha256, pbkdf2_sha512};
+use hash::H512;
+use generator::Random;
+use Error;
+
+/// Number of PBKDF2 rounds used to stretch mnemonic into seed
+const PBKDF2_ROUNDS: u32 = 2048;
+
+/// Every word encodes 11 bits
+const BITS_PER_WORD: usize = 11;
+
+lazy_static! {
+	static ref WORDLIST: Vec<&'static str> = include_str!("english.txt").split_whitespace().collect();
+}
+
+/// Sequence of words from english wordlist encoding entropy and its checksum
+#[derive(Debug, PartialEq, Clone)]
+pub struct Mnemonic {
+	/// Indexes of words in wordlist
+	indexes: Vec<u16>,
+}
+
+impl Mnemonic {
+	/// Generates mnemonic with `word_count` words. Valid counts are 12, 15, 18, 21 and 24
+	pub fn generate(word_count: usize) -> Result<Self, Error> {
+		if word_count < 12 || word_count > 24 || word_count % 3 != 0 {
+			return Err(Error::InvalidMnemonic);
+		}
+
+		let mut entropy = vec![0u8; word_count / 3 * 4];
+		try!(Random::generate_bytes(&mut entropy));
+		Mnemonic::from_entropy(&entropy)
+	}
+
+	/// Creates mnemonic encoding given entropy. Entropy must be 16 to 32 bytes long and a multiple of 4 bytes
+	pub fn from_entropy(entropy: &[u8]) -> Result<Self, Error> {
+		if entropy.len() < 16 || entropy.len() > 32 || entropy.len() % 4 != 0 {
+			return Err(Error::InvalidMnemonic);
+		}
+
+		let checksum = sha256(entropy);
+		let mut data = entropy.to_vec();
+		data.push(checksum[0]);
+
+		let word_count = entropy.len() * 8 * 33 / 32 / BITS_PER_WORD;
+		let indexes = (0..word_count)
+			.map(|word| (0..BITS_PER_WORD).fold(0u16, |index, bit| (index << 1) | bit_at(&data, word * BITS_PER_WORD + bit)))
+			.collect();
+
+		Ok(Mnemonic {
+			indexes: indexes,
+		})
+	}
+
+	/// Entropy encoded by this mnemonic
+	pub fn entropy(&self) -> Vec<u8> {
+		let entropy_bits = self.indexes.len() * BITS_PER_WORD * 32 / 33;
+		let mut entropy = vec![0u8; entropy_bits / 8];
+		for bit in 0..entropy_bits {
+			let index = self.indexes[bit / BITS_PER_WORD];
+			if index & (1 << (BITS_PER_WORD - 1 - bit % BITS_PER_WORD)) != 0 {
+				entropy[bit / 8] |= 1 << (7 - bit % 8);
+			}
+		}
+		entropy
+	}
+
+	pub fn words(&self) -> Vec<&'static str> {
+		self.indexes.iter().map(|index| WORDLIST[*index as usize]).collect()
+	}
+
+	/// Stretches mnemonic into seed for hierarchical deterministic keys
+	pub fn to_seed(&self, passphrase: &str) -> H512 {
+		let mut seed = H512::default();
+		let salt = format!("mnemonic{}", passphrase);
+		pbkdf2_sha512(self.to_string().as_bytes(), salt.as_bytes(), PBKDF2_ROUNDS, &mut *seed);
+		seed
+	}
+}
+
+fn bit_at(data: &[u8], bit: usize) -> u16 {
+	((data[bit / 8] >> (7 - bit % 8)) & 1) as u16
+}
+
+impl fmt::Display for Mnemonic {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		self.words().join(" ").fmt(f)
+	}
+}
+
+impl FromStr for Mnemonic {
+	type Err = Error;
+
+	/// Parses space separated words and validates their checksum
+	fn from_str(s: &str) -> Result<Self, Error> {
+		let indexes: Vec<u16> = try!(s.split_whitespace()
+			.map(|word| word.to_lowercase())
+			.map(|word| WORDLIST.binary_search_by(|probe| (*probe).cmp(word.as_str()))
+				.map(|index| index as u16)
+				.map_err(|_| Error::InvalidMnemonic))
+			.collect());
+
+		if indexes.len() < 12 || indexes.len() > 24 || indexes.len() % 3 != 0 {
+			return Err(Error::InvalidMnemonic);
+		}
+
+		let mnemonic = Mnemonic {
+			indexes: indexes,
+		};
+
+		// recreating mnemonic from its entropy recalculates checksum
+		if try!(Mnemonic::from_entropy(&mnemonic.entropy())) != mnemonic {
+			return Err(Error::InvalidChecksum);
+		}
+
+		Ok(mnemonic)
+	}
+}
+
+impl From<&'static str> for Mnemonic {
+	fn from(s: &'static str) -> Self {
+		s.parse().unwrap()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use bytes::Bytes;
+	use hash::H512;
+	use Error;
+	use super::Mnemonic;
+
+	/// Test vectors from:
+	/// https://github.com/trezor/python-mnemonic/blob/master/vectors.json
+	#[test]
+	fn test_mnemonic_from_entropy() {
+		let entropy: Bytes = "00000000000000000000000000000000".into();
+		let mnemonic = Mnemonic::from_entropy(&entropy).unwrap();
+		assert_eq!(mnemonic.to_string(), "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about");
+
+		let entropy: Bytes = "8080808080808080808080808080808080808080808080808080808080808080".into();
+		let mnemonic = Mnemonic::from_entropy(&entropy).unwrap();
+		assert_eq!(mnemonic.to_string(), "letter advice cage absurd amount doctor acoustic avoid letter advice cage absurd amount doctor acoustic avoid letter advice cage absurd amount doctor acoustic bless");
+		assert_eq!(mnemonic.entropy(), &*entropy);
+	}
+
+	#[test]
+	fn test_mnemonic_to_seed() {
+		let mnemonic = Mnemonic::from("abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about");
+		let expected: H512 = "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04".into();
+		assert_eq!(mnemonic.to_seed("TREZOR"), expected);
+
+		let mnemonic = Mnemonic::from("legal winner thank year wave sausage worth useful legal winner thank yellow");
+		let expected: H512 = "2e8905819b8723fe2c1d161860e5ee1830318dbf49a83bd451cfb8440c28bd6fa457fe1296106559a3c80937a1c1069be3a3a5bd381ee6260e8d9739fce1f607".into();
+		assert_eq!(mnemonic.to_seed("TREZOR"), expected);
+	}
+
+	#[test]
+	fn test_mnemonic_from_str_errors() {
+		assert_eq!("abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon".parse::<Mnemonic>(), Err(Error::InvalidChecksum));
+		assert_eq!("abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon notaword".parse::<Mnemonic>(), Err(Error::InvalidMnemonic));
+		assert_eq!("abandon about".parse::<Mnemonic>(), Err(Error::InvalidMnemonic));
+	}
+
+	#[test]
+	fn test_generate_mnemonic() {
+		let mnemonic = Mnemonic::generate(24).unwrap();
+		assert_eq!(mnemonic.words().len(), 24);
+		assert_eq!(mnemonic.to_string().parse::<Mnemonic>(), Ok(mnemonic));
+		assert!(Mnemonic::generate(13).is_err());
+	}
+}
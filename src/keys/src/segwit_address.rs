@@ -0,0 +1,214 @@
+//! Native segwit address of witness version 0
+//!
+//! Witness program is encoded in bech32 format with human readable part depending on network,
+//! eg: bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4.
+//!
+//! https://github.com/bitcoin/bips/blob/master/bip-0173.mediawiki
+
+use std::fmt;
+use std::str::FromStr;
+use bytes::Bytes;
+use hash::H256;
+use network::Network;
+use {Error, AddressHash};
+
+const CHARSET: &'static [u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+const GENERATOR: [u32; 5] = [0x3b6a57b2, 0x26508e6d, 0x1ea119fa, 0x3d4233dd, 0x2a1462b3];
+const CHECKSUM_SIZE: usize = 6;
+const MAX_LENGTH: usize = 90;
+
+/// Witness program of version 0 with network identifier
+#[derive(Debug, PartialEq, Clone)]
+pub struct SegwitAddress {
+	/// The network of the address.
+	pub network: Network,
+	/// Public key hash for P2WPKH or script hash for P2WSH.
+	pub program: Bytes,
+}
+
+impl SegwitAddress {
+	/// Pay to witness public key hash address
+	pub fn p2wpkh(network: Network, hash: &AddressHash) -> Self {
+		SegwitAddress {
+			network: network,
+			program: hash[..].into(),
+		}
+	}
+
+	/// Pay to witness script hash address. `hash` is sha256 of witness script
+	pub fn p2wsh(network: Network, hash: &H256) -> Self {
+		SegwitAddress {
+			network: network,
+			program: hash[..].into(),
+		}
+	}
+
+	fn hrp(network: Network) -> &'static str {
+		match network {
+			Network::Mainnet => "bc",
+			Network::Testnet => "tb",
+		}
+	}
+}
+
+fn polymod(values: &[u8]) -> u32 {
+	values.iter().fold(1u32, |checksum, value| {
+		let top = checksum >> 25;
+		let checksum = ((checksum & 0x1ffffff) << 5) ^ (*value as u32);
+		GENERATOR.iter().enumerate()
+			.filter(|&(i, _)| (top >> i) & 1 == 1)
+			.fold(checksum, |checksum, (_, generator)| checksum ^ generator)
+	})
+}
+
+fn hrp_expand(hrp: &str) -> Vec<u8> {
+	let mut result: Vec<u8> = hrp.bytes().map(|byte| byte >> 5).collect();
+	result.push(0);
+	result.extend(hrp.bytes().map(|byte| byte & 31));
+	result
+}
+
+fn create_checksum(hrp: &str, data: &[u8]) -> Vec<u8> {
+	let mut values = hrp_expand(hrp);
+	values.extend_from_slice(data);
+	values.extend_from_slice(&[0u8; CHECKSUM_SIZE]);
+	let checksum = polymod(&values) ^ 1;
+	(0..CHECKSUM_SIZE).map(|i| ((checksum >> (5 * (5 - i))) & 31) as u8).collect()
+}
+
+/// Regroups bits of `data` from `from` bits per item to `to` bits per item
+fn convert_bits(data: &[u8], from: u32, to: u32, pad: bool) -> Result<Vec<u8>, Error> {
+	let mut accumulator = 0u32;
+	let mut bits = 0u32;
+	let max_value = (1u32 << to) - 1;
+	let mut result = vec![];
+	for value in data {
+		let value = *value as u32;
+		if value >> from != 0 {
+			return Err(Error::InvalidAddress);
+		}
+		accumulator = (accumulator << from) | value;
+		bits += from;
+		while bits >= to {
+			bits -= to;
+			result.push(((accumulator >> bits) & max_value) as u8);
+		}
+	}
+
+	if pad {
+		if bits > 0 {
+			result.push(((accumulator << (to - bits)) & max_value) as u8);
+		}
+	} else if bits >= from || ((accumulator << (to - bits)) & max_value) != 0 {
+		return Err(Error::InvalidAddress);
+	}
+	Ok(result)
+}
+
+impl fmt::Display for SegwitAddress {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let hrp = Self::hrp(self.network);
+		let mut data = vec![0u8];
+		data.extend(convert_bits(&self.program, 8, 5, true).expect("bytes always fit into 8 bits; qed"));
+		let checksum = create_checksum(hrp, &data);
+
+		let encoded: String = data.iter()
+			.chain(checksum.iter())
+			.map(|value| CHARSET[*value as usize] as char)
+			.collect();
+		format!("{}1{}", hrp, encoded).fmt(f)
+	}
+}
+
+impl FromStr for SegwitAddress {
+	type Err = Error;
+
+	fn from_str(s: &str) -> Result<Self, Error> where Self: Sized {
+		if s.len() > MAX_LENGTH || s.bytes().any(|byte| byte < 33 || byte > 126) {
+			return Err(Error::InvalidAddress);
+		}
+		let lowercase = s.to_lowercase();
+		if lowercase != s && s.to_uppercase() != s {
+			return Err(Error::InvalidAddress);
+		}
+
+		let separator = match lowercase.rfind('1') {
+			Some(separator) if separator > 0 && separator + CHECKSUM_SIZE < lowercase.len() => separator,
+			_ => return Err(Error::InvalidAddress),
+		};
+		let (hrp, encoded) = lowercase.split_at(separator);
+		let network = match hrp {
+			"bc" => Network::Mainnet,
+			"tb" => Network::Testnet,
+			_ => return Err(Error::InvalidNetwork),
+		};
+
+		let data: Vec<u8> = try!(encoded[1..].bytes()
+			.map(|byte| CHARSET.iter().position(|c| *c == byte).map(|value| value as u8).ok_or(Error::InvalidAddress))
+			.collect());
+
+		let mut values = hrp_expand(hrp);
+		values.extend_from_slice(&data);
+		if polymod(&values) != 1 {
+			return Err(Error::InvalidChecksum);
+		}
+
+		let data = &data[..data.len() - CHECKSUM_SIZE];
+		// only witness version 0 is supported
+		if data.is_empty() || data[0] != 0 {
+			return Err(Error::InvalidAddress);
+		}
+
+		let program = try!(convert_bits(&data[1..], 5, 8, false));
+		if program.len() != 20 && program.len() != 32 {
+			return Err(Error::InvalidAddress);
+		}
+
+		Ok(SegwitAddress {
+			network: network,
+			program: program.into(),
+		})
+	}
+}
+
+impl From<&'static str> for SegwitAddress {
+	fn from(s: &'static str) -> Self {
+		s.parse().unwrap()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use network::Network;
+	use Error;
+	use super::SegwitAddress;
+
+	/// Test vectors from:
+	/// https://github.com/bitcoin/bips/blob/master/bip-0173.mediawiki#test-vectors
+	#[test]
+	fn test_segwit_address_from_str() {
+		let address: SegwitAddress = "BC1QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7KV8F3T4".parse().unwrap();
+		assert_eq!(address, SegwitAddress::p2wpkh(Network::Mainnet, &"751e76e8199196d454941c45d1b3a323f1433bd6".into()));
+
+		let address: SegwitAddress = "tb1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3q0sl5k7".parse().unwrap();
+		assert_eq!(address, SegwitAddress::p2wsh(Network::Testnet, &"1863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262".into()));
+	}
+
+	#[test]
+	fn test_segwit_address_to_string() {
+		let address = SegwitAddress::p2wpkh(Network::Mainnet, &"751e76e8199196d454941c45d1b3a323f1433bd6".into());
+		assert_eq!(address.to_string(), "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4");
+
+		let address = SegwitAddress::p2wsh(Network::Testnet, &"000000c4a5cad46221b2a187905e5266362b99d5e91c6ce24d165dab93e86433".into());
+		assert_eq!(address.to_string(), "tb1qqqqqp399et2xygdj5xreqhjjvcmzhxw4aywxecjdzew6hylgvsesrxh6hy");
+	}
+
+	#[test]
+	fn test_segwit_address_errors() {
+		assert_eq!("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t5".parse::<SegwitAddress>(), Err(Error::InvalidChecksum));
+		assert_eq!("tb1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3q0sL5k7".parse::<SegwitAddress>(), Err(Error::InvalidAddress));
+		assert_eq!("bc1rw5uspcuh".parse::<SegwitAddress>(), Err(Error::InvalidAddress));
+		assert_eq!("bc10w508d6qejxtdg4y5r3zarvary0c5xw7kw508d6qejxtdg4y5r3zarvary0c5xw7kw5rljs90".parse::<SegwitAddress>(), Err(Error::InvalidAddress));
+		assert_eq!("tc1qw508d6qejxtdg4y5r3zarvary0c5xw7kg3g4ty".parse::<SegwitAddress>(), Err(Error::InvalidNetwork));
+	}
+}
@@ -171,6 +171,9 @@ pub struct NonFinalDoubleSpendSet {
 	/// Outputs which also will be removed from memory pool in case of newer transaction insertion
 	/// (i.e. outputs of nonfinal transactions && their descendants)
 	pub dependent_spends: HashSet<HashedOutPoint>,
+	/// Hashes of transactions which will be removed from memory pool in case of newer transaction insertion
+	/// (i.e. nonfinal transactions && their descendants)
+	pub replaced: HashSet<H256>,
 }
 
 impl From<OutPoint> for HashedOutPoint {
@@ -431,14 +434,15 @@ impl Storage {
 	pub fn check_double_spend(&self, transaction: &Transaction) -> DoubleSpendCheckResult {
 		let mut double_spends: HashSet<HashedOutPoint> = HashSet::new();
 		let mut dependent_spends: HashSet<HashedOutPoint> = HashSet::new();
+		let mut replaced: HashSet<H256> = HashSet::new();
 
 		for input in &transaction.inputs {
 			// find transaction that spends the same output
 			let prevout: HashedOutPoint = input.previous_output.clone().into();
 			if let Some(entry_hash) = self.by_previous_output.get(&prevout).cloned() {
-				// check if this is final transaction. If so, that's a potential double-spend error
+				// check if this is final transaction which doesn't signal replacement. If so, that's a potential double-spend error
 				let entry = self.by_hash.get(&entry_hash).expect("checked that it exists line above; qed");
-				if entry.transaction.is_final() {
+				if entry.transaction.is_final() && !entry.transaction.signals_replacement() {
 					return DoubleSpendCheckResult::DoubleSpend(entry_hash,	 prevout.out_point.hash, prevout.out_point.index);
 				}
 				// else remember this double spend
@@ -450,6 +454,7 @@ impl Storage {
 					// if the same output is already spent with another in-pool transaction
 					if let Some(dependent_entry_hash) = self.by_previous_output.get(&dependent_prevout).cloned() {
 						let dependent_entry = self.by_hash.get(&dependent_entry_hash).expect("checked that it exists line above; qed");
+						replaced.insert(dependent_entry_hash.clone());
 						let dependent_outputs: Vec<_> = dependent_entry.transaction.outputs.iter().enumerate().map(|(idx, _)| OutPoint {
 							hash: dependent_entry_hash.clone(),
 							index: idx as u32,
@@ -467,6 +472,7 @@ impl Storage {
 			DoubleSpendCheckResult::NonFinalDoubleSpend(NonFinalDoubleSpendSet {
 				double_spends: double_spends,
 				dependent_spends: dependent_spends,
+				replaced: replaced,
 			})
 		}
 	}
@@ -1416,6 +1422,31 @@ mod tests {
 
 	}
 
+	#[test]
+	fn test_memory_pool_check_double_spend_replaceable() {
+		let chain = &mut ChainBuilder::new();
+
+		TransactionBuilder::with_output(10).store(chain)								// t0
+			.reset().set_input(&chain.at(0), 0).add_output(20).store(chain)				// replaceable: t0[0] -> t1
+			.reset().set_input(&chain.at(0), 0).add_output(30).store(chain);			// replacement: t0[0] -> t2
+
+		let mut replaceable = chain.at(1);
+		replaceable.inputs[0].sequence = 0xfffffffd;
+
+		let mut pool = MemoryPool::new();
+		pool.insert_verified(replaceable.clone().into());
+		match pool.check_double_spend(&chain.at(2)) {
+			DoubleSpendCheckResult::NonFinalDoubleSpend(set) => {
+				assert_eq!(set.double_spends.len(), 1);
+				assert!(set.double_spends.contains(&replaceable.inputs[0].previous_output.clone().into()));
+				assert_eq!(set.dependent_spends.len(), 1);
+				assert_eq!(set.replaced.len(), 1);
+				assert!(set.replaced.contains(&replaceable.hash()));
+			},
+			_ => panic!("unexpected"),
+		}
+	}
+
 	#[test]
 	fn test_memory_poolis_spent() {
 		let tx1: Transaction = TransactionBuilder::with_default_input(0).into();
@@ -1,7 +1,8 @@
 use std::collections::HashMap;
 use chain::{Transaction, TransactionOutput, OutPoint};
 use db::{TransactionOutputProvider, SharedStore};
-use memory_pool::{DoubleSpendCheckResult, HashedOutPoint, NonFinalDoubleSpendSet, MemoryPoolRef};
+use memory_pool::{DoubleSpendCheckResult, HashedOutPoint, NonFinalDoubleSpendSet, MemoryPool, MemoryPoolRef};
+use ser::Serializable;
 use verification::TransactionError;
 
 
@@ -39,17 +40,70 @@ impl MemoryPoolTransactionOutputProvider {
 				nonfinal_spends: None,
 			}),
 			// there are non-final transactions, which are spending same inputs in memory pool
-			DoubleSpendCheckResult::NonFinalDoubleSpend(nonfinal_spends) => Ok(MemoryPoolTransactionOutputProvider {
-				storage_provider: storage,
-				mempool_inputs: transaction.inputs.iter()
-					.map(|input| (
-						input.previous_output.clone().into(),
-						memory_pool.transaction_output(&input.previous_output, usize::max_value()),
-					)).collect(),
-				nonfinal_spends: Some(nonfinal_spends),
-			}),
+			DoubleSpendCheckResult::NonFinalDoubleSpend(nonfinal_spends) => {
+				let provider = MemoryPoolTransactionOutputProvider {
+					storage_provider: storage,
+					mempool_inputs: transaction.inputs.iter()
+						.map(|input| (
+							input.previous_output.clone().into(),
+							memory_pool.transaction_output(&input.previous_output, usize::max_value()),
+						)).collect(),
+					nonfinal_spends: Some(nonfinal_spends),
+				};
+				provider.check_replacement_fee(&memory_pool, transaction)?;
+				Ok(provider)
+			},
 		}
 	}
+
+	/// Transactions signaling replacement may only be replaced by transaction which pays more fee
+	/// than all replaced transactions together and higher fee rate than any of them (bip125)
+	fn check_replacement_fee(&self, memory_pool: &MemoryPool, transaction: &Transaction) -> Result<(), TransactionError> {
+		let replaced: Vec<&Transaction> = match self.nonfinal_spends {
+			Some(ref nonfinal_spends) => nonfinal_spends.replaced.iter()
+				.filter_map(|hash| memory_pool.read_by_hash(hash))
+				.collect(),
+			None => return Ok(()),
+		};
+		if !replaced.iter().any(|replaced| replaced.signals_replacement()) {
+			return Ok(());
+		}
+
+		// missing inputs are reported by verification
+		let fee = match paid_fee(transaction, |prevout| self.transaction_output(prevout, usize::max_value())) {
+			Some(fee) => fee,
+			None => return Ok(()),
+		};
+		let size = transaction.serialized_size() as u128;
+
+		let mut replaced_fees: u128 = 0;
+		for replaced in replaced {
+			// inputs of verified memory pool transactions are known
+			let replaced_fee = paid_fee(replaced, |prevout| memory_pool
+				.transaction_output(prevout, usize::max_value())
+				.or_else(|| self.storage_provider.transaction_output(prevout, usize::max_value())))
+				.unwrap_or(0);
+			if fee as u128 * replaced.serialized_size() as u128 <= replaced_fee as u128 * size {
+				return Err(TransactionError::InsufficientReplacementFee);
+			}
+			replaced_fees += replaced_fee as u128;
+		}
+		if fee as u128 <= replaced_fees {
+			return Err(TransactionError::InsufficientReplacementFee);
+		}
+		Ok(())
+	}
+}
+
+/// Fee paid by transaction, if all its previous outputs are found
+fn paid_fee<F>(transaction: &Transaction, previous_output: F) -> Option<u64>
+	where F: Fn(&OutPoint) -> Option<TransactionOutput>
+{
+	let mut inputs_sum: u64 = 0;
+	for input in &transaction.inputs {
+		inputs_sum = inputs_sum.checked_add(previous_output(&input.previous_output)?.value)?;
+	}
+	inputs_sum.checked_sub(transaction.total_spends())
 }
 
 impl TransactionOutputProvider for MemoryPoolTransactionOutputProvider {
@@ -100,6 +154,7 @@ mod tests {
 	use chain::OutPoint;
 	use db::{TransactionOutputProvider, BlockChainDatabase};
 	use memory_pool::MemoryPool;
+	use verification::TransactionError;
 	use super::MemoryPoolTransactionOutputProvider;
 
 	#[test]
@@ -136,4 +191,28 @@ mod tests {
 		assert_eq!(provider.transaction_output(&OutPoint { hash: dchain.at(1).hash(), index: 0, }, 0), None);
 		assert_eq!(provider.transaction_output(&OutPoint { hash: dchain.at(2).hash(), index: 0, }, 0), None);
 	}
+
+	#[test]
+	fn when_transaction_replaces_transaction_signaling_replacement() {
+		let dchain = &mut chain_builder::ChainBuilder::new();
+
+		chain_builder::TransactionBuilder::with_output(100).store(dchain)				// t0
+			.reset().set_input(&dchain.at(0), 0).add_output(90).store(dchain)			// replaceable: t0[0] -> t1, fee 10
+			.reset().set_input(&dchain.at(0), 0).add_output(95).store(dchain)			// lower fee replacement: t0[0] -> t2
+			.reset().set_input(&dchain.at(0), 0).add_output(50).store(dchain);			// higher fee replacement: t0[0] -> t3
+
+		let mut replaceable = dchain.at(1);
+		replaceable.inputs[0].sequence = 0xfffffffd;
+
+		let storage = Arc::new(BlockChainDatabase::init_test_chain(vec![chain_builder::genesis().into()]));
+		let memory_pool = Arc::new(RwLock::new(MemoryPool::new()));
+		{
+			memory_pool.write().insert_verified(dchain.at(0).into());
+			memory_pool.write().insert_verified(replaceable.into());
+		}
+
+		assert_eq!(MemoryPoolTransactionOutputProvider::for_transaction(storage.clone(), &memory_pool, &dchain.at(2)).err(),
+			Some(TransactionError::InsufficientReplacementFee));
+		assert!(MemoryPoolTransactionOutputProvider::for_transaction(storage, &memory_pool, &dchain.at(3)).is_ok());
+	}
 }
@@ -30,3 +30,4 @@ pub use jsonrpc_http_server::tokio_core::reactor::{Remote};
 
 pub use jsonrpc_http_server::Server;
 pub use rpc_server::start_http;
+pub use v1::Metadata;
@@ -2,14 +2,16 @@
 use std::io;
 use std::net::SocketAddr;
 use jsonrpc_core;
-use jsonrpc_http_server::{self, ServerBuilder, Server, Host};
+use jsonrpc_http_server::{self, hyper, ServerBuilder, Server, Host};
+use v1::Metadata;
 
 /// Start http server asynchronously and returns result with `Server` handle on success or an error.
-pub fn start_http<M: jsonrpc_core::Metadata + Default>(
+/// Wallet requests are served by wallet named in request path `/wallet/<name>`.
+pub fn start_http(
 	addr: &SocketAddr,
 	cors_domains: Option<Vec<String>>,
 	allowed_hosts: Option<Vec<String>>,
-	handler: jsonrpc_core::MetaIoHandler<M>,
+	handler: jsonrpc_core::MetaIoHandler<Metadata>,
 	) -> Result<Server, io::Error> {
 
 	let cors_domains = cors_domains.map(|domains| {
@@ -25,5 +27,6 @@ pub fn start_http<M: jsonrpc_core::Metadata + Default>(
 	ServerBuilder::new(handler)
 		.cors(cors_domains.into())
 		.allowed_hosts(allowed_hosts.map(|hosts| hosts.into_iter().map(Host::from).collect()).into())
+		.meta_extractor(|request: &hyper::Request| Metadata::from_path(request.path()))
 		.start_http(addr)
 }
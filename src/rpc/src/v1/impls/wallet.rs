@@ -0,0 +1,1193 @@
+use std::ops::Deref;
+use std::str::FromStr;
+use jsonrpc_core::Error;
+use jsonrpc_macros::Trailing;
+use v1::Metadata;
+use v1::traits::Wallet;
+use v1::types::{H256, Bytes, OutPoint, WalletTransaction, WalletBalance, UnspentOutput, RescanResponse, RawTransaction, FinalizePsbtResponse};
+use v1::helpers::errors::{execution, invalid_params};
+use chain::{OutPoint as GlobalOutPoint, Psbt, Transaction as GlobalTransaction};
+use chain::constants::SATOSHIS_IN_COIN;
+use global_script::{Builder, finalize_psbt};
+use primitives::bytes::Bytes as GlobalBytes;
+use primitives::hash::H256 as GlobalH256;
+use ser::{deserialize, serialize_with_flags, Reader, SERIALIZE_TRANSACTION_WITNESS};
+use keys::{Address, Private, SegwitAddress, verify_message};
+use verification::policy::MAX_NULL_DATA_SIZE;
+
+pub struct WalletClient<T: WalletClientCoreApi> {
+	core: T,
+}
+
+/// Wallet itself lives in node binary, which provides implementation of this trait
+pub trait WalletClientCoreApi: Send + Sync + 'static {
+	/// Core which serves requests with wallet loaded under `name`
+	fn with_wallet(&self, name: &str) -> Result<Self, String> where Self: Sized;
+	/// Loads wallet `name`, creating it if there is none yet
+	fn load_wallet(&self, name: String) -> Result<(), String>;
+	fn unload_wallet(&self, name: String) -> Result<(), String>;
+	fn list_wallets(&self) -> Result<Vec<String>, String>;
+	fn new_address(&self) -> Result<Address, String>;
+	fn balance(&self) -> Result<WalletBalance, String>;
+	/// Pays `amount` satoshis to legacy or bech32 `address`. Default fee rate is used if none is given
+	fn send_to_address(&self, address: String, amount: u64, fee_rate: Option<u64>) -> Result<GlobalH256, String>;
+	/// Stores `data` in zero value null data output. Default fee rate is used if none is given
+	fn send_data(&self, data: GlobalBytes, fee_rate: Option<u64>) -> Result<GlobalH256, String>;
+	fn list_unspent(&self) -> Result<Vec<UnspentOutput>, String>;
+	fn dump_private_key(&self, address: Address) -> Result<Private, String>;
+	fn import_private_key(&self, private: Private) -> Result<(), String>;
+	fn transaction(&self, hash: GlobalH256) -> Result<WalletTransaction, String>;
+	/// Scans blocks from `from_height` to the best one for wallet transactions
+	fn rescan(&self, from_height: u32) -> Result<RescanResponse, String>;
+	/// Locks `out_points` if `unlock` is false and unlocks them otherwise. Empty list unlocks every output
+	fn lock_unspent(&self, unlock: bool, out_points: Vec<GlobalOutPoint>) -> Result<(), String>;
+	fn list_locked_unspent(&self) -> Result<Vec<GlobalOutPoint>, String>;
+	fn list_transactions(&self) -> Result<Vec<WalletTransaction>, String>;
+	fn import_address(&self, address: Address) -> Result<(), String>;
+	/// Names `address` with `label`. Empty label removes the name
+	fn set_label(&self, address: Address, label: String) -> Result<(), String>;
+	fn addresses_by_label(&self, label: String) -> Result<Vec<Address>, String>;
+	fn update_psbt(&self, psbt: Psbt) -> Result<Psbt, String>;
+	fn sign_psbt(&self, psbt: Psbt) -> Result<Psbt, String>;
+	fn sign_message(&self, address: Address, message: String) -> Result<String, String>;
+}
+
+/// Core of wallet selected by request: either the default one or one loaded by name
+enum SelectedCore<'a, T: 'a> {
+	Default(&'a T),
+	Named(T),
+}
+
+impl<'a, T> Deref for SelectedCore<'a, T> {
+	type Target = T;
+
+	fn deref(&self) -> &T {
+		match *self {
+			SelectedCore::Default(core) => core,
+			SelectedCore::Named(ref core) => core,
+		}
+	}
+}
+
+fn parse_psbt(psbt: &str) -> Result<Psbt, Error> {
+	psbt.parse().map_err(|e| invalid_params("psbt", e))
+}
+
+impl<T> WalletClient<T> where T: WalletClientCoreApi {
+	pub fn new(core: T) -> Self {
+		WalletClient {
+			core: core,
+		}
+	}
+
+	fn select(&self, meta: Metadata) -> Result<SelectedCore<T>, Error> {
+		match meta.wallet {
+			Some(name) => self.core.with_wallet(&name)
+				.map(SelectedCore::Named)
+				.map_err(|e| invalid_params("wallet", e)),
+			None => Ok(SelectedCore::Default(&self.core)),
+		}
+	}
+}
+
+impl<T> Wallet for WalletClient<T> where T: WalletClientCoreApi {
+	type Metadata = Metadata;
+
+	fn load_wallet(&self, name: String) -> Result<String, Error> {
+		self.core.load_wallet(name.clone())
+			.map(|_| name)
+			.map_err(|e| execution(e))
+	}
+
+	fn unload_wallet(&self, name: String) -> Result<(), Error> {
+		self.core.unload_wallet(name)
+			.map_err(|e| execution(e))
+	}
+
+	fn list_wallets(&self) -> Result<Vec<String>, Error> {
+		self.core.list_wallets()
+			.map_err(|e| execution(e))
+	}
+
+	fn get_new_address(&self, meta: Self::Metadata) -> Result<String, Error> {
+		let core = try!(self.select(meta));
+		core.new_address()
+			.map(|address| address.to_string())
+			.map_err(|e| execution(e))
+	}
+
+	fn get_balance(&self, meta: Self::Metadata) -> Result<WalletBalance, Error> {
+		let core = try!(self.select(meta));
+		core.balance()
+			.map_err(|e| execution(e))
+	}
+
+	fn send_to_address(&self, meta: Self::Metadata, address: String, amount: f64, fee_rate: Trailing<u64>) -> Result<H256, Error> {
+		let core = try!(self.select(meta));
+		if Address::from_str(&address).is_err() {
+			try!(SegwitAddress::from_str(&address).map_err(|e| invalid_params("address", e)));
+		}
+		if !(amount > 0.0) {
+			return Err(invalid_params("amount", "amount must be positive"));
+		}
+		let amount = (amount * SATOSHIS_IN_COIN as f64).round() as u64;
+		core.send_to_address(address, amount, fee_rate.into())
+			.map(|hash| hash.into())
+			.map_err(|e| execution(e))
+	}
+
+	fn send_data(&self, meta: Self::Metadata, data: Bytes, fee_rate: Trailing<u64>) -> Result<H256, Error> {
+		let core = try!(self.select(meta));
+		let data: Vec<u8> = data.into();
+		let script_size = Builder::build_nulldata(&data).len();
+		if script_size > MAX_NULL_DATA_SIZE {
+			return Err(invalid_params("data", format!("null data script of {} bytes exceeds {} bytes", script_size, MAX_NULL_DATA_SIZE)));
+		}
+		core.send_data(data.into(), fee_rate.into())
+			.map(|hash| hash.into())
+			.map_err(|e| execution(e))
+	}
+
+	fn list_unspent(&self, meta: Self::Metadata) -> Result<Vec<UnspentOutput>, Error> {
+		let core = try!(self.select(meta));
+		core.list_unspent()
+			.map_err(|e| execution(e))
+	}
+
+	fn dump_private_key(&self, meta: Self::Metadata, address: String) -> Result<String, Error> {
+		let core = try!(self.select(meta));
+		let address: Address = try!(address.parse().map_err(|e| invalid_params("address", e)));
+		core.dump_private_key(address)
+			.map(|private| private.to_string())
+			.map_err(|e| execution(e))
+	}
+
+	fn import_private_key(&self, meta: Self::Metadata, private: String) -> Result<(), Error> {
+		let core = try!(self.select(meta));
+		let private: Private = try!(private.parse().map_err(|e| invalid_params("privkey", e)));
+		core.import_private_key(private)
+			.map_err(|e| execution(e))
+	}
+
+	fn get_transaction(&self, meta: Self::Metadata, hash: H256) -> Result<WalletTransaction, Error> {
+		let core = try!(self.select(meta));
+		core.transaction(hash.into())
+			.map_err(|e| execution(e))
+	}
+
+	fn rescan(&self, meta: Self::Metadata, from_height: Trailing<u32>) -> Result<RescanResponse, Error> {
+		let core = try!(self.select(meta));
+		core.rescan(from_height.unwrap_or_default())
+			.map_err(|e| execution(e))
+	}
+
+	fn lock_unspent(&self, meta: Self::Metadata, unlock: bool, out_points: Trailing<Vec<OutPoint>>) -> Result<bool, Error> {
+		let core = try!(self.select(meta));
+		let out_points = out_points.unwrap_or_default()
+			.into_iter()
+			.map(|out_point| GlobalOutPoint {
+				hash: out_point.txid.into(),
+				index: out_point.vout,
+			})
+			.collect();
+		core.lock_unspent(unlock, out_points)
+			.map(|_| true)
+			.map_err(|e| execution(e))
+	}
+
+	fn list_lock_unspent(&self, meta: Self::Metadata) -> Result<Vec<OutPoint>, Error> {
+		let core = try!(self.select(meta));
+		core.list_locked_unspent()
+			.map(|out_points| out_points.into_iter()
+				.map(|out_point| OutPoint {
+					txid: out_point.hash.into(),
+					vout: out_point.index,
+				})
+				.collect())
+			.map_err(|e| execution(e))
+	}
+
+	fn list_transactions(&self, meta: Self::Metadata) -> Result<Vec<WalletTransaction>, Error> {
+		let core = try!(self.select(meta));
+		core.list_transactions()
+			.map_err(|e| execution(e))
+	}
+
+	fn import_address(&self, meta: Self::Metadata, address: String) -> Result<(), Error> {
+		let core = try!(self.select(meta));
+		let address: Address = try!(address.parse().map_err(|e| invalid_params("address", e)));
+		core.import_address(address)
+			.map_err(|e| execution(e))
+	}
+
+	fn set_label(&self, meta: Self::Metadata, address: String, label: String) -> Result<(), Error> {
+		let core = try!(self.select(meta));
+		let address: Address = try!(address.parse().map_err(|e| invalid_params("address", e)));
+		core.set_label(address, label)
+			.map_err(|e| execution(e))
+	}
+
+	fn get_addresses_by_label(&self, meta: Self::Metadata, label: String) -> Result<Vec<String>, Error> {
+		let core = try!(self.select(meta));
+		core.addresses_by_label(label)
+			.map(|addresses| addresses.into_iter().map(|address| address.to_string()).collect())
+			.map_err(|e| execution(e))
+	}
+
+	fn create_psbt(&self, raw_transaction: RawTransaction) -> Result<String, Error> {
+		let raw_transaction_data: Vec<u8> = raw_transaction.into();
+		let transaction: GlobalTransaction = try!(deserialize(Reader::new(&raw_transaction_data)).map_err(|e| invalid_params("tx", e)));
+		Psbt::from_unsigned_transaction(transaction)
+			.map(|psbt| psbt.to_string())
+			.map_err(|e| execution(e))
+	}
+
+	fn update_psbt(&self, meta: Self::Metadata, psbt: String) -> Result<String, Error> {
+		let core = try!(self.select(meta));
+		let psbt = try!(parse_psbt(&psbt));
+		core.update_psbt(psbt)
+			.map(|psbt| psbt.to_string())
+			.map_err(|e| execution(e))
+	}
+
+	fn sign_psbt(&self, meta: Self::Metadata, psbt: String) -> Result<String, Error> {
+		let core = try!(self.select(meta));
+		let psbt = try!(parse_psbt(&psbt));
+		core.sign_psbt(psbt)
+			.map(|psbt| psbt.to_string())
+			.map_err(|e| execution(e))
+	}
+
+	fn combine_psbt(&self, psbts: Vec<String>) -> Result<String, Error> {
+		let mut psbts = psbts.iter().map(|psbt| parse_psbt(psbt));
+		let mut combined = match psbts.next() {
+			Some(psbt) => try!(psbt),
+			None => return Err(invalid_params("psbts", "empty list")),
+		};
+		for psbt in psbts {
+			try!(combined.combine(try!(psbt)).map_err(|e| execution(e)));
+		}
+		Ok(combined.to_string())
+	}
+
+	fn finalize_psbt(&self, psbt: String) -> Result<FinalizePsbtResponse, Error> {
+		let mut psbt = try!(parse_psbt(&psbt));
+		let complete = finalize_psbt(&mut psbt);
+		Ok(FinalizePsbtResponse {
+			psbt: psbt.to_string(),
+			complete: complete,
+		})
+	}
+
+	fn extract_psbt(&self, psbt: String) -> Result<RawTransaction, Error> {
+		let psbt = try!(parse_psbt(&psbt));
+		psbt.extract_transaction()
+			.map(|transaction| serialize_with_flags(&transaction, SERIALIZE_TRANSACTION_WITNESS).into())
+			.map_err(|e| execution(e))
+	}
+
+	fn sign_message(&self, meta: Self::Metadata, address: String, message: String) -> Result<String, Error> {
+		let core = try!(self.select(meta));
+		let address: Address = try!(address.parse().map_err(|e| invalid_params("address", e)));
+		core.sign_message(address, message)
+			.map_err(|e| execution(e))
+	}
+
+	fn verify_message(&self, address: String, signature: String, message: String) -> Result<bool, Error> {
+		let address: Address = try!(address.parse().map_err(|e| invalid_params("address", e)));
+		verify_message(&address, &signature, &message)
+			.map_err(|e| invalid_params("signature", e))
+	}
+}
+
+#[cfg(test)]
+pub mod tests {
+	use jsonrpc_core::MetaIoHandler;
+	use chain::{OutPoint as GlobalOutPoint, Psbt};
+	use keys::{Address, Private};
+	use primitives::hash::H256 as GlobalH256;
+	use v1::traits::Wallet;
+	use v1::types::{H256, WalletTransaction, WalletTransactionCategory, WalletBalance, UnspentOutput, RescanResponse};
+	use super::*;
+
+	#[derive(Default)]
+	struct SuccessWalletClientCore;
+	#[derive(Default)]
+	struct ErrorWalletClientCore;
+
+	fn wallet_transaction() -> WalletTransaction {
+		WalletTransaction {
+			txid: H256::from(0x56),
+			category: WalletTransactionCategory::Receive,
+			amount: 0.25,
+			fee: None,
+			address: Some("1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa".to_owned()),
+			label: None,
+			blockheight: Some(2),
+			confirmations: 1,
+		}
+	}
+
+	impl WalletClientCoreApi for SuccessWalletClientCore {
+		fn with_wallet(&self, name: &str) -> Result<Self, String> {
+			assert_eq!(name, "savings");
+			Ok(SuccessWalletClientCore)
+		}
+
+		fn load_wallet(&self, name: String) -> Result<(), String> {
+			assert_eq!(name, "savings");
+			Ok(())
+		}
+
+		fn unload_wallet(&self, name: String) -> Result<(), String> {
+			assert_eq!(name, "savings");
+			Ok(())
+		}
+
+		fn list_wallets(&self) -> Result<Vec<String>, String> {
+			Ok(vec!["default".to_owned(), "savings".to_owned()])
+		}
+
+		fn new_address(&self) -> Result<Address, String> {
+			Ok("1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH".into())
+		}
+
+		fn balance(&self) -> Result<WalletBalance, String> {
+			Ok(WalletBalance {
+				confirmed: 1.5,
+				unconfirmed: 0.25,
+				immature: 0.0,
+			})
+		}
+
+		fn send_to_address(&self, _address: String, amount: u64, fee_rate: Option<u64>) -> Result<GlobalH256, String> {
+			assert_eq!(amount, 29000000);
+			assert_eq!(fee_rate, None);
+			Ok(GlobalH256::from(0x56))
+		}
+
+		fn send_data(&self, data: GlobalBytes, fee_rate: Option<u64>) -> Result<GlobalH256, String> {
+			assert_eq!(data, "0102".into());
+			assert_eq!(fee_rate, Some(10));
+			Ok(GlobalH256::from(0x56))
+		}
+
+		fn list_unspent(&self) -> Result<Vec<UnspentOutput>, String> {
+			Ok(vec![])
+		}
+
+		fn dump_private_key(&self, _address: Address) -> Result<Private, String> {
+			Ok("KwDiBf89QgGbjEhKnhXJuH7LrciVrZi3qYjgd9M7rFU73sVHnoWn".into())
+		}
+
+		fn import_private_key(&self, _private: Private) -> Result<(), String> {
+			Ok(())
+		}
+
+		fn transaction(&self, _hash: GlobalH256) -> Result<WalletTransaction, String> {
+			Ok(wallet_transaction())
+		}
+
+		fn rescan(&self, from_height: u32) -> Result<RescanResponse, String> {
+			Ok(RescanResponse {
+				start_height: from_height,
+				stop_height: 20,
+			})
+		}
+
+		fn lock_unspent(&self, unlock: bool, out_points: Vec<GlobalOutPoint>) -> Result<(), String> {
+			assert!(!unlock);
+			assert_eq!(out_points, vec![GlobalOutPoint {
+				hash: GlobalH256::from(0x56),
+				index: 1,
+			}]);
+			Ok(())
+		}
+
+		fn list_locked_unspent(&self) -> Result<Vec<GlobalOutPoint>, String> {
+			Ok(vec![GlobalOutPoint {
+				hash: GlobalH256::from(0x56),
+				index: 1,
+			}])
+		}
+
+		fn list_transactions(&self) -> Result<Vec<WalletTransaction>, String> {
+			Ok(vec![wallet_transaction()])
+		}
+
+		fn import_address(&self, _address: Address) -> Result<(), String> {
+			Ok(())
+		}
+
+		fn set_label(&self, _address: Address, label: String) -> Result<(), String> {
+			assert_eq!(label, "savings");
+			Ok(())
+		}
+
+		fn addresses_by_label(&self, _label: String) -> Result<Vec<Address>, String> {
+			Ok(vec!["1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa".into()])
+		}
+
+		fn update_psbt(&self, psbt: Psbt) -> Result<Psbt, String> {
+			Ok(psbt)
+		}
+
+		fn sign_psbt(&self, psbt: Psbt) -> Result<Psbt, String> {
+			Ok(psbt)
+		}
+
+		fn sign_message(&self, _address: Address, _message: String) -> Result<String, String> {
+			Ok("H1W0".to_owned())
+		}
+	}
+
+	impl WalletClientCoreApi for ErrorWalletClientCore {
+		fn with_wallet(&self, _name: &str) -> Result<Self, String> {
+			Err("error".to_owned())
+		}
+
+		fn load_wallet(&self, _name: String) -> Result<(), String> {
+			Err("error".to_owned())
+		}
+
+		fn unload_wallet(&self, _name: String) -> Result<(), String> {
+			Err("error".to_owned())
+		}
+
+		fn list_wallets(&self) -> Result<Vec<String>, String> {
+			Err("error".to_owned())
+		}
+
+		fn new_address(&self) -> Result<Address, String> {
+			Err("error".to_owned())
+		}
+
+		fn balance(&self) -> Result<WalletBalance, String> {
+			Err("error".to_owned())
+		}
+
+		fn send_to_address(&self, _address: String, _amount: u64, _fee_rate: Option<u64>) -> Result<GlobalH256, String> {
+			Err("error".to_owned())
+		}
+
+		fn send_data(&self, _data: GlobalBytes, _fee_rate: Option<u64>) -> Result<GlobalH256, String> {
+			Err("error".to_owned())
+		}
+
+		fn list_unspent(&self) -> Result<Vec<UnspentOutput>, String> {
+			Err("error".to_owned())
+		}
+
+		fn dump_private_key(&self, _address: Address) -> Result<Private, String> {
+			Err("error".to_owned())
+		}
+
+		fn import_private_key(&self, _private: Private) -> Result<(), String> {
+			Err("error".to_owned())
+		}
+
+		fn transaction(&self, _hash: GlobalH256) -> Result<WalletTransaction, String> {
+			Err("error".to_owned())
+		}
+
+		fn rescan(&self, _from_height: u32) -> Result<RescanResponse, String> {
+			Err("error".to_owned())
+		}
+
+		fn lock_unspent(&self, _unlock: bool, _out_points: Vec<GlobalOutPoint>) -> Result<(), String> {
+			Err("error".to_owned())
+		}
+
+		fn list_locked_unspent(&self) -> Result<Vec<GlobalOutPoint>, String> {
+			Err("error".to_owned())
+		}
+
+		fn list_transactions(&self) -> Result<Vec<WalletTransaction>, String> {
+			Err("error".to_owned())
+		}
+
+		fn import_address(&self, _address: Address) -> Result<(), String> {
+			Err("error".to_owned())
+		}
+
+		fn set_label(&self, _address: Address, _label: String) -> Result<(), String> {
+			Err("error".to_owned())
+		}
+
+		fn addresses_by_label(&self, _label: String) -> Result<Vec<Address>, String> {
+			Err("error".to_owned())
+		}
+
+		fn update_psbt(&self, _psbt: Psbt) -> Result<Psbt, String> {
+			Err("error".to_owned())
+		}
+
+		fn sign_psbt(&self, _psbt: Psbt) -> Result<Psbt, String> {
+			Err("error".to_owned())
+		}
+
+		fn sign_message(&self, _address: Address, _message: String) -> Result<String, String> {
+			Err("error".to_owned())
+		}
+	}
+
+	#[test]
+	fn loadwallet_success() {
+		let client = WalletClient::new(SuccessWalletClientCore::default());
+		let mut handler = MetaIoHandler::default();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "loadwallet",
+				"params": ["savings"],
+				"id": 1
+			}"#), Metadata::default()
+		).unwrap();
+
+		assert_eq!(r#"{"jsonrpc":"2.0","result":"savings","id":1}"#, &sample);
+	}
+
+	#[test]
+	fn loadwallet_error() {
+		let client = WalletClient::new(ErrorWalletClientCore::default());
+		let mut handler = MetaIoHandler::default();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "loadwallet",
+				"params": ["savings"],
+				"id": 1
+			}"#), Metadata::default()
+		).unwrap();
+
+		assert_eq!(r#"{"jsonrpc":"2.0","error":{"code":-32015,"message":"Execution error.","data":"\"error\""},"id":1}"#, &sample);
+	}
+
+	#[test]
+	fn unloadwallet_success() {
+		let client = WalletClient::new(SuccessWalletClientCore::default());
+		let mut handler = MetaIoHandler::default();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "unloadwallet",
+				"params": ["savings"],
+				"id": 1
+			}"#), Metadata::default()
+		).unwrap();
+
+		assert_eq!(r#"{"jsonrpc":"2.0","result":null,"id":1}"#, &sample);
+	}
+
+	#[test]
+	fn listwallets_success() {
+		let client = WalletClient::new(SuccessWalletClientCore::default());
+		let mut handler = MetaIoHandler::default();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "listwallets",
+				"params": [],
+				"id": 1
+			}"#), Metadata::default()
+		).unwrap();
+
+		assert_eq!(r#"{"jsonrpc":"2.0","result":["default","savings"],"id":1}"#, &sample);
+	}
+
+	#[test]
+	fn getbalance_of_named_wallet() {
+		let client = WalletClient::new(SuccessWalletClientCore::default());
+		let mut handler = MetaIoHandler::default();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "getbalance",
+				"params": [],
+				"id": 1
+			}"#), Metadata::from_path("/wallet/savings")
+		).unwrap();
+
+		assert_eq!(r#"{"jsonrpc":"2.0","result":{"confirmed":1.5,"unconfirmed":0.25,"immature":0.0},"id":1}"#, &sample);
+	}
+
+	#[test]
+	fn getbalance_of_unknown_wallet() {
+		let client = WalletClient::new(ErrorWalletClientCore::default());
+		let mut handler = MetaIoHandler::default();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "getbalance",
+				"params": [],
+				"id": 1
+			}"#), Metadata::from_path("/wallet/savings")
+		).unwrap();
+
+		assert_eq!(r#"{"jsonrpc":"2.0","error":{"code":-32602,"message":"Couldn't parse parameters: wallet","data":"\"error\""},"id":1}"#, &sample);
+	}
+
+	#[test]
+	fn listtransactions_success() {
+		let client = WalletClient::new(SuccessWalletClientCore::default());
+		let mut handler = MetaIoHandler::default();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "listtransactions",
+				"params": [],
+				"id": 1
+			}"#), Metadata::default()
+		).unwrap();
+
+		assert_eq!(r#"{"jsonrpc":"2.0","result":[{"txid":"5600000000000000000000000000000000000000000000000000000000000000","category":"receive","amount":0.25,"fee":null,"address":"1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa","label":null,"blockheight":2,"confirmations":1}],"id":1}"#, &sample);
+	}
+
+	#[test]
+	fn listtransactions_error() {
+		let client = WalletClient::new(ErrorWalletClientCore::default());
+		let mut handler = MetaIoHandler::default();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "listtransactions",
+				"params": [],
+				"id": 1
+			}"#), Metadata::default()
+		).unwrap();
+
+		assert_eq!(r#"{"jsonrpc":"2.0","error":{"code":-32015,"message":"Execution error.","data":"\"error\""},"id":1}"#, &sample);
+	}
+
+	#[test]
+	fn importaddress_success() {
+		let client = WalletClient::new(SuccessWalletClientCore::default());
+		let mut handler = MetaIoHandler::default();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "importaddress",
+				"params": ["1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa"],
+				"id": 1
+			}"#), Metadata::default()
+		).unwrap();
+
+		assert_eq!(r#"{"jsonrpc":"2.0","result":null,"id":1}"#, &sample);
+	}
+
+	#[test]
+	fn importaddress_invalid_address() {
+		let client = WalletClient::new(SuccessWalletClientCore::default());
+		let mut handler = MetaIoHandler::default();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "importaddress",
+				"params": ["notanaddress"],
+				"id": 1
+			}"#), Metadata::default()
+		).unwrap();
+
+		assert_eq!(r#"{"jsonrpc":"2.0","error":{"code":-32602,"message":"Couldn't parse parameters: address","data":"InvalidAddress"},"id":1}"#, &sample);
+	}
+
+	#[test]
+	fn setlabel_success() {
+		let client = WalletClient::new(SuccessWalletClientCore::default());
+		let mut handler = MetaIoHandler::default();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "setlabel",
+				"params": ["1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa", "savings"],
+				"id": 1
+			}"#), Metadata::default()
+		).unwrap();
+
+		assert_eq!(r#"{"jsonrpc":"2.0","result":null,"id":1}"#, &sample);
+	}
+
+	#[test]
+	fn setlabel_invalid_address() {
+		let client = WalletClient::new(SuccessWalletClientCore::default());
+		let mut handler = MetaIoHandler::default();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "setlabel",
+				"params": ["notanaddress", "savings"],
+				"id": 1
+			}"#), Metadata::default()
+		).unwrap();
+
+		assert_eq!(r#"{"jsonrpc":"2.0","error":{"code":-32602,"message":"Couldn't parse parameters: address","data":"InvalidAddress"},"id":1}"#, &sample);
+	}
+
+	#[test]
+	fn getaddressesbylabel_success() {
+		let client = WalletClient::new(SuccessWalletClientCore::default());
+		let mut handler = MetaIoHandler::default();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "getaddressesbylabel",
+				"params": ["savings"],
+				"id": 1
+			}"#), Metadata::default()
+		).unwrap();
+
+		assert_eq!(r#"{"jsonrpc":"2.0","result":["1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa"],"id":1}"#, &sample);
+	}
+
+	#[test]
+	fn getaddressesbylabel_error() {
+		let client = WalletClient::new(ErrorWalletClientCore::default());
+		let mut handler = MetaIoHandler::default();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "getaddressesbylabel",
+				"params": ["savings"],
+				"id": 1
+			}"#), Metadata::default()
+		).unwrap();
+
+		assert_eq!(r#"{"jsonrpc":"2.0","error":{"code":-32015,"message":"Execution error.","data":"\"error\""},"id":1}"#, &sample);
+	}
+
+	#[test]
+	fn createpsbt_success() {
+		let client = WalletClient::new(SuccessWalletClientCore::default());
+		let mut handler = MetaIoHandler::default();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "createpsbt",
+				"params": ["0200000001c3a1b5a1ee01ee3a7f0f48d4d5b7ec6d0e8c9a3bc5a0d3a3e6f8e4f1ad0b2e460100000000ffffffff0150c3000000000000160014751e76e8199196d454941c45d1b3a323f1433bd600000000"],
+				"id": 1
+			}"#), Metadata::default()
+		).unwrap();
+
+		assert_eq!(r#"{"jsonrpc":"2.0","result":"cHNidP8BAFICAAAAAcOhtaHuAe46fw9I1NW37G0OjJo7xaDTo+b45PGtCy5GAQAAAAD/////AVDDAAAAAAAAFgAUdR526BmRltRUlBxF0bOjI/FDO9YAAAAAAAAA","id":1}"#, &sample);
+	}
+
+	#[test]
+	fn signpsbt_error() {
+		let client = WalletClient::new(ErrorWalletClientCore::default());
+		let mut handler = MetaIoHandler::default();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "signpsbt",
+				"params": ["cHNidP8BAFICAAAAAcOhtaHuAe46fw9I1NW37G0OjJo7xaDTo+b45PGtCy5GAQAAAAD/////AVDDAAAAAAAAFgAUdR526BmRltRUlBxF0bOjI/FDO9YAAAAAAAAA"],
+				"id": 1
+			}"#), Metadata::default()
+		).unwrap();
+
+		assert_eq!(r#"{"jsonrpc":"2.0","error":{"code":-32015,"message":"Execution error.","data":"\"error\""},"id":1}"#, &sample);
+	}
+
+	#[test]
+	fn combinepsbt_different_transactions() {
+		let client = WalletClient::new(SuccessWalletClientCore::default());
+		let mut handler = MetaIoHandler::default();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "combinepsbt",
+				"params": [["cHNidP8BAFICAAAAAcOhtaHuAe46fw9I1NW37G0OjJo7xaDTo+b45PGtCy5GAQAAAAD/////AVDDAAAAAAAAFgAUdR526BmRltRUlBxF0bOjI/FDO9YAAAAAAAAA", "cHNidP8BAFICAAAAAcOhtaHuAe46fw9I1NW37G0OjJo7xaDTo+b45PGtCy5GAQAAAAD/////AVDDAAAAAAAAFgAUdR526BmRltRUlBxF0bOjI/FDO9YBAAAAAAAA"]],
+				"id": 1
+			}"#), Metadata::default()
+		).unwrap();
+
+		assert_eq!(r#"{"jsonrpc":"2.0","error":{"code":-32015,"message":"Execution error.","data":"DifferentTransactions"},"id":1}"#, &sample);
+	}
+
+	#[test]
+	fn finalizepsbt_incomplete() {
+		let client = WalletClient::new(SuccessWalletClientCore::default());
+		let mut handler = MetaIoHandler::default();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "finalizepsbt",
+				"params": ["cHNidP8BAFICAAAAAcOhtaHuAe46fw9I1NW37G0OjJo7xaDTo+b45PGtCy5GAQAAAAD/////AVDDAAAAAAAAFgAUdR526BmRltRUlBxF0bOjI/FDO9YAAAAAAAAA"],
+				"id": 1
+			}"#), Metadata::default()
+		).unwrap();
+
+		assert_eq!(r#"{"jsonrpc":"2.0","result":{"psbt":"cHNidP8BAFICAAAAAcOhtaHuAe46fw9I1NW37G0OjJo7xaDTo+b45PGtCy5GAQAAAAD/////AVDDAAAAAAAAFgAUdR526BmRltRUlBxF0bOjI/FDO9YAAAAAAAAA","complete":false},"id":1}"#, &sample);
+	}
+
+	#[test]
+	fn extractpsbt_invalid_psbt() {
+		let client = WalletClient::new(SuccessWalletClientCore::default());
+		let mut handler = MetaIoHandler::default();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "extractpsbt",
+				"params": ["notbase64!"],
+				"id": 1
+			}"#), Metadata::default()
+		).unwrap();
+
+		assert_eq!(r#"{"jsonrpc":"2.0","error":{"code":-32602,"message":"Couldn't parse parameters: psbt","data":"InvalidBase64"},"id":1}"#, &sample);
+	}
+
+	#[test]
+	fn signmessage_success() {
+		let client = WalletClient::new(SuccessWalletClientCore::default());
+		let mut handler = MetaIoHandler::default();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "signmessage",
+				"params": ["1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH", "I own this address"],
+				"id": 1
+			}"#), Metadata::default()
+		).unwrap();
+
+		assert_eq!(r#"{"jsonrpc":"2.0","result":"H1W0","id":1}"#, &sample);
+	}
+
+	#[test]
+	fn verifymessage_invalid_signature() {
+		let client = WalletClient::new(SuccessWalletClientCore::default());
+		let mut handler = MetaIoHandler::default();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "verifymessage",
+				"params": ["1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH", "AAAA", "I own this address"],
+				"id": 1
+			}"#), Metadata::default()
+		).unwrap();
+
+		assert_eq!(r#"{"jsonrpc":"2.0","error":{"code":-32602,"message":"Couldn't parse parameters: signature","data":"InvalidSignature"},"id":1}"#, &sample);
+	}
+
+	#[test]
+	fn getnewaddress_success() {
+		let client = WalletClient::new(SuccessWalletClientCore::default());
+		let mut handler = MetaIoHandler::default();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "getnewaddress",
+				"params": [],
+				"id": 1
+			}"#), Metadata::default()
+		).unwrap();
+
+		assert_eq!(r#"{"jsonrpc":"2.0","result":"1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH","id":1}"#, &sample);
+	}
+
+	#[test]
+	fn getbalance_success() {
+		let client = WalletClient::new(SuccessWalletClientCore::default());
+		let mut handler = MetaIoHandler::default();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "getbalance",
+				"params": [],
+				"id": 1
+			}"#), Metadata::default()
+		).unwrap();
+
+		assert_eq!(r#"{"jsonrpc":"2.0","result":{"confirmed":1.5,"unconfirmed":0.25,"immature":0.0},"id":1}"#, &sample);
+	}
+
+	#[test]
+	fn sendtoaddress_success() {
+		let client = WalletClient::new(SuccessWalletClientCore::default());
+		let mut handler = MetaIoHandler::default();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "sendtoaddress",
+				"params": ["bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4", 0.29],
+				"id": 1
+			}"#), Metadata::default()
+		).unwrap();
+
+		assert_eq!(r#"{"jsonrpc":"2.0","result":"5600000000000000000000000000000000000000000000000000000000000000","id":1}"#, &sample);
+	}
+
+	#[test]
+	fn sendtoaddress_invalid_address() {
+		let client = WalletClient::new(SuccessWalletClientCore::default());
+		let mut handler = MetaIoHandler::default();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "sendtoaddress",
+				"params": ["notanaddress", 0.29],
+				"id": 1
+			}"#), Metadata::default()
+		).unwrap();
+
+		assert_eq!(r#"{"jsonrpc":"2.0","error":{"code":-32602,"message":"Couldn't parse parameters: address","data":"InvalidAddress"},"id":1}"#, &sample);
+	}
+
+	#[test]
+	fn sendtoaddress_negative_amount() {
+		let client = WalletClient::new(SuccessWalletClientCore::default());
+		let mut handler = MetaIoHandler::default();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "sendtoaddress",
+				"params": ["1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH", -1.0],
+				"id": 1
+			}"#), Metadata::default()
+		).unwrap();
+
+		assert_eq!(r#"{"jsonrpc":"2.0","error":{"code":-32602,"message":"Couldn't parse parameters: amount","data":"\"amount must be positive\""},"id":1}"#, &sample);
+	}
+
+	#[test]
+	fn senddata_success() {
+		let client = WalletClient::new(SuccessWalletClientCore::default());
+		let mut handler = MetaIoHandler::default();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "senddata",
+				"params": ["0102", 10],
+				"id": 1
+			}"#), Metadata::default()
+		).unwrap();
+
+		assert_eq!(r#"{"jsonrpc":"2.0","result":"5600000000000000000000000000000000000000000000000000000000000000","id":1}"#, &sample);
+	}
+
+	#[test]
+	fn senddata_too_large() {
+		let client = WalletClient::new(SuccessWalletClientCore::default());
+		let mut handler = MetaIoHandler::default();
+		handler.extend_with(client.to_delegate());
+
+		let request = format!(r#"{{"jsonrpc": "2.0", "method": "senddata", "params": ["{}"], "id": 1}}"#, "00".repeat(81));
+		let sample = handler.handle_request_sync(&request, Metadata::default()).unwrap();
+
+		assert_eq!(r#"{"jsonrpc":"2.0","error":{"code":-32602,"message":"Couldn't parse parameters: data","data":"\"null data script of 84 bytes exceeds 83 bytes\""},"id":1}"#, &sample);
+	}
+
+	#[test]
+	fn senddata_error() {
+		let client = WalletClient::new(ErrorWalletClientCore::default());
+		let mut handler = MetaIoHandler::default();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "senddata",
+				"params": ["0102"],
+				"id": 1
+			}"#), Metadata::default()
+		).unwrap();
+
+		assert_eq!(r#"{"jsonrpc":"2.0","error":{"code":-32015,"message":"Execution error.","data":"\"error\""},"id":1}"#, &sample);
+	}
+
+	#[test]
+	fn listunspent_error() {
+		let client = WalletClient::new(ErrorWalletClientCore::default());
+		let mut handler = MetaIoHandler::default();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "listunspent",
+				"params": [],
+				"id": 1
+			}"#), Metadata::default()
+		).unwrap();
+
+		assert_eq!(r#"{"jsonrpc":"2.0","error":{"code":-32015,"message":"Execution error.","data":"\"error\""},"id":1}"#, &sample);
+	}
+
+	#[test]
+	fn dumpprivkey_success() {
+		let client = WalletClient::new(SuccessWalletClientCore::default());
+		let mut handler = MetaIoHandler::default();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "dumpprivkey",
+				"params": ["1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH"],
+				"id": 1
+			}"#), Metadata::default()
+		).unwrap();
+
+		assert_eq!(r#"{"jsonrpc":"2.0","result":"KwDiBf89QgGbjEhKnhXJuH7LrciVrZi3qYjgd9M7rFU73sVHnoWn","id":1}"#, &sample);
+	}
+
+	#[test]
+	fn importprivkey_invalid_key() {
+		let client = WalletClient::new(SuccessWalletClientCore::default());
+		let mut handler = MetaIoHandler::default();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "importprivkey",
+				"params": ["notakey"],
+				"id": 1
+			}"#), Metadata::default()
+		).unwrap();
+
+		assert_eq!(r#"{"jsonrpc":"2.0","error":{"code":-32602,"message":"Couldn't parse parameters: privkey","data":"InvalidPrivate"},"id":1}"#, &sample);
+	}
+
+	#[test]
+	fn gettransaction_error() {
+		let client = WalletClient::new(ErrorWalletClientCore::default());
+		let mut handler = MetaIoHandler::default();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "gettransaction",
+				"params": ["5600000000000000000000000000000000000000000000000000000000000000"],
+				"id": 1
+			}"#), Metadata::default()
+		).unwrap();
+
+		assert_eq!(r#"{"jsonrpc":"2.0","error":{"code":-32015,"message":"Execution error.","data":"\"error\""},"id":1}"#, &sample);
+	}
+
+	#[test]
+	fn rescan_default_height() {
+		let client = WalletClient::new(SuccessWalletClientCore::default());
+		let mut handler = MetaIoHandler::default();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "rescan",
+				"params": [],
+				"id": 1
+			}"#), Metadata::default()
+		).unwrap();
+
+		assert_eq!(r#"{"jsonrpc":"2.0","result":{"start_height":0,"stop_height":20},"id":1}"#, &sample);
+	}
+
+	#[test]
+	fn rescan_error() {
+		let client = WalletClient::new(ErrorWalletClientCore::default());
+		let mut handler = MetaIoHandler::default();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "rescan",
+				"params": [10],
+				"id": 1
+			}"#), Metadata::default()
+		).unwrap();
+
+		assert_eq!(r#"{"jsonrpc":"2.0","error":{"code":-32015,"message":"Execution error.","data":"\"error\""},"id":1}"#, &sample);
+	}
+
+	#[test]
+	fn lockunspent_success() {
+		let client = WalletClient::new(SuccessWalletClientCore::default());
+		let mut handler = MetaIoHandler::default();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "lockunspent",
+				"params": [false, [{"txid": "5600000000000000000000000000000000000000000000000000000000000000", "vout": 1}]],
+				"id": 1
+			}"#), Metadata::default()
+		).unwrap();
+
+		assert_eq!(r#"{"jsonrpc":"2.0","result":true,"id":1}"#, &sample);
+	}
+
+	#[test]
+	fn listlockunspent_success() {
+		let client = WalletClient::new(SuccessWalletClientCore::default());
+		let mut handler = MetaIoHandler::default();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "listlockunspent",
+				"params": [],
+				"id": 1
+			}"#), Metadata::default()
+		).unwrap();
+
+		assert_eq!(r#"{"jsonrpc":"2.0","result":[{"txid":"5600000000000000000000000000000000000000000000000000000000000000","vout":1}],"id":1}"#, &sample);
+	}
+}
@@ -0,0 +1,40 @@
+use jsonrpc_core;
+
+/// Prefix of request path which selects wallet by name
+const WALLET_PATH_PREFIX: &'static str = "/wallet/";
+
+/// Data of RPC request which is not passed in its parameters.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Metadata {
+	/// Name of wallet from request path `/wallet/<name>`. Default wallet is used if there is none
+	pub wallet: Option<String>,
+}
+
+impl jsonrpc_core::Metadata for Metadata {}
+
+impl Metadata {
+	/// Reads metadata from path of http request
+	pub fn from_path(path: &str) -> Self {
+		let name = match path.starts_with(WALLET_PATH_PREFIX) {
+			true => path[WALLET_PATH_PREFIX.len()..].trim_right_matches('/'),
+			false => "",
+		};
+
+		Metadata {
+			wallet: if name.is_empty() { None } else { Some(name.to_owned()) },
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::Metadata;
+
+	#[test]
+	fn metadata_from_path() {
+		assert_eq!(Metadata::from_path("/").wallet, None);
+		assert_eq!(Metadata::from_path("/wallet/").wallet, None);
+		assert_eq!(Metadata::from_path("/wallet/savings").wallet, Some("savings".to_owned()));
+		assert_eq!(Metadata::from_path("/wallet/savings/").wallet, Some("savings".to_owned()));
+	}
+}
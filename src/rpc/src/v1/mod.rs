@@ -1,14 +1,18 @@
 #[macro_use]
 pub mod helpers;
 pub mod impls;
+pub mod metadata;
 pub mod traits;
 pub mod types;
 
+pub use self::metadata::Metadata;
 pub use self::traits::Raw;
 pub use self::traits::Miner;
 pub use self::traits::BlockChain;
 pub use self::traits::Network;
+pub use self::traits::Wallet;
 pub use self::impls::{RawClient, RawClientCore};
 pub use self::impls::{MinerClient, MinerClientCore};
 pub use self::impls::{BlockChainClient, BlockChainClientCore};
 pub use self::impls::{NetworkClient, NetworkClientCore};
+pub use self::impls::{WalletClient, WalletClientCoreApi};
@@ -2,8 +2,10 @@ mod blockchain;
 mod miner;
 mod raw;
 mod network;
+mod wallet;
 
 pub use self::blockchain::BlockChain;
 pub use self::miner::Miner;
 pub use self::raw::Raw;
 pub use self::network::Network;
+pub use self::wallet::Wallet;
@@ -0,0 +1,116 @@
+use jsonrpc_macros::Trailing;
+use jsonrpc_core::Error;
+
+use v1::types::{H256, Bytes, OutPoint, WalletTransaction, WalletBalance, UnspentOutput, RescanResponse, RawTransaction, FinalizePsbtResponse};
+
+build_rpc_trait! {
+	/// Rustheus wallet interface. Requests to `/wallet/<name>` path are served by wallet with that name, the rest by default wallet.
+	pub trait Wallet {
+		type Metadata;
+
+		/// Load wallet with given name, creating it if there is none yet.
+		/// @curl-example: curl --data-binary '{"jsonrpc": "2.0", "method": "loadwallet", "params": ["savings"], "id":1 }' -H 'content-type: application/json' http://127.0.0.1:8332/
+		#[rpc(name = "loadwallet")]
+		fn load_wallet(&self, String) -> Result<String, Error>;
+		/// Unload wallet with given name. Default wallet can't be unloaded.
+		/// @curl-example: curl --data-binary '{"jsonrpc": "2.0", "method": "unloadwallet", "params": ["savings"], "id":1 }' -H 'content-type: application/json' http://127.0.0.1:8332/
+		#[rpc(name = "unloadwallet")]
+		fn unload_wallet(&self, String) -> Result<(), Error>;
+		/// List names of loaded wallets.
+		/// @curl-example: curl --data-binary '{"jsonrpc": "2.0", "method": "listwallets", "params": [], "id":1 }' -H 'content-type: application/json' http://127.0.0.1:8332/
+		#[rpc(name = "listwallets")]
+		fn list_wallets(&self) -> Result<Vec<String>, Error>;
+		/// Derive new wallet address.
+		/// @curl-example: curl --data-binary '{"jsonrpc": "2.0", "method": "getnewaddress", "params": [], "id":1 }' -H 'content-type: application/json' http://127.0.0.1:8332/
+		#[rpc(meta, name = "getnewaddress")]
+		fn get_new_address(&self, Self::Metadata) -> Result<String, Error>;
+		/// Get confirmed, unconfirmed and immature balance of wallet in BTC.
+		/// @curl-example: curl --data-binary '{"jsonrpc": "2.0", "method": "getbalance", "params": [], "id":1 }' -H 'content-type: application/json' http://127.0.0.1:8332/
+		#[rpc(meta, name = "getbalance")]
+		fn get_balance(&self, Self::Metadata) -> Result<WalletBalance, Error>;
+		/// Send amount in BTC to legacy or bech32 address with optional fee rate in satoshis per virtual byte. Returns transaction hash.
+		/// @curl-example: curl --data-binary '{"jsonrpc": "2.0", "method": "sendtoaddress", "params": ["1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa", 0.1, 10], "id":1 }' -H 'content-type: application/json' http://127.0.0.1:8332/
+		#[rpc(meta, name = "sendtoaddress")]
+		fn send_to_address(&self, Self::Metadata, String, f64, Trailing<u64>) -> Result<H256, Error>;
+		/// Store hex encoded data in zero value OP_RETURN output with optional fee rate in satoshis per virtual byte. Returns transaction hash.
+		/// @curl-example: curl --data-binary '{"jsonrpc": "2.0", "method": "senddata", "params": ["e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855", 10], "id":1 }' -H 'content-type: application/json' http://127.0.0.1:8332/
+		#[rpc(meta, name = "senddata")]
+		fn send_data(&self, Self::Metadata, Bytes, Trailing<u64>) -> Result<H256, Error>;
+		/// List unspent outputs of wallet keys and watch-only addresses.
+		/// @curl-example: curl --data-binary '{"jsonrpc": "2.0", "method": "listunspent", "params": [], "id":1 }' -H 'content-type: application/json' http://127.0.0.1:8332/
+		#[rpc(meta, name = "listunspent")]
+		fn list_unspent(&self, Self::Metadata) -> Result<Vec<UnspentOutput>, Error>;
+		/// Reveal private key of wallet address in WIF.
+		/// @curl-example: curl --data-binary '{"jsonrpc": "2.0", "method": "dumpprivkey", "params": ["1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa"], "id":1 }' -H 'content-type: application/json' http://127.0.0.1:8332/
+		#[rpc(meta, name = "dumpprivkey")]
+		fn dump_private_key(&self, Self::Metadata, String) -> Result<String, Error>;
+		/// Add private key in WIF to wallet.
+		/// @curl-example: curl --data-binary '{"jsonrpc": "2.0", "method": "importprivkey", "params": ["KwDiBf89QgGbjEhKnhXJuH7LrciVrZi3qYjgd9M7rFU73sVHnoWn"], "id":1 }' -H 'content-type: application/json' http://127.0.0.1:8332/
+		#[rpc(meta, name = "importprivkey")]
+		fn import_private_key(&self, Self::Metadata, String) -> Result<(), Error>;
+		/// Get wallet transaction with given hash.
+		/// @curl-example: curl --data-binary '{"jsonrpc": "2.0", "method": "gettransaction", "params": ["5600000000000000000000000000000000000000000000000000000000000000"], "id":1 }' -H 'content-type: application/json' http://127.0.0.1:8332/
+		#[rpc(meta, name = "gettransaction")]
+		fn get_transaction(&self, Self::Metadata, H256) -> Result<WalletTransaction, Error>;
+		/// Scan blocks starting from given height, whole chain by default, for wallet transactions.
+		/// @curl-example: curl --data-binary '{"jsonrpc": "2.0", "method": "rescan", "params": [100], "id":1 }' -H 'content-type: application/json' http://127.0.0.1:8332/
+		#[rpc(meta, name = "rescan")]
+		fn rescan(&self, Self::Metadata, Trailing<u32>) -> Result<RescanResponse, Error>;
+		/// Lock outputs, so wallet doesn't spend them, if the first parameter is false. Unlock them otherwise. Unlocking without outputs unlocks all of them.
+		/// @curl-example: curl --data-binary '{"jsonrpc": "2.0", "method": "lockunspent", "params": [false, [{"txid": "5600000000000000000000000000000000000000000000000000000000000000", "vout": 0}]], "id":1 }' -H 'content-type: application/json' http://127.0.0.1:8332/
+		#[rpc(meta, name = "lockunspent")]
+		fn lock_unspent(&self, Self::Metadata, bool, Trailing<Vec<OutPoint>>) -> Result<bool, Error>;
+		/// List locked outputs.
+		/// @curl-example: curl --data-binary '{"jsonrpc": "2.0", "method": "listlockunspent", "params": [], "id":1 }' -H 'content-type: application/json' http://127.0.0.1:8332/
+		#[rpc(meta, name = "listlockunspent")]
+		fn list_lock_unspent(&self, Self::Metadata) -> Result<Vec<OutPoint>, Error>;
+		/// List incoming and outgoing wallet transactions from blockchain and memory pool.
+		/// @curl-example: curl --data-binary '{"jsonrpc": "2.0", "method": "listtransactions", "params": [], "id":1 }' -H 'content-type: application/json' http://127.0.0.1:8332/
+		#[rpc(meta, name = "listtransactions")]
+		fn list_transactions(&self, Self::Metadata) -> Result<Vec<WalletTransaction>, Error>;
+		/// Watch address without its private key. Its coins count in balance and history, but can't be spent.
+		/// @curl-example: curl --data-binary '{"jsonrpc": "2.0", "method": "importaddress", "params": ["1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa"], "id":1 }' -H 'content-type: application/json' http://127.0.0.1:8332/
+		#[rpc(meta, name = "importaddress")]
+		fn import_address(&self, Self::Metadata, String) -> Result<(), Error>;
+		/// Name address with label. Empty label removes the name.
+		/// @curl-example: curl --data-binary '{"jsonrpc": "2.0", "method": "setlabel", "params": ["1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa", "savings"], "id":1 }' -H 'content-type: application/json' http://127.0.0.1:8332/
+		#[rpc(meta, name = "setlabel")]
+		fn set_label(&self, Self::Metadata, String, String) -> Result<(), Error>;
+		/// List addresses named with label.
+		/// @curl-example: curl --data-binary '{"jsonrpc": "2.0", "method": "getaddressesbylabel", "params": ["savings"], "id":1 }' -H 'content-type: application/json' http://127.0.0.1:8332/
+		#[rpc(meta, name = "getaddressesbylabel")]
+		fn get_addresses_by_label(&self, Self::Metadata, String) -> Result<Vec<String>, Error>;
+		/// Create base64 encoded partially signed transaction from unsigned raw transaction.
+		/// @curl-example: curl --data-binary '{"jsonrpc": "2.0", "method": "createpsbt", "params": ["0200000001c3a1b5a1ee01ee3a7f0f48d4d5b7ec6d0e8c9a3bc5a0d3a3e6f8e4f1ad0b2e460100000000ffffffff0150c3000000000000160014751e76e8199196d454941c45d1b3a323f1433bd600000000"], "id":1 }' -H 'content-type: application/json' http://127.0.0.1:8332/
+		#[rpc(name = "createpsbt")]
+		fn create_psbt(&self, RawTransaction) -> Result<String, Error>;
+		/// Add outputs spent by PSBT inputs and witness scripts known to wallet.
+		/// @curl-example: curl --data-binary '{"jsonrpc": "2.0", "method": "updatepsbt", "params": ["cHNidP8BAFICAAAAAcOhtaHuAe46fw9I1NW37G0OjJo7xaDTo+b45PGtCy5GAQAAAAD/////AVDDAAAAAAAAFgAUdR526BmRltRUlBxF0bOjI/FDO9YAAAAAAAAA"], "id":1 }' -H 'content-type: application/json' http://127.0.0.1:8332/
+		#[rpc(meta, name = "updatepsbt")]
+		fn update_psbt(&self, Self::Metadata, String) -> Result<String, Error>;
+		/// Add signatures of wallet keys to PSBT inputs.
+		/// @curl-example: curl --data-binary '{"jsonrpc": "2.0", "method": "signpsbt", "params": ["cHNidP8BAFICAAAAAcOhtaHuAe46fw9I1NW37G0OjJo7xaDTo+b45PGtCy5GAQAAAAD/////AVDDAAAAAAAAFgAUdR526BmRltRUlBxF0bOjI/FDO9YAAAAAAAAA"], "id":1 }' -H 'content-type: application/json' http://127.0.0.1:8332/
+		#[rpc(meta, name = "signpsbt")]
+		fn sign_psbt(&self, Self::Metadata, String) -> Result<String, Error>;
+		/// Merge signatures and other data of several PSBTs for the same transaction.
+		/// @curl-example: curl --data-binary '{"jsonrpc": "2.0", "method": "combinepsbt", "params": [["cHNidP8BAFICAAAAAcOhtaHuAe46fw9I1NW37G0OjJo7xaDTo+b45PGtCy5GAQAAAAD/////AVDDAAAAAAAAFgAUdR526BmRltRUlBxF0bOjI/FDO9YAAAAAAAAA", "cHNidP8BAFICAAAAAcOhtaHuAe46fw9I1NW37G0OjJo7xaDTo+b45PGtCy5GAQAAAAD/////AVDDAAAAAAAAFgAUdR526BmRltRUlBxF0bOjI/FDO9YAAAAAAAAA"]], "id":1 }' -H 'content-type: application/json' http://127.0.0.1:8332/
+		#[rpc(name = "combinepsbt")]
+		fn combine_psbt(&self, Vec<String>) -> Result<String, Error>;
+		/// Build final scripts of PSBT inputs which have enough signatures.
+		/// @curl-example: curl --data-binary '{"jsonrpc": "2.0", "method": "finalizepsbt", "params": ["cHNidP8BAFICAAAAAcOhtaHuAe46fw9I1NW37G0OjJo7xaDTo+b45PGtCy5GAQAAAAD/////AVDDAAAAAAAAFgAUdR526BmRltRUlBxF0bOjI/FDO9YAAAAAAAAA"], "id":1 }' -H 'content-type: application/json' http://127.0.0.1:8332/
+		#[rpc(name = "finalizepsbt")]
+		fn finalize_psbt(&self, String) -> Result<FinalizePsbtResponse, Error>;
+		/// Extract signed raw transaction from finalized PSBT.
+		/// @curl-example: curl --data-binary '{"jsonrpc": "2.0", "method": "extractpsbt", "params": ["cHNidP8BAFICAAAAAcOhtaHuAe46fw9I1NW37G0OjJo7xaDTo+b45PGtCy5GAQAAAAD/////AVDDAAAAAAAAFgAUdR526BmRltRUlBxF0bOjI/FDO9YAAAAAAAAA"], "id":1 }' -H 'content-type: application/json' http://127.0.0.1:8332/
+		#[rpc(name = "extractpsbt")]
+		fn extract_psbt(&self, String) -> Result<RawTransaction, Error>;
+		/// Sign message with key of wallet address to prove its ownership. Returns base64 encoded signature.
+		/// @curl-example: curl --data-binary '{"jsonrpc": "2.0", "method": "signmessage", "params": ["1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH", "I own this address"], "id":1 }' -H 'content-type: application/json' http://127.0.0.1:8332/
+		#[rpc(meta, name = "signmessage")]
+		fn sign_message(&self, Self::Metadata, String, String) -> Result<String, Error>;
+		/// Check that base64 encoded signature of message is made by key of address.
+		/// @curl-example: curl --data-binary '{"jsonrpc": "2.0", "method": "verifymessage", "params": ["1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH", "H1W0...", "I own this address"], "id":1 }' -H 'content-type: application/json' http://127.0.0.1:8332/
+		#[rpc(name = "verifymessage")]
+		fn verify_message(&self, String, String, String) -> Result<bool, Error>;
+	}
+}
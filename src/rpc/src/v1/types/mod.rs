@@ -7,9 +7,15 @@ mod get_block_response;
 mod get_tx_out_response;
 mod get_tx_out_set_info_response;
 mod hash;
+mod out_point;
+mod psbt;
+mod rescan;
 mod script;
 mod transaction;
 mod uint;
+mod unspent_output;
+mod wallet_balance;
+mod wallet_transaction;
 mod nodes;
 
 pub use self::block::RawBlock;
@@ -20,10 +26,16 @@ pub use self::get_block_response::{GetBlockResponse, VerboseBlock};
 pub use self::get_tx_out_response::GetTxOutResponse;
 pub use self::get_tx_out_set_info_response::GetTxOutSetInfoResponse;
 pub use self::hash::{H160, H256};
+pub use self::psbt::FinalizePsbtResponse;
 pub use self::script::ScriptType;
+pub use self::out_point::OutPoint;
+pub use self::rescan::RescanResponse;
 pub use self::transaction::{RawTransaction, Transaction, TransactionInput, TransactionOutput,
 	TransactionOutputWithAddress, TransactionOutputWithScriptData, TransactionInputScript,
 	TransactionOutputScript, SignedTransactionInput, GetRawTransactionResponse,
 	SignedTransactionOutput, TransactionOutputs};
 pub use self::uint::U256;
+pub use self::unspent_output::UnspentOutput;
+pub use self::wallet_balance::WalletBalance;
 pub use self::nodes::{AddNodeOperation, NodeInfo};
+pub use self::wallet_transaction::{WalletTransaction, WalletTransactionCategory};
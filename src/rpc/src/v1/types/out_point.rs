@@ -0,0 +1,26 @@
+use super::hash::H256;
+
+/// Reference to transaction output
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct OutPoint {
+	/// Hash of transaction which created output
+	pub txid: H256,
+	/// Index of output in transaction
+	pub vout: u32,
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json;
+	use super::super::hash::H256;
+	use super::*;
+
+	#[test]
+	fn out_point_deserialize() {
+		let out_point: OutPoint = serde_json::from_str(r#"{"txid":"5600000000000000000000000000000000000000000000000000000000000000","vout":1}"#).unwrap();
+		assert_eq!(out_point, OutPoint {
+			txid: H256::from(0x56),
+			vout: 1,
+		});
+	}
+}
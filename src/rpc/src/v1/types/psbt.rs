@@ -0,0 +1,23 @@
+/// finalizepsbt response
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct FinalizePsbtResponse {
+	/// Base64 encoded PSBT with final scripts of inputs which have enough signatures
+	pub psbt: String,
+	/// Whether every input is finalized, so transaction can be extracted
+	pub complete: bool,
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json;
+	use super::*;
+
+	#[test]
+	fn finalize_psbt_response_serialize() {
+		let response = FinalizePsbtResponse {
+			psbt: "cHNidP8=".to_owned(),
+			complete: false,
+		};
+		assert_eq!(serde_json::to_string(&response).unwrap(), r#"{"psbt":"cHNidP8=","complete":false}"#);
+	}
+}
@@ -0,0 +1,23 @@
+/// rescan response
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct RescanResponse {
+	/// Height of the first scanned block
+	pub start_height: u32,
+	/// Height of the last scanned block
+	pub stop_height: u32,
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json;
+	use super::*;
+
+	#[test]
+	fn rescan_response_serialize() {
+		let response = RescanResponse {
+			start_height: 10,
+			stop_height: 20,
+		};
+		assert_eq!(serde_json::to_string(&response).unwrap(), r#"{"start_height":10,"stop_height":20}"#);
+	}
+}
@@ -0,0 +1,44 @@
+use super::bytes::Bytes;
+use super::hash::H256;
+
+/// listunspent response entry
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct UnspentOutput {
+	/// Hash of transaction which created this output
+	pub txid: H256,
+	/// Index of output in transaction
+	pub vout: u32,
+	/// Address this output pays to
+	pub address: Option<String>,
+	/// Output script
+	#[serde(rename = "scriptPubKey")]
+	pub script_pubkey: Bytes,
+	/// Output value in BTC
+	pub amount: f64,
+	/// Number of confirmations of transaction. Zero for memory pool transactions
+	pub confirmations: u32,
+	/// False for outputs of watch-only addresses
+	pub spendable: bool,
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json;
+	use super::super::bytes::Bytes;
+	use super::super::hash::H256;
+	use super::*;
+
+	#[test]
+	fn unspent_output_serialize() {
+		let output = UnspentOutput {
+			txid: H256::from(0x56),
+			vout: 1,
+			address: Some("1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa".to_owned()),
+			script_pubkey: Bytes::new(vec![0, 20]),
+			amount: 0.5,
+			confirmations: 0,
+			spendable: true,
+		};
+		assert_eq!(serde_json::to_string(&output).unwrap(), r#"{"txid":"5600000000000000000000000000000000000000000000000000000000000000","vout":1,"address":"1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa","scriptPubKey":"0014","amount":0.5,"confirmations":0,"spendable":true}"#);
+	}
+}
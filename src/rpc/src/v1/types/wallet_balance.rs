@@ -0,0 +1,26 @@
+/// getbalance response. Amounts are in BTC
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct WalletBalance {
+	/// Outputs of transactions included in blocks
+	pub confirmed: f64,
+	/// Outputs of transactions which are still in memory pool
+	pub unconfirmed: f64,
+	/// Coinbase outputs which can't be spent until they reach maturity
+	pub immature: f64,
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json;
+	use super::*;
+
+	#[test]
+	fn wallet_balance_serialize() {
+		let balance = WalletBalance {
+			confirmed: 1.5,
+			unconfirmed: 0.25,
+			immature: 50.0,
+		};
+		assert_eq!(serde_json::to_string(&balance).unwrap(), r#"{"confirmed":1.5,"unconfirmed":0.25,"immature":50.0}"#);
+	}
+}
@@ -0,0 +1,75 @@
+use super::hash::H256;
+
+/// Direction of wallet transaction
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+pub enum WalletTransactionCategory {
+	/// Transaction spends wallet coins
+	#[serde(rename="send")]
+	Send,
+	/// Transaction pays to wallet keys
+	#[serde(rename="receive")]
+	Receive,
+	/// Coinbase transaction pays to wallet keys
+	#[serde(rename="generate")]
+	Generate,
+}
+
+/// listtransactions response entry
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct WalletTransaction {
+	/// Transaction hash
+	pub txid: H256,
+	/// Transaction direction
+	pub category: WalletTransactionCategory,
+	/// Received amount if positive, sent amount if negative, in BTC. Fee is not included
+	pub amount: f64,
+	/// Transaction fee in BTC. Known only when every input spends wallet coins
+	pub fee: Option<f64>,
+	/// Recipient of sent coins or sender of received ones
+	pub address: Option<String>,
+	/// Label of recipient of sent coins or of wallet address which received coins
+	pub label: Option<String>,
+	/// Height of block containing transaction. None for memory pool transactions
+	pub blockheight: Option<u32>,
+	/// Number of confirmations of this transaction
+	pub confirmations: u32,
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json;
+	use super::super::hash::H256;
+	use super::*;
+
+	#[test]
+	fn wallet_transaction_serialize() {
+		let transaction = WalletTransaction {
+			txid: H256::from(0x56),
+			category: WalletTransactionCategory::Send,
+			amount: -0.5,
+			fee: Some(0.0001),
+			address: Some("1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa".to_owned()),
+			label: Some("exchange".to_owned()),
+			blockheight: None,
+			confirmations: 0,
+		};
+		assert_eq!(serde_json::to_string(&transaction).unwrap(), r#"{"txid":"5600000000000000000000000000000000000000000000000000000000000000","category":"send","amount":-0.5,"fee":0.0001,"address":"1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa","label":"exchange","blockheight":null,"confirmations":0}"#);
+	}
+
+	#[test]
+	fn wallet_transaction_deserialize() {
+		let transaction = WalletTransaction {
+			txid: H256::from(0x56),
+			category: WalletTransactionCategory::Generate,
+			amount: 50.0,
+			fee: None,
+			address: None,
+			label: None,
+			blockheight: Some(12),
+			confirmations: 3,
+		};
+		assert_eq!(
+			serde_json::from_str::<WalletTransaction>(r#"{"txid":"5600000000000000000000000000000000000000000000000000000000000000","category":"generate","amount":50.0,"fee":null,"address":null,"label":null,"blockheight":12,"confirmations":3}"#).unwrap(),
+			transaction);
+	}
+}
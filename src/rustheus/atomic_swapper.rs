@@ -10,9 +10,10 @@ use chain::bytes::Bytes;
 use chain::{Transaction};
 use crypto::{dhash160, sha256};
 use std::time::{SystemTime, UNIX_EPOCH};
-use wallet::WalletRef;
+use wallet::{WalletRef, WalletError};
 use message::types::Tx;
-use transaction_helper::{TransactionHelperRef, SignError, FundError};
+use transaction_helper::{TransactionHelperRef, SignError, FundError, DEFAULT_FEE_RATE};
+use coin_selection::CoinSelection;
 use std::sync::mpsc::Receiver;
 use ser::{deserialize, serialize, Reader};
 use script::Error as ScriptError;
@@ -31,6 +32,7 @@ pub enum ContractError
 {
     FundError(FundError),
     SignError(SignError),
+    WalletError(WalletError),
 }
 
 #[derive(Debug)]
@@ -60,11 +62,17 @@ impl From<FundError> for ContractError {
     }
 }
 
+impl From<WalletError> for ContractError {
+    fn from(err: WalletError) -> ContractError {
+        ContractError::WalletError(err)
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub enum Task {
     //atomic swaps
-    Initiate(Address, u64),
-    Participate(Address, u64, H256),
+    Initiate(Address, u64, CoinSelection),
+    Participate(Address, u64, H256, CoinSelection),
     Redeem(Bytes, Bytes, Bytes),
     ExtractSecret(H256, H256),
     AuditContract(Bytes, Bytes),
@@ -76,6 +84,7 @@ struct ContractArgs {
     amount:     u64,
     locktime:   u32,
     secret_hash: H256,
+    selection:  CoinSelection,
 }
 
 struct BuiltContract {
@@ -97,6 +106,7 @@ struct AtomicSwapDataPushes {
 	LockTime:         i64,  //TODO why is this signed?
 }
 
+/// Runs atomic swaps with default wallet. Wallet selected by `usewallet` doesn't apply to them
 pub struct AtomicSwapper {
     acceptor: AcceptorRef,
     cpupool: CpuPool,
@@ -129,8 +139,8 @@ impl AtomicSwapper {
         loop {
             if let Ok(task) = self.task_receiver.recv() {
                 match task {
-                    Task::Initiate(address, amount) => self.initiate(address, amount),
-                    Task::Participate(address, amount, secret_hash) => self.participate(address, amount, secret_hash),
+                    Task::Initiate(address, amount, selection) => self.initiate(address, amount, selection),
+                    Task::Participate(address, amount, secret_hash, selection) => self.participate(address, amount, secret_hash, selection),
                     Task::Redeem(contract, contract_transaction, secret) => self.redeem(contract, contract_transaction, secret),
                     Task::ExtractSecret(transaction, secret) => self.extract_secret(transaction, secret),
                     Task::AuditContract(contract, contract_transaction) => self.audit_contract(contract, contract_transaction),
@@ -141,7 +151,7 @@ impl AtomicSwapper {
         }
     }
 
-    fn initiate(&self, address: Address, amount: u64) {
+    fn initiate(&self, address: Address, amount: u64, selection: CoinSelection) {
         //TODO check if correct network
 		//let mut secret = [u8; 32];
         let mut secret: [u8; SECRET_SIZE] = [0u8; SECRET_SIZE];
@@ -166,6 +176,7 @@ impl AtomicSwapper {
             amount:     amount,
             locktime:   locktime as u32,    //TODO check if u32 is suitable
             secret_hash: secret_hash,
+            selection:  selection,
         });
 
         let contract = match contract {
@@ -193,13 +204,19 @@ impl AtomicSwapper {
         println!("{:?}\n", contract.refundTx);
 
         let message_wrapper = self.message_wrapper.to_owned();
+        let transaction_helper = self.transaction_helper.clone();
+        let contract_transaction = contract.contractTx.clone();
         let task = self.acceptor.async_accept_transaction(contract.contractTx.clone())
-            .map(move |transaction| message_wrapper.broadcast(&Tx::with_transaction(transaction)));
+            .map(move |transaction| message_wrapper.broadcast(&Tx::with_transaction(transaction)))
+            .then(move |result| {
+                transaction_helper.release_inputs(&contract_transaction);
+                result
+            });
 
         let _ = self.cpupool.spawn(task);
     }
     
-    fn participate(&self, address: Address, amount: u64, secret_hash: H256) {
+    fn participate(&self, address: Address, amount: u64, secret_hash: H256, selection: CoinSelection) {
         let current_time = SystemTime::now();
         let time_since_the_epoch = current_time
             .duration_since(UNIX_EPOCH)
@@ -211,6 +228,7 @@ impl AtomicSwapper {
             amount:     amount,
             locktime:   locktime as u32,
             secret_hash: secret_hash,
+            selection:  selection,
         });
 
         let contract = match contract {
@@ -237,8 +255,14 @@ impl AtomicSwapper {
         println!("{:?}\n", contract.refundTx);
 
         let message_wrapper = self.message_wrapper.to_owned();
+        let transaction_helper = self.transaction_helper.clone();
+        let contract_transaction = contract.contractTx.clone();
         let task = self.acceptor.async_accept_transaction(contract.contractTx.clone())
-            .map(move |transaction| message_wrapper.broadcast(&Tx::with_transaction(transaction)));
+            .map(move |transaction| message_wrapper.broadcast(&Tx::with_transaction(transaction)))
+            .then(move |result| {
+                transaction_helper.release_inputs(&contract_transaction);
+                result
+            });
         
         let _ = self.cpupool.spawn(task);
     }
@@ -292,7 +316,13 @@ impl AtomicSwapper {
             }
         };
 
-        let recipientAddr = self.wallet.write().new_keypair();
+        let recipientAddr = match self.wallet.write().new_keypair() {
+            Ok(address) => address,
+            Err(err) => {
+                error!("Cannot create redeem address: {:?}", err);
+                return;
+            }
+        };
         let wallet = self.wallet.read();
         let key = wallet.find_keypair_with_public_hash(&recipientAddr.hash).unwrap(); //HACK
 
@@ -432,7 +462,7 @@ impl AtomicSwapper {
     }
 
     fn buildContract(&self, args: ContractArgs) -> Result<BuiltContract, ContractError> {
-        let refund_address_hash = self.wallet.write().new_keypair().hash;
+        let refund_address_hash = self.wallet.write().new_keypair()?.hash;
 
         let contract = atomicSwapContract(refund_address_hash, args.them,
             args.locktime, args.secret_hash);
@@ -447,8 +477,7 @@ impl AtomicSwapper {
 
         let transaction: Transaction = TransactionBuilder::with_output_and_pubkey(args.amount, contractP2SHPkScript.to_bytes()).into();
 
-        let funded_transaction = self.transaction_helper.fund_transaction(transaction)?;
-        let contractTx = self.transaction_helper.sign_transaction(funded_transaction)?;
+        let contractTx = self.transaction_helper.fund_and_sign_transaction(transaction, DEFAULT_FEE_RATE, args.selection)?;
 
         let contractFee = 0u64;
 
@@ -0,0 +1,294 @@
+use chain::{OutPoint, TransactionOutput};
+use rand::{thread_rng, Rng};
+use std::str::FromStr;
+
+/// Limits number of branches explored by branch and bound search
+const BNB_MAX_TRIES: usize = 100_000;
+
+/// Unspent wallet output which can be used as transaction input
+#[derive(Debug, Clone)]
+pub struct Coin {
+    pub out_point: OutPoint,
+    pub output: TransactionOutput,
+    /// Output value minus fee for spending it
+    pub effective_value: u64,
+}
+
+pub trait CoinSelector {
+    /// Picks coins with total effective value of at least `target`. Exceeding `target` by less
+    /// than `cost_of_change` means change isn't worth creating
+    fn select(&self, coins: Vec<Coin>, target: u64, cost_of_change: u64) -> Option<Vec<Coin>>;
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum CoinSelection {
+    LargestFirst,
+    SmallestFirst,
+    BranchAndBound,
+    RandomImprove,
+}
+
+impl CoinSelection {
+    pub fn selector(&self) -> Box<CoinSelector> {
+        match *self {
+            CoinSelection::LargestFirst => Box::new(LargestFirst),
+            CoinSelection::SmallestFirst => Box::new(SmallestFirst),
+            CoinSelection::BranchAndBound => Box::new(BranchAndBound),
+            CoinSelection::RandomImprove => Box::new(RandomImprove),
+        }
+    }
+}
+
+impl Default for CoinSelection {
+    fn default() -> Self {
+        CoinSelection::LargestFirst
+    }
+}
+
+impl FromStr for CoinSelection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "largest" => Ok(CoinSelection::LargestFirst),
+            "smallest" => Ok(CoinSelection::SmallestFirst),
+            "bnb" => Ok(CoinSelection::BranchAndBound),
+            "random" => Ok(CoinSelection::RandomImprove),
+            _ => Err(format!("Unknown coin selection {}. Use one of: largest, smallest, bnb, random", s)),
+        }
+    }
+}
+
+/// Takes coins in given order until their sum reaches target
+fn accumulate<I: IntoIterator<Item = Coin>>(coins: I, target: u64) -> Option<Vec<Coin>> {
+    let mut sum = 0;
+    let mut selected = vec![];
+    for coin in coins {
+        sum += coin.effective_value;
+        selected.push(coin);
+        if sum >= target {
+            return Some(selected);
+        }
+    }
+    None
+}
+
+/// Spends as few coins as possible
+pub struct LargestFirst;
+
+impl CoinSelector for LargestFirst {
+    fn select(&self, mut coins: Vec<Coin>, target: u64, _cost_of_change: u64) -> Option<Vec<Coin>> {
+        coins.sort_by(|a, b| b.effective_value.cmp(&a.effective_value));
+        accumulate(coins, target)
+    }
+}
+
+/// Consolidates small coins
+pub struct SmallestFirst;
+
+impl CoinSelector for SmallestFirst {
+    fn select(&self, mut coins: Vec<Coin>, target: u64, _cost_of_change: u64) -> Option<Vec<Coin>> {
+        coins.sort_by(|a, b| a.effective_value.cmp(&b.effective_value));
+        accumulate(coins, target)
+    }
+}
+
+/// Searches for coins matching target closely enough not to create change.
+/// Fails if there is no such combination
+pub struct BranchAndBound;
+
+struct BnbSearch<'a> {
+    coins: &'a [Coin],
+    target: u64,
+    max_value: u64,
+    tries: usize,
+    selected: Vec<usize>,
+    best: Option<(Vec<usize>, u64)>,
+}
+
+impl<'a> BnbSearch<'a> {
+    /// Explores branches with and without coin at `index`
+    fn search(&mut self, index: usize, value: u64, remaining: u64) {
+        if self.tries == 0 || self.best.as_ref().map_or(false, |&(_, excess)| excess == 0) {
+            return;
+        }
+        self.tries -= 1;
+
+        if value > self.max_value || value + remaining < self.target {
+            return;
+        }
+
+        if value >= self.target {
+            let excess = value - self.target;
+            if self.best.as_ref().map_or(true, |&(_, best_excess)| excess < best_excess) {
+                self.best = Some((self.selected.clone(), excess));
+            }
+            // adding more coins only increases excess
+            return;
+        }
+
+        if index == self.coins.len() {
+            return;
+        }
+
+        let coin_value = self.coins[index].effective_value;
+        self.selected.push(index);
+        self.search(index + 1, value + coin_value, remaining - coin_value);
+        self.selected.pop();
+        self.search(index + 1, value, remaining - coin_value);
+    }
+}
+
+impl CoinSelector for BranchAndBound {
+    fn select(&self, mut coins: Vec<Coin>, target: u64, cost_of_change: u64) -> Option<Vec<Coin>> {
+        // trying large coins first finds solutions faster
+        coins.sort_by(|a, b| b.effective_value.cmp(&a.effective_value));
+        let total = coins.iter().map(|coin| coin.effective_value).sum();
+
+        let max_value = target.checked_add(cost_of_change)?;
+        let best = {
+            let mut search = BnbSearch {
+                coins: &coins,
+                target,
+                max_value,
+                tries: BNB_MAX_TRIES,
+                selected: vec![],
+                best: None,
+            };
+            search.search(0, 0, total);
+            search.best
+        };
+
+        best.map(|(indexes, _)| indexes.into_iter().map(|index| coins[index].clone()).collect())
+    }
+}
+
+/// Picks random coins until target is reached, then keeps adding random coins while it brings
+/// sum closer to twice the target. Resulting change is similar in size to payment, which
+/// keeps coins from fragmenting and makes it harder to tell payment from change
+pub struct RandomImprove;
+
+impl CoinSelector for RandomImprove {
+    fn select(&self, mut coins: Vec<Coin>, target: u64, _cost_of_change: u64) -> Option<Vec<Coin>> {
+        thread_rng().shuffle(&mut coins);
+
+        let mut remaining = coins.into_iter();
+        let mut selected = accumulate(remaining.by_ref(), target)?;
+
+        let ideal = target.saturating_mul(2);
+        let max = target.saturating_mul(3);
+        let distance = |value: u64| if value > ideal { value - ideal } else { ideal - value };
+        let mut sum: u64 = selected.iter().map(|coin| coin.effective_value).sum();
+        for coin in remaining {
+            let new_sum = sum + coin.effective_value;
+            if new_sum <= max && distance(new_sum) < distance(sum) {
+                sum = new_sum;
+                selected.push(coin);
+            }
+        }
+
+        Some(selected)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chain::{OutPoint, TransactionOutput};
+    use primitives::hash::H256;
+    use super::{Coin, CoinSelector, LargestFirst, SmallestFirst, BranchAndBound, BnbSearch, RandomImprove, BNB_MAX_TRIES};
+
+    fn coins(values: &[u64]) -> Vec<Coin> {
+        values
+            .iter()
+            .enumerate()
+            .map(|(index, &value)| Coin {
+                out_point: OutPoint {
+                    hash: H256::from(index as u8),
+                    index: 0,
+                },
+                output: TransactionOutput {
+                    value,
+                    script_pubkey: Default::default(),
+                },
+                effective_value: value,
+            })
+            .collect()
+    }
+
+    fn values(selected: Option<Vec<Coin>>) -> Option<Vec<u64>> {
+        selected.map(|coins| coins.into_iter().map(|coin| coin.effective_value).collect())
+    }
+
+    #[test]
+    fn largest_first_spends_largest_coins() {
+        let selected = LargestFirst.select(coins(&[1, 5, 3, 10]), 12, 0);
+        assert_eq!(values(selected), Some(vec![10, 5]));
+    }
+
+    #[test]
+    fn largest_first_fails_on_insufficient_funds() {
+        assert!(LargestFirst.select(coins(&[1, 5, 3, 10]), 20, 0).is_none());
+    }
+
+    #[test]
+    fn smallest_first_spends_smallest_coins() {
+        let selected = SmallestFirst.select(coins(&[10, 3, 1, 5]), 8, 0);
+        assert_eq!(values(selected), Some(vec![1, 3, 5]));
+    }
+
+    #[test]
+    fn smallest_first_fails_on_insufficient_funds() {
+        assert!(SmallestFirst.select(coins(&[10, 3, 1, 5]), 20, 0).is_none());
+    }
+
+    #[test]
+    fn branch_and_bound_finds_exact_match() {
+        let selected = BranchAndBound.select(coins(&[1, 5, 3, 10, 7]), 8, 0);
+        assert_eq!(values(selected), Some(vec![7, 1]));
+    }
+
+    #[test]
+    fn branch_and_bound_accepts_excess_below_cost_of_change() {
+        let selected = BranchAndBound.select(coins(&[10, 4]), 9, 1);
+        assert_eq!(values(selected), Some(vec![10]));
+    }
+
+    #[test]
+    fn branch_and_bound_fails_without_changeless_solution() {
+        assert!(BranchAndBound.select(coins(&[10, 20]), 5, 1).is_none());
+        assert!(BranchAndBound.select(coins(&[10, 20]), 40, 1).is_none());
+    }
+
+    #[test]
+    fn branch_and_bound_fails_on_target_overflow() {
+        assert!(BranchAndBound.select(coins(&[10, 20]), u64::max_value(), 1).is_none());
+    }
+
+    #[test]
+    fn branch_and_bound_gives_up_after_max_tries() {
+        // sums of even coins never match odd target, so the whole tree would be explored without limit
+        let coins = coins(&[2; 40]);
+        let mut search = BnbSearch {
+            coins: &coins,
+            target: 41,
+            max_value: 41,
+            tries: BNB_MAX_TRIES,
+            selected: vec![],
+            best: None,
+        };
+        search.search(0, 0, 80);
+        assert_eq!(search.tries, 0);
+        assert!(search.best.is_none());
+    }
+
+    #[test]
+    fn random_improve_aims_at_twice_the_target() {
+        let selected = RandomImprove.select(coins(&[1; 100]), 10, 0).unwrap();
+        assert_eq!(selected.len(), 20);
+    }
+
+    #[test]
+    fn random_improve_fails_on_insufficient_funds() {
+        assert!(RandomImprove.select(coins(&[1; 5]), 10, 0).is_none());
+    }
+}
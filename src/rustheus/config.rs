@@ -11,7 +11,8 @@ pub struct Config {
 	pub number: u16,
 	pub telnet_port: u16,
 	pub rpc_config: RpcHttpConfig,
-
+	/// Command run for every transaction touching wallet addresses
+	pub wallet_notify: Option<String>,
 }
 
 pub const DEFAULT_DB_CACHE: usize = 512;
@@ -39,13 +40,16 @@ pub fn parse(matches: &clap::ArgMatches) -> Result<Config, String> {
 	let mut rpc_config = parse_rpc_config(network, matches)?;
 	rpc_config.port += number;
 
+	let wallet_notify = matches.value_of("walletnotify").map(str::to_owned);
+
 	let config = Config {
 		is_first,
 		number,
 		network,
 		telnet_port,
 		consensus,
-		rpc_config
+		rpc_config,
+		wallet_notify,
 	};
 
 	Ok(config)
@@ -1,19 +1,49 @@
 use shrust::{Shell, ShellIO};
 use std::net::TcpListener;
-use std::sync::mpsc::Sender;
+use std::sync::mpsc::{self, Sender, SendError};
 use std::str::FromStr;
+use std::path::PathBuf;
 use executor::Task as ExecutorTask;
-use keys::{Address, Private};
-use wallet_manager::Task as WalletTask;
+use chain::{OutPoint, Psbt};
+use keys::{Address, Private, Public, Mnemonic};
+use wallet_manager::{Task as WalletTask, Request as WalletRequest, Recipient};
+use wallets::DEFAULT_WALLET;
 use primitives::hash::H256;
 use primitives::bytes::Bytes;
 use atomic_swapper::Task as AtomicSwapperTask;
+use transaction_helper::{DEFAULT_FEE_RATE, TimeLock};
+use coin_selection::CoinSelection;
 
 //TODO please find a way to do this better. This tuple is needed to access senders from command closures
 type Senders = (Sender<ExecutorTask>,
-                Sender<WalletTask>,
+                WalletSender,
                 Sender<AtomicSwapperTask>);
 
+/// Sends wallet tasks for wallet selected in this telnet session, so sessions don't switch wallets of each other
+#[derive(Clone)]
+struct WalletSender {
+    sender: Sender<WalletRequest>,
+    /// Wallet selected with `usewallet` or `loadwallet`. Default wallet is used if there is none
+    wallet: Option<String>,
+}
+
+impl WalletSender {
+    fn send(&self, task: WalletTask) -> Result<(), SendError<WalletRequest>> {
+        self.sender.send((self.wallet.clone(), task))
+    }
+}
+
+/// Parses output reference of form <txid>:<vout>
+fn parse_out_point(s: &str) -> Result<OutPoint, String> {
+    let mut parts = s.splitn(2, ':');
+    let hash = parts.next().map(H256::from_str);
+    let index = parts.next().map(str::parse::<u32>);
+    match (hash, index) {
+        (Some(Ok(hash)), Some(Ok(index))) => Ok(OutPoint { hash, index }),
+        _ => Err(format!("Can't parse output {}, expected <txid>:<vout>", s)),
+    }
+}
+
 pub struct InputListener {
     port: u16,
     shell: Shell<Senders>,
@@ -24,7 +54,7 @@ impl InputListener {
     pub fn new(
         port: u16,
         executor: Sender<ExecutorTask>,
-        wallet_manager: Sender<WalletTask>,
+        wallet_manager: Sender<WalletRequest>,
         atomic_swapper: Sender<AtomicSwapperTask>,
         terminator: Sender<bool>,
     ) -> Self {
@@ -34,9 +64,13 @@ impl InputListener {
 
     fn create_shell(
         executor: Sender<ExecutorTask>,
-        wallet_manager: Sender<WalletTask>,
+        wallet_manager: Sender<WalletRequest>,
         atomic_swapper: Sender<AtomicSwapperTask>,
     ) -> Shell<Senders> {
+        let wallet_manager = WalletSender {
+            sender: wallet_manager,
+            wallet: None,
+        };
         let senders = (executor, wallet_manager, atomic_swapper);
 
         let mut shell = Shell::new(senders);
@@ -57,12 +91,38 @@ impl InputListener {
         );
         shell.new_command(
             "walletcreate",
-            "Create address and show private and public keys",
+            "Create address and show private and public keys. New wallet shows mnemonic of [12|24] words first",
             0,
-            |_, senders, _| {
+            |_, senders, args| {
                 let ref wallet_manager = senders.1;
+                let word_count = match args.get(0) {
+                    Some(arg) => match arg.parse::<usize>() {
+                        Ok(count) if count == 12 || count == 24 => count,
+                        _ => {
+                            error!("Mnemonic can have either 12 or 24 words");
+                            return Ok(());
+                        }
+                    },
+                    None => 12,
+                };
                 info!("Creating wallet...");
-                wallet_manager.send(WalletTask::CreateWallet())?;
+                wallet_manager.send(WalletTask::CreateWallet(word_count))?;
+                Ok(())
+            },
+        );
+        shell.new_command(
+            "walletrestore",
+            "Restore wallet keys from mnemonic <words...> and look for used addresses in blockchain",
+            12,
+            |_, senders, args| {
+                let ref wallet_manager = senders.1;
+                match Mnemonic::from_str(&args.join(" ")) {
+                    Ok(mnemonic) => {
+                        info!("Restoring wallet...");
+                        wallet_manager.send(WalletTask::RestoreWallet(mnemonic))?;
+                    }
+                    Err(err) => error!("Can't parse mnemonic: {}", err),
+                }
                 Ok(())
             },
         );
@@ -87,6 +147,146 @@ impl InputListener {
                 }
             },
         );
+        shell.new_command(
+            "dumpwallet",
+            "Write every wallet key in WIF with its creation time, label and address, watch-only addresses and multisig witness scripts to new <file>. HD master key is written only as a comment and importwallet doesn't restore HD seed, so keep mnemonic backup too",
+            1,
+            |_, senders, args| {
+                let ref wallet_manager = senders.1;
+                wallet_manager.send(WalletTask::DumpWallet(PathBuf::from(args[0])))?;
+                Ok(())
+            },
+        );
+        shell.new_command(
+            "importwallet",
+            "Add keys, watch-only addresses, witness scripts and labels from <file> written by dumpwallet and rescan blockchain for their transactions",
+            1,
+            |_, senders, args| {
+                let ref wallet_manager = senders.1;
+                wallet_manager.send(WalletTask::ImportWallet(PathBuf::from(args[0])))?;
+                Ok(())
+            },
+        );
+        shell.new_command(
+            "sweepprivkey",
+            "Move every coin of external private key to new wallet address without adding the key to wallet. Usage: sweepprivkey <wif> [fee rate in satoshis per virtual byte]",
+            1,
+            |_, senders, args| {
+                let ref wallet_manager = senders.1;
+                let fee_rate = match args.get(1).map(|arg| arg.parse::<u64>()) {
+                    Some(Ok(fee_rate)) => fee_rate,
+                    Some(Err(err)) => {
+                        error!("Can't parse fee rate: {}", err);
+                        return Ok(());
+                    }
+                    None => DEFAULT_FEE_RATE,
+                };
+                match Private::from_str(args[0]) {
+                    Ok(private) => wallet_manager.send(WalletTask::SweepPrivateKey(private, fee_rate))?,
+                    Err(err) => error!("Can't parse private key: {}", err),
+                }
+                Ok(())
+            },
+        );
+        shell.new_command(
+            "importaddress",
+            "Watch <address> without its private key. Its coins count in balance and history, but can't be spent",
+            1,
+            |_, senders, args| {
+                let ref wallet_manager = senders.1;
+                match Address::from_str(args[0]) {
+                    Ok(address) => wallet_manager.send(WalletTask::ImportAddress(address))?,
+                    Err(err) => error!("Can't parse address: {}", err),
+                }
+                Ok(())
+            },
+        );
+        shell.new_command(
+            "setlabel",
+            "Name wallet or any other <address> with <label> shown in balance and history. Words of label are joined with single spaces. Omitted label removes the name",
+            1,
+            |_, senders, args| {
+                let ref wallet_manager = senders.1;
+                match Address::from_str(args[0]) {
+                    Ok(address) => wallet_manager.send(WalletTask::SetLabel(address, args[1..].join(" ")))?,
+                    Err(err) => error!("Can't parse address: {}", err),
+                }
+                Ok(())
+            },
+        );
+        shell.new_command(
+            "walletpassphrase",
+            "Unlock wallet keys with <passphrase>. The first call sets passphrase of a new wallet",
+            1,
+            |_, senders, args| {
+                let ref wallet_manager = senders.1;
+                let task = WalletTask::Unlock(args[0].to_owned());
+                wallet_manager.send(task)?;
+                Ok(())
+            },
+        );
+        shell.new_command(
+            "walletlock",
+            "Remove decrypted wallet keys from memory",
+            0,
+            |_, senders, _| {
+                let ref wallet_manager = senders.1;
+                wallet_manager.send(WalletTask::Lock())?;
+                Ok(())
+            },
+        );
+        shell.new_command(
+            "loadwallet",
+            "Load wallet with <name>, creating it if there is none yet, and use it for following wallet commands of this session",
+            1,
+            |_, senders, args| {
+                let ref mut wallet_manager = senders.1;
+                let (loaded_sender, loaded_receiver) = mpsc::channel();
+                wallet_manager.send(WalletTask::OpenWallet(args[0].to_owned(), loaded_sender))?;
+                // selection is kept if wallet fails to load
+                if loaded_receiver.recv().unwrap_or(false) {
+                    wallet_manager.wallet = Some(args[0].to_owned());
+                }
+                Ok(())
+            },
+        );
+        shell.new_command(
+            "unloadwallet",
+            "Unload wallet with <name>. Default wallet is used by this session if it was the selected one",
+            1,
+            |_, senders, args| {
+                let ref mut wallet_manager = senders.1;
+                wallet_manager.send(WalletTask::UnloadWallet(args[0].to_owned()))?;
+                if wallet_manager.wallet.as_ref().map(String::as_str) == Some(args[0]) {
+                    wallet_manager.wallet = None;
+                }
+                Ok(())
+            },
+        );
+        shell.new_command(
+            "listwallets",
+            "List loaded wallets. The one selected in this session is marked with *",
+            0,
+            |_, senders, _| {
+                let ref wallet_manager = senders.1;
+                wallet_manager.send(WalletTask::ListWallets())?;
+                Ok(())
+            },
+        );
+        shell.new_command(
+            "usewallet",
+            "Select loaded wallet with <name> for following wallet commands of this session. Other sessions keep their wallets",
+            1,
+            |_, senders, args| {
+                let ref mut wallet_manager = senders.1;
+                wallet_manager.wallet = match args[0] {
+                    DEFAULT_WALLET => None,
+                    name => Some(name.to_owned()),
+                };
+                info!("Wallet {} is used for following wallet commands", args[0]);
+                Ok(())
+            },
+        );
         shell.new_command(
             "balance",
             "Show balance of currently loaded wallet",
@@ -98,16 +298,114 @@ impl InputListener {
                 Ok(())
             },
         );
+        shell.new_command(
+            "history",
+            "Show incoming and outgoing wallet transactions with their confirmations",
+            0,
+            |_, senders, _| {
+                let ref wallet_manager = senders.1;
+                wallet_manager.send(WalletTask::ShowHistory())?;
+                Ok(())
+            },
+        );
+        shell.new_command(
+            "rescan",
+            "Scan blocks starting from [height] for transactions of wallet keys and watch-only addresses. Scans whole chain by default",
+            0,
+            |_, senders, args| {
+                let ref wallet_manager = senders.1;
+                match args.get(0).map_or(Ok(0), |arg| arg.parse::<u32>()) {
+                    Ok(from_height) => wallet_manager.send(WalletTask::Rescan(from_height))?,
+                    Err(err) => error!("Can't parse height: {}", err),
+                }
+                Ok(())
+            },
+        );
+        shell.new_command(
+            "bumpfee",
+            "Replace wallet transaction <txid> from memory pool with one paying higher fee out of change. Usage: bumpfee <txid> [fee rate in satoshis per virtual byte]",
+            1,
+            |_, senders, args| {
+                let ref wallet_manager = senders.1;
+                let hash = match H256::from_str(args[0]) {
+                    Ok(hash) => hash,
+                    Err(err) => {
+                        error!("Can't parse hash: {}", err);
+                        return Ok(());
+                    }
+                };
+                match args.get(1).map(|arg| arg.parse::<u64>()) {
+                    Some(Err(err)) => error!("Can't parse fee rate: {}", err),
+                    Some(Ok(fee_rate)) => wallet_manager.send(WalletTask::BumpFee(hash, Some(fee_rate)))?,
+                    None => wallet_manager.send(WalletTask::BumpFee(hash, None))?,
+                }
+                Ok(())
+            },
+        );
+        shell.new_command(
+            "lockunspent",
+            "Exclude outputs from funding of wallet transactions. Usage: lockunspent <unlock: true|false> [<txid>:<vout>...]. Unlocking without outputs unlocks all of them",
+            1,
+            |_, senders, args| {
+                let ref wallet_manager = senders.1;
+                let unlock = match args[0].parse::<bool>() {
+                    Ok(unlock) => unlock,
+                    Err(err) => {
+                        error!("Can't parse unlock flag: {}", err);
+                        return Ok(());
+                    }
+                };
+                match args[1..].iter().map(|arg| parse_out_point(arg)).collect::<Result<Vec<_>, _>>() {
+                    Ok(out_points) => wallet_manager.send(WalletTask::LockUnspent(unlock, out_points))?,
+                    Err(err) => error!("{}", err),
+                }
+                Ok(())
+            },
+        );
+        shell.new_command(
+            "listlockunspent",
+            "Show outputs excluded from funding",
+            0,
+            |_, senders, _| {
+                let ref wallet_manager = senders.1;
+                wallet_manager.send(WalletTask::ListLockUnspent())?;
+                Ok(())
+            },
+        );
         shell.new_command(
             "transfer",
-            "Transfer <address> <amount>",
+            "Transfer to legacy or bech32 <address> <amount> [fee rate in satoshis per virtual byte] [coin selection: largest|smallest|bnb|random] [time lock: locktime=<height or unix time>|delay=<blocks>]",
             2,
             |_, senders, args| {
                 let ref wallet_manager = senders.1;
-                match Address::from_str(args[0]) {
-                    Ok(address) => match args[1].parse::<u64>() {
+                let fee_rate = match args.get(2).map(|arg| arg.parse::<u64>()) {
+                    Some(Ok(fee_rate)) => fee_rate,
+                    Some(Err(err)) => {
+                        error!("Can't parse fee rate: {}", err);
+                        return Ok(());
+                    }
+                    None => DEFAULT_FEE_RATE,
+                };
+                let selection = match args.get(3).map(|arg| arg.parse::<CoinSelection>()) {
+                    Some(Ok(selection)) => selection,
+                    Some(Err(err)) => {
+                        error!("{}", err);
+                        return Ok(());
+                    }
+                    None => CoinSelection::default(),
+                };
+                let time_lock = match args.get(4).map(|arg| arg.parse::<TimeLock>()) {
+                    Some(Ok(time_lock)) => Some(time_lock),
+                    Some(Err(err)) => {
+                        error!("{}", err);
+                        return Ok(());
+                    }
+                    None => None,
+                };
+                match Recipient::from_str(args[0]) {
+                    Ok(recipient) => match args[1].parse::<u64>() {
                         Ok(amount) => {
-                            let task = WalletTask::SendCash(address, amount);
+                            let task = WalletTask::SendCash(recipient, amount, fee_rate, selection, time_lock);
                             wallet_manager.send(task)?;
                         }
                         Err(err) => error!("Can't parse amount: {}", err),
@@ -119,6 +417,170 @@ impl InputListener {
                 Ok(())
             },
         );
+        shell.new_command(
+            "senddata",
+            "Store <hex data> in zero value OP_RETURN output of wallet transaction. Usage: senddata <hex data> [fee rate in satoshis per virtual byte]",
+            1,
+            |_, senders, args| {
+                let ref wallet_manager = senders.1;
+                let fee_rate = match args.get(1).map(|arg| arg.parse::<u64>()) {
+                    Some(Ok(fee_rate)) => fee_rate,
+                    Some(Err(err)) => {
+                        error!("Can't parse fee rate: {}", err);
+                        return Ok(());
+                    }
+                    None => DEFAULT_FEE_RATE,
+                };
+                match Bytes::from_str(args[0]) {
+                    Ok(data) => wallet_manager.send(WalletTask::SendData(data, fee_rate))?,
+                    Err(err) => error!("Can't parse data: {:?}", err),
+                }
+                Ok(())
+            },
+        );
+        shell.new_command(
+            "createmultisig",
+            "Create P2WSH address requiring <m> signatures of <public keys...> in hex and remember it for co-signing",
+            2,
+            |_, senders, args| {
+                let ref wallet_manager = senders.1;
+                let required = match args[0].parse::<u8>() {
+                    Ok(required) => required,
+                    Err(err) => {
+                        error!("Can't parse number of required signatures: {}", err);
+                        return Ok(());
+                    }
+                };
+                let mut publics = vec![];
+                for arg in &args[1..] {
+                    match Bytes::from_str(arg).map_err(|_| ()).and_then(|bytes| Public::from_slice(&bytes).map_err(|_| ())) {
+                        Ok(public) => publics.push(public),
+                        Err(_) => {
+                            error!("Can't parse public key {}", arg);
+                            return Ok(());
+                        }
+                    }
+                }
+                wallet_manager.send(WalletTask::CreateMultisig(required, publics))?;
+                Ok(())
+            },
+        );
+        shell.new_command(
+            "cosign",
+            "Add wallet signatures to <raw transaction> spending multisig outputs. Complete transaction is broadcasted",
+            1,
+            |_, senders, args| {
+                let ref wallet_manager = senders.1;
+                let raw_transaction = Bytes::from_str(args[0])?;
+                wallet_manager.send(WalletTask::Cosign(raw_transaction))?;
+                Ok(())
+            },
+        );
+        shell.new_command(
+            "psbtcreate",
+            "Create partially signed transaction in base64 from unsigned <raw transaction> hex",
+            1,
+            |_, senders, args| {
+                let ref wallet_manager = senders.1;
+                let raw_transaction = Bytes::from_str(args[0])?;
+                wallet_manager.send(WalletTask::CreatePsbt(raw_transaction))?;
+                Ok(())
+            },
+        );
+        shell.new_command(
+            "psbtupdate",
+            "Add outputs spent by <psbt> inputs and witness scripts known to wallet",
+            1,
+            |_, senders, args| {
+                let ref wallet_manager = senders.1;
+                match Psbt::from_str(args[0]) {
+                    Ok(psbt) => wallet_manager.send(WalletTask::UpdatePsbt(psbt))?,
+                    Err(err) => error!("Can't parse PSBT: {:?}", err),
+                }
+                Ok(())
+            },
+        );
+        shell.new_command(
+            "psbtsign",
+            "Add signatures of wallet keys to <psbt> inputs",
+            1,
+            |_, senders, args| {
+                let ref wallet_manager = senders.1;
+                match Psbt::from_str(args[0]) {
+                    Ok(psbt) => wallet_manager.send(WalletTask::SignPsbt(psbt))?,
+                    Err(err) => error!("Can't parse PSBT: {:?}", err),
+                }
+                Ok(())
+            },
+        );
+        shell.new_command(
+            "psbtcombine",
+            "Merge signatures of several <psbts...> of the same transaction",
+            2,
+            |_, senders, args| {
+                let ref wallet_manager = senders.1;
+                match args.iter().map(|arg| Psbt::from_str(arg)).collect::<Result<Vec<_>, _>>() {
+                    Ok(psbts) => wallet_manager.send(WalletTask::CombinePsbt(psbts))?,
+                    Err(err) => error!("Can't parse PSBT: {:?}", err),
+                }
+                Ok(())
+            },
+        );
+        shell.new_command(
+            "psbtfinalize",
+            "Build final scripts of <psbt> inputs which have enough signatures",
+            1,
+            |_, senders, args| {
+                let ref wallet_manager = senders.1;
+                match Psbt::from_str(args[0]) {
+                    Ok(psbt) => wallet_manager.send(WalletTask::FinalizePsbt(psbt))?,
+                    Err(err) => error!("Can't parse PSBT: {:?}", err),
+                }
+                Ok(())
+            },
+        );
+        shell.new_command(
+            "psbtextract",
+            "Broadcast transaction of finalized <psbt>",
+            1,
+            |_, senders, args| {
+                let ref wallet_manager = senders.1;
+                match Psbt::from_str(args[0]) {
+                    Ok(psbt) => wallet_manager.send(WalletTask::ExtractPsbt(psbt))?,
+                    Err(err) => error!("Can't parse PSBT: {:?}", err),
+                }
+                Ok(())
+            },
+        );
+        shell.new_command(
+            "signmessage",
+            "Sign <text> with key of wallet <address> to prove its ownership. Words of text are joined with single spaces",
+            2,
+            |_, senders, args| {
+                let ref wallet_manager = senders.1;
+                match Address::from_str(args[0]) {
+                    Ok(address) => wallet_manager.send(WalletTask::SignMessage(address, args[1..].join(" ")))?,
+                    Err(err) => error!("Can't parse address: {}", err),
+                }
+                Ok(())
+            },
+        );
+        shell.new_command(
+            "verifymessage",
+            "Check that key of <address> made base64 <signature> of <text>",
+            3,
+            |_, senders, args| {
+                let ref wallet_manager = senders.1;
+                match Address::from_str(args[0]) {
+                    Ok(address) => {
+                        let task = WalletTask::VerifyMessage(address, args[1].to_owned(), args[2..].join(" "));
+                        wallet_manager.send(task)?;
+                    }
+                    Err(err) => error!("Can't parse address: {}", err),
+                }
+                Ok(())
+            },
+        );
         shell.new_command(
             "txmeta",
             "Get transaction meta data for debug",
@@ -175,14 +637,22 @@ impl InputListener {
         );
         shell.new_command(
             "initiate",
-            "Atomic swap initiate <participant address> <amount>",
+            "Atomic swap initiate <participant address> <amount> [coin selection: largest|smallest|bnb|random]. Contract is always funded from default wallet",
             2,
             |_, senders, args| {
                 let ref atomic_swapper = senders.2;
+                let selection = match args.get(2).map(|arg| arg.parse::<CoinSelection>()) {
+                    Some(Ok(selection)) => selection,
+                    Some(Err(err)) => {
+                        error!("{}", err);
+                        return Ok(());
+                    }
+                    None => CoinSelection::default(),
+                };
                 match Address::from_str(args[0]) {
                     Ok(address) => match args[1].parse::<u64>() {
                         Ok(amount) => {
-                            let task = AtomicSwapperTask::Initiate(address, amount);
+                            let task = AtomicSwapperTask::Initiate(address, amount, selection);
                             atomic_swapper.send(task)?;
                         }
                         Err(err) => error!("Can't parse amount: {}", err),
@@ -223,14 +693,22 @@ impl InputListener {
         );
         shell.new_command(
             "participate",
-            "Atomic swap participate <contract> <contract_raw_transaction> <secret>",
+            "Atomic swap participate <initiator address> <amount> <secret hash> [coin selection: largest|smallest|bnb|random]. Contract is always funded from default wallet",
             3,
             |_, senders, args| {
                 let ref atomic_swapper = senders.2;
                 let address = Address::from_str(args[0])?;
                 let amount = args[1].parse::<u64>()?;
                 let secret_hash = H256::from_str(args[2])?;
-                let task = AtomicSwapperTask::Participate(address, amount, secret_hash);
+                let selection = match args.get(3).map(|arg| arg.parse::<CoinSelection>()) {
+                    Some(Ok(selection)) => selection,
+                    Some(Err(err)) => {
+                        error!("{}", err);
+                        return Ok(());
+                    }
+                    None => CoinSelection::default(),
+                };
+                let task = AtomicSwapperTask::Participate(address, amount, secret_hash, selection);
                 atomic_swapper.send(task)?;
                 Ok(())
             },
@@ -16,6 +16,8 @@ extern crate pretty_env_logger;
 extern crate primitives;
 extern crate script;
 extern crate serialization as ser;
+#[macro_use]
+extern crate serialization_derive;
 extern crate shrust;
 extern crate sync;
 extern crate verification;
@@ -23,6 +25,7 @@ extern crate rpc as ethcore_rpc;
 extern crate futures;
 extern crate futures_cpupool;
 extern crate chain_builder;
+extern crate rand;
 
 #[macro_use]
 extern crate log;
@@ -48,6 +51,10 @@ mod rpc;
 mod rpc_apis;
 mod atomic_swapper;
 mod transaction_helper;
+mod coin_selection;
+mod wallet_rpc;
+mod wallets;
+mod wallet_notifier;
 
 use executor::Executor;
 use executor::Task as ExecutorTask;
@@ -56,11 +63,13 @@ use input_listener::InputListener;
 use p2p::NetworkNode;
 use service::Service;
 use sync::{Acceptor, MessageHandler, MessageWrapper, Responder};
-use wallet::Wallet;
+use wallet::{Wallet, WalletError};
 use wallet_manager::WalletManager;
 use atomic_swapper::AtomicSwapper;
 use transaction_helper::TransactionHelper;
 use memory_pool::UtxoAndOutputProvider;
+use wallets::{LoadedWallet, Wallets};
+use wallet_notifier::WalletNotifier;
 
 fn main() {
     pretty_env_logger::init();
@@ -88,12 +97,20 @@ fn main() {
                 .long("testnet")
                 .help("Use testnet rules where tokens have no real world value")
         )
+        .arg(
+            Arg::with_name("walletnotify")
+                .long("walletnotify")
+                .help("Execute command when wallet transaction is accepted to mempool or block. %s in command is replaced by transaction hash, %b by block hash")
+                .takes_value(true)
+        )
         .get_matches();
 
     let config = config::parse(&matches).expect("Could not parse command line arguments");
 
     //setup database
     let db_path_string = "./db".to_owned() + matches.value_of("number").unwrap_or("") + "/";
+    let wallet_path_string = db_path_string.clone() + "wallet.dat";
+    let wallets_path_string = db_path_string.clone() + "wallets";
     let default_db_cache = 512;
     let storage = db_utils::open_db(db_path_string, default_db_cache);
     db_utils::init_db(storage.clone(), NetworkParams::Mainnet).unwrap(); //init db with genesis block
@@ -146,13 +163,38 @@ fn main() {
         terminate_receiver,
     );
 
-    let wallet = Arc::new(RwLock::new(Wallet::new()));
+    let wallet = match Wallet::open(wallet_path_string) {
+        Ok(wallet) => wallet,
+        Err(WalletError::UnsupportedVersion(version)) => {
+            error!("Wallet file has version {} which is not supported by this node. Please use newer node", version);
+            process::exit(1);
+        }
+        Err(err) => {
+            error!("Failed to open wallet: {:?}", err);
+            process::exit(1);
+        }
+    };
+    let wallet = Arc::new(RwLock::new(wallet));
 
     let utxo_provider = UtxoAndOutputProvider::new(storage.clone(), mempool_ref.clone());
     let transaction_helper = Arc::new(TransactionHelper::new(
         utxo_provider,
+        storage.clone(),
+        mempool_ref.clone(),
         wallet.clone(),
     ));
+    let wallets = Arc::new(Wallets::new(
+        wallets_path_string.into(),
+        storage.clone(),
+        mempool_ref.clone(),
+        LoadedWallet {
+            wallet: wallet.clone(),
+            transaction_helper: transaction_helper.clone(),
+        },
+    ));
+    if let Some(command) = config.wallet_notify.clone() {
+        acceptor.subscribe(Arc::new(WalletNotifier::new(command, wallets.clone())));
+    }
 
     //setup wallet task and miscellaneous task executor
     let mut wallet_manager = WalletManager::new(
@@ -160,8 +202,8 @@ fn main() {
         storage.clone(),
         wallet_manager_receiver,
         message_wrapper.clone(),
-        wallet.clone(),
-        transaction_helper.clone(),
+        wallets.clone(),
+        acceptor.clone(),
     );
     let mut executor = Executor::new(
         mempool_ref.clone(),
@@ -172,11 +214,11 @@ fn main() {
 
     let mut atomic_swapper = AtomicSwapper::new(
         acceptor.clone(),
-        transaction_helper,
+        transaction_helper.clone(),
         cpupool,
-        message_wrapper,
+        message_wrapper.clone(),
         atomic_swapper_receiver,
-        wallet
+        wallet.clone()
     );
 
     //setup telnet listener
@@ -192,6 +234,8 @@ fn main() {
 		network: config.network,
 		storage: storage,
 		acceptor,
+		wallets,
+		message_wrapper,
 	};
 	let _rpc_server = rpc::new_http(config.rpc_config, rpc_deps).expect("Can't launch json-rpc service");
 
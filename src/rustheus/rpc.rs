@@ -1,16 +1,19 @@
 use std::net::SocketAddr;
 use std::sync::Arc;
 use rpc_apis::{self, ApiSet};
-use ethcore_rpc::{Server, start_http, MetaIoHandler, Compatibility, Remote};
+use ethcore_rpc::{Server, start_http, MetaIoHandler, Metadata, Compatibility, Remote};
 use params::NetworkParams;
 use std::io;
 use sync;
 use db::SharedStore;
+use wallets::WalletsRef;
 
 pub struct Dependencies {
 	pub network: NetworkParams,
 	pub acceptor: sync::AcceptorRef,
 	pub storage: SharedStore,
+	pub wallets: WalletsRef,
+	pub message_wrapper: sync::MessageWrapper,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -64,6 +67,6 @@ pub fn setup_http_rpc_server(
 	}
 }
 
-fn setup_rpc_server(apis: ApiSet, deps: Dependencies) -> MetaIoHandler<()> {
+fn setup_rpc_server(apis: ApiSet, deps: Dependencies) -> MetaIoHandler<Metadata> {
 	rpc_apis::setup_rpc(MetaIoHandler::with_compatibility(Compatibility::Both), apis, deps)
 }
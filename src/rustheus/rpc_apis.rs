@@ -1,7 +1,8 @@
 use std::str::FromStr;
 use std::collections::HashSet;
 use rpc::Dependencies;
-use ethcore_rpc::MetaIoHandler;
+use ethcore_rpc::{MetaIoHandler, Metadata};
+use wallet_rpc::WalletClientCore;
 
 #[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
 pub enum Api {
@@ -13,6 +14,8 @@ pub enum Api {
 	BlockChain,
 	/// Network
 	Network,
+	/// Wallet of this node
+	Wallet,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -22,7 +25,7 @@ pub enum ApiSet {
 
 impl Default for ApiSet {
 	fn default() -> Self {
-		ApiSet::List(vec![Api::Raw, Api::Miner, Api::BlockChain, Api::Network].into_iter().collect())
+		ApiSet::List(vec![Api::Raw, Api::Miner, Api::BlockChain, Api::Network, Api::Wallet].into_iter().collect())
 	}
 }
 
@@ -35,6 +38,7 @@ impl FromStr for Api {
 			"miner" => Ok(Api::Miner),
 			"blockchain" => Ok(Api::BlockChain),
 			"network" => Ok(Api::Network),
+			"wallet" => Ok(Api::Wallet),
 			api => Err(format!("Unknown api: {}", api)),
 		}
 	}
@@ -48,7 +52,7 @@ impl ApiSet {
 	}
 }
 
-pub fn setup_rpc(mut handler: MetaIoHandler<()>, apis: ApiSet, deps: Dependencies) -> MetaIoHandler<()> {
+pub fn setup_rpc(mut handler: MetaIoHandler<Metadata>, apis: ApiSet, deps: Dependencies) -> MetaIoHandler<Metadata> {
 	use ethcore_rpc::v1::*;
 
 	for api in apis.list_apis() {
@@ -57,6 +61,7 @@ pub fn setup_rpc(mut handler: MetaIoHandler<()>, apis: ApiSet, deps: Dependencie
 			Api::Miner => handler.extend_with(MinerClient::new(MinerClientCore::new()).to_delegate()),
 			Api::BlockChain => handler.extend_with(BlockChainClient::new(BlockChainClientCore::new(deps.network, deps.storage.clone())).to_delegate()),
 			Api::Network => handler.extend_with(NetworkClient::new(NetworkClientCore::new()).to_delegate()),
+			Api::Wallet => handler.extend_with(WalletClient::new(WalletClientCore::new(deps.wallets.clone(), deps.acceptor.clone(), deps.message_wrapper.clone())).to_delegate()),
 
 		}
 	}
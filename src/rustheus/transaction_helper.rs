@@ -1,28 +1,152 @@
-use chain::constants::SEQUENCE_LOCKTIME_DISABLE_FLAG;
-use chain::{OutPoint, Transaction};
-use db::{TransactionUtxoProvider, TransactionOutputProvider};
-use keys::{Private, KeyPair};
-use script::{Builder, Script, SighashBase, SignatureVersion, TransactionInputSigner};
-use wallet::WalletRef;
+use chain::constants::{SEQUENCE_MAX_REPLACEABLE, SEQUENCE_LOCKTIME_DISABLE_FLAG, SEQUENCE_LOCKTIME_TYPE_FLAG, SEQUENCE_LOCKTIME_MASK};
+use chain::{OutPoint, Transaction, Psbt};
+use db::{SharedStore, BlockRef, TransactionUtxoProvider, TransactionOutputProvider, TransactionMetaProvider};
+use keys::{Address, AddressHash, Private, KeyPair, Network, Signature, Type};
+use script::{Builder, Script, ScriptType, SighashBase, SignatureVersion, TransactionInputSigner, sign_psbt_input};
+use wallet::{Wallet, WalletRef, WalletError, TransactionRecord};
 use chain::{TransactionInput, TransactionOutput};
 use std::sync::Arc;
-use memory_pool::UtxoAndOutputProvider;
+use std::cmp;
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+use parking_lot::Mutex;
+use memory_pool::{MemoryPoolRef, MemoryPoolOrderingStrategy, UtxoAndOutputProvider};
+use coin_selection::{Coin, CoinSelection};
+use crypto::dhash160;
 use primitives::bytes::Bytes;
+use primitives::hash::H256;
+use ser::Serializable;
+use verification::constants::COINBASE_MATURITY;
+use verification::policy::MAX_NULL_DATA_SIZE;
 
 pub type TransactionHelperRef = Arc<TransactionHelper>;
 
+/// Fee rate in satoshis per virtual byte used when user doesn't provide one. Equals minimal relay fee
+/// of default node policy, so transactions paying it are still relayed
+pub const DEFAULT_FEE_RATE: u64 = 1;
+
+/// Number of blocks scanned between saves of wallet transaction records and progress reports
+const RESCAN_BATCH_SIZE: u32 = 1000;
+/// Number of blocks scanned again when the last scanned block leaves canonical chain
+const REORGANIZATION_RESCAN_DEPTH: u32 = 100;
+
+/// Fee rate in satoshis per virtual byte which replacement transaction adds to fee of original one
+const BUMP_FEE_RATE_INCREMENT: u64 = 1;
+
+/// Signature push (DER signature up to 72 bytes and sighash type) and compressed public key push
+const SIGNATURE_AND_PUBLIC_SIZE: usize = 1 + 73 + 1 + 33;
+/// Number of witness items prepended to witness
+const WITNESS_ITEMS_COUNT_SIZE: usize = 1;
+/// Segwit marker and flag
+const WITNESS_HEADER_SIZE: usize = 2;
+const WITNESS_SCALE_FACTOR: usize = 4;
+/// P2WPKH output: value, script length and script
+const P2WPKH_OUTPUT_SIZE: u64 = 8 + 1 + 22;
+/// Virtual size of input spending P2WPKH output. Outputs worth less than fee for such input are dust
+const P2WPKH_SPEND_VIRTUAL_SIZE: u64 = 69;
+/// Label of addresses which receive change of wallet transactions
+pub const CHANGE_LABEL: &'static str = "change";
+
+/// Estimates sizes of script_sig and witness which will be set to input spending `prevout`
+fn estimate_signature_size(prevout: &TransactionOutput) -> (usize, usize) {
+    let script: Script = prevout.script_pubkey.clone().into();
+    match script.script_type() {
+        ScriptType::PubKeyHash => (SIGNATURE_AND_PUBLIC_SIZE, 0),
+        // push of P2WPKH redeem script
+        ScriptType::ScriptHash => (1 + 22, WITNESS_ITEMS_COUNT_SIZE + SIGNATURE_AND_PUBLIC_SIZE),
+        _ => (0, WITNESS_ITEMS_COUNT_SIZE + SIGNATURE_AND_PUBLIC_SIZE),
+    }
+}
+
+/// Virtual size which input adds to transaction: outpoint, sequence, script_sig and witness
+fn estimate_input_virtual_size(script_sig_size: usize, witness_size: usize) -> u64 {
+    let base_size = 32 + 4 + 4 + 1 + script_sig_size;
+    let weight = base_size * WITNESS_SCALE_FACTOR + witness_size;
+    ((weight + WITNESS_SCALE_FACTOR - 1) / WITNESS_SCALE_FACTOR) as u64
+}
+
+/// Virtual size of `transaction` after its inputs get script_sigs and witnesses of given total sizes
+fn estimate_virtual_size(transaction: &Transaction, script_sigs_size: usize, witnesses_size: usize) -> u64 {
+    let base_size = transaction.serialized_size() + script_sigs_size;
+    let witness_size = if witnesses_size > 0 { WITNESS_HEADER_SIZE + witnesses_size } else { 0 };
+    let weight = base_size * WITNESS_SCALE_FACTOR + witness_size;
+    ((weight + WITNESS_SCALE_FACTOR - 1) / WITNESS_SCALE_FACTOR) as u64
+}
+
+/// Address which `output` pays to. Witness key hash is shown as P2PKH address, the same way wallet shows its keys
+pub fn output_address(output: &TransactionOutput) -> Option<Address> {
+    let script: Script = output.script_pubkey.clone().into();
+    script.extract_destinations()
+        .ok()
+        .and_then(|destinations| destinations.into_iter().next())
+        .map(|destination| Address {
+            kind: if script.is_pay_to_witness_key_hash() { Type::P2PKH } else { destination.kind },
+            network: Network::Mainnet, //TODO check for network correctness
+            hash: destination.hash,
+        })
+}
+
+/// Time lock of wallet payment
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum TimeLock {
+    /// Transaction can't be mined before block height, or unix time if value is not below `LOCKTIME_THRESHOLD`
+    Absolute(u32),
+    /// Transaction can't be mined until its inputs have given number of confirmations (BIP 68)
+    Relative(u16),
+}
+
+impl FromStr for TimeLock {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, '=');
+        match (parts.next(), parts.next()) {
+            (Some("locktime"), Some(value)) => value.parse()
+                .map(TimeLock::Absolute)
+                .map_err(|err| format!("Can't parse lock time: {}", err)),
+            (Some("delay"), Some(value)) => value.parse()
+                .map(TimeLock::Relative)
+                .map_err(|err| format!("Can't parse delay: {}", err)),
+            _ => Err(format!("Unknown time lock {}. Use one of: locktime=<height or unix time>, delay=<blocks>", s)),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum FundError {
     NoFunds,
     NotEnoughFunds,
+    /// Chosen coin selection strategy couldn't pick coins, eg: there is no exact match
+    NoSuitableCoins,
+    /// Null data output script is larger than standardness policy allows
+    NullDataTooLarge(usize),
+    /// Amounts or fee at given fee rate don't fit into 64 bits
+    Overflow,
+    WalletError(WalletError),
+}
+
+impl From<WalletError> for FundError {
+    fn from(err: WalletError) -> FundError {
+        FundError::WalletError(err)
+    }
 }
 
 #[derive(Debug)]
 pub enum SignError {
+    WalletLocked,
     NoSuchPrevout,
+    /// Prevout pays to address imported without private key
+    WatchOnlyPrevout,
     NoKeysToUnlockPrevout,
-    PrevoutWitnessParseError,
-    PrevoutWitnessVersionTooHigh,
+    /// Prevout script is neither P2PKH, P2WPKH, P2SH-P2WPKH nor P2WSH
+    UnsupportedPrevout,
+    /// Wallet doesn't know witness script of P2WSH prevout
+    UnknownWitnessScript,
+    /// Witness script of P2WSH prevout is not multisig
+    UnsupportedWitnessScript,
+    /// Wallet keys are not enough to spend P2WSH multisig prevout
+    NotEnoughSignatures,
     FundError(FundError),
 }
 
@@ -32,37 +156,499 @@ impl From<FundError> for SignError {
     }
 }
 
+#[derive(Debug)]
+pub enum RescanError {
+    /// Requested height is above the best block
+    HeightAboveBestBlock,
+    WalletError(WalletError),
+}
+
+impl From<WalletError> for RescanError {
+    fn from(err: WalletError) -> RescanError {
+        RescanError::WalletError(err)
+    }
+}
+
+#[derive(Debug)]
+pub enum BumpFeeError {
+    NotInMemoryPool,
+    /// None of transaction inputs signals replacement
+    NotReplaceable,
+    /// Some inputs spend outputs which wallet can't sign
+    ForeignInputs,
+    /// There is no output paying back to wallet, which could pay the fee
+    NoChange,
+    /// Requested fee rate doesn't increase the fee
+    FeeTooLow,
+    /// Change is not enough to pay increased fee
+    NotEnoughChange,
+    SignError(SignError),
+}
+
+impl From<SignError> for BumpFeeError {
+    fn from(err: SignError) -> BumpFeeError {
+        BumpFeeError::SignError(err)
+    }
+}
+
+#[derive(Debug)]
+pub enum LockError {
+    /// Output doesn't exist or is already spent
+    UnknownOutput(OutPoint),
+    /// Output being unlocked was not locked
+    NotLocked(OutPoint),
+}
+
+/// Wallet coins split by whether they can be spent
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Balance {
+    /// Outputs of transactions included in blocks
+    pub confirmed: u64,
+    /// Outputs of transactions which are still in memory pool
+    pub unconfirmed: u64,
+    /// Coinbase outputs which can't be spent until they reach maturity
+    pub immature: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HistoryKind {
+    Received,
+    Sent,
+    /// Coinbase paying to wallet key
+    Generated,
+}
+
+/// Wallet transaction found in blockchain or memory pool
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryEntry {
+    pub hash: H256,
+    pub kind: HistoryKind,
+    /// Received amount if positive, sent amount if negative. Fee is not included
+    pub amount: i64,
+    /// Known only when every input spends wallet coins
+    pub fee: Option<u64>,
+    /// Recipient of sent coins or sender of received ones
+    pub address: Option<Address>,
+    /// Label of recipient of sent coins or of wallet address which received coins
+    pub label: Option<String>,
+    /// Height of block containing transaction. None for memory pool transactions
+    pub height: Option<u32>,
+    pub confirmations: u32,
+}
+
+/// Wallet output which is spent neither in blockchain nor in memory pool
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnspentOutput {
+    pub out_point: OutPoint,
+    pub output: TransactionOutput,
+    /// Zero for outputs of memory pool transactions
+    pub confirmations: u32,
+    /// False for outputs paying to watch-only addresses
+    pub spendable: bool,
+}
+
 pub struct TransactionHelper {
     utxo_provider: UtxoAndOutputProvider,
+    storage: SharedStore,
+    mempool: MemoryPoolRef,
     wallet: WalletRef,
+    /// Held while blocks are scanned, so concurrent scans don't overwrite records of each other
+    scan_lock: Mutex<()>,
+    /// Outputs which funding doesn't pick. Locked by user or reserved by funding until transaction is broadcast
+    locked_out_points: Mutex<HashSet<OutPoint>>,
+    /// Broadcast wallet transactions which are not mined yet
+    unconfirmed: Mutex<HashMap<H256, Transaction>>,
 }
 
 impl TransactionHelper {
     pub fn new(
         utxo_provider: UtxoAndOutputProvider,
+        storage: SharedStore,
+        mempool: MemoryPoolRef,
         wallet: WalletRef,
     ) -> Self {
+        // inputs of time-locked payments stay reserved until they are broadcast
+        let pending_inputs = wallet.read()
+            .pending_transactions()
+            .into_iter()
+            .flat_map(|transaction| transaction.inputs)
+            .map(|input| input.previous_output)
+            .collect();
         TransactionHelper {
             utxo_provider,
+            storage,
+            mempool,
             wallet,
+            scan_lock: Mutex::new(()),
+            locked_out_points: Mutex::new(pending_inputs),
+            unconfirmed: Mutex::new(HashMap::new()),
         }
     }
 
-    //TODO seek for spent outputs in mempool
-    fn get_unspent_out_points(&self) -> Vec<OutPoint> {
-        self.wallet
-            .read()
-            .keys
+    /// Excludes unspent `out_points` from funding
+    pub fn lock_unspent(&self, out_points: Vec<OutPoint>) -> Result<(), LockError> {
+        if let Some(out_point) = out_points.iter().find(|out_point|
+            self.utxo_provider.transaction_output(out_point, 0).is_none() || self.utxo_provider.is_spent(out_point)) {
+            return Err(LockError::UnknownOutput(out_point.clone()));
+        }
+        self.locked_out_points.lock().extend(out_points);
+        Ok(())
+    }
+
+    /// Allows funding to pick `out_points` again
+    pub fn unlock_unspent(&self, out_points: &[OutPoint]) -> Result<(), LockError> {
+        let mut locked = self.locked_out_points.lock();
+        if let Some(out_point) = out_points.iter().find(|out_point| !locked.contains(*out_point)) {
+            return Err(LockError::NotLocked(out_point.clone()));
+        }
+        for out_point in out_points {
+            locked.remove(out_point);
+        }
+        Ok(())
+    }
+
+    pub fn unlock_all_unspent(&self) {
+        self.locked_out_points.lock().clear();
+    }
+
+    pub fn list_locked_unspent(&self) -> Vec<OutPoint> {
+        self.locked_out_points.lock().iter().cloned().collect()
+    }
+
+    /// Releases outputs reserved by funding of `transaction`. Called once transaction is in memory
+    /// pool, which keeps its inputs from being spent again, or when it failed to get there
+    pub fn release_inputs(&self, transaction: &Transaction) {
+        let mut locked = self.locked_out_points.lock();
+        for input in &transaction.inputs {
+            locked.remove(&input.previous_output);
+        }
+    }
+
+    /// Keeps broadcast `transaction` until it is mined, so it can be relayed again. Tracked transactions
+    /// spending the same outputs are forgotten, since `transaction` replaces them
+    pub fn track_unconfirmed(&self, transaction: Transaction) {
+        let mut unconfirmed = self.unconfirmed.lock();
+        {
+            let spent: HashSet<&OutPoint> = transaction.inputs.iter().map(|input| &input.previous_output).collect();
+            unconfirmed.retain(|_, tracked| !tracked.inputs.iter().any(|input| spent.contains(&input.previous_output)));
+        }
+        unconfirmed.insert(transaction.hash(), transaction);
+    }
+
+    /// Forgets tracked transactions which are mined or spend outputs already spent by mined transactions.
+    /// Returns the rest, which still wait for confirmation
+    pub fn unconfirmed_transactions(&self) -> Vec<Transaction> {
+        let mut unconfirmed = self.unconfirmed.lock();
+        unconfirmed.retain(|hash, transaction| {
+            if self.storage.transaction_meta(hash).is_some() {
+                debug!("Wallet transaction {} is mined", hash);
+                return false;
+            }
+            let conflicts = transaction.inputs.iter().any(|input| {
+                let out_point = &input.previous_output;
+                self.storage.transaction_meta(&out_point.hash)
+                    .and_then(|meta| meta.is_spent(out_point.index as usize))
+                    .unwrap_or(false)
+            });
+            if conflicts {
+                info!("Wallet transaction {} is dropped, since its inputs are spent by mined transaction", hash);
+            }
+            !conflicts
+        });
+        unconfirmed.values().cloned().collect()
+    }
+
+    /// Outputs paying to `address_hashes` which are not spent neither in blockchain nor in memory pool
+    fn get_unspent_out_points(&self, address_hashes: &[AddressHash]) -> Vec<OutPoint> {
+        address_hashes
             .iter()
-            .flat_map(|keypair| 
+            .flat_map(|address_hash|
                 self.utxo_provider
-                    .transaction_with_output_address(&keypair.address().hash))
+                    .transaction_with_output_address(address_hash))
+            .collect()
+    }
+
+    /// Sums unspent outputs of every wallet key and watch-only address
+    pub fn balance(&self) -> Balance {
+        let address_hashes = self.wallet.read().tracked_address_hashes();
+        self.balance_of(&address_hashes)
+    }
+
+    /// Sums unspent outputs of given addresses
+    pub fn balance_of(&self, address_hashes: &[AddressHash]) -> Balance {
+        let best_height = self.storage.best_block().number;
+        self.get_unspent_out_points(address_hashes)
+            .into_iter()
+            .filter_map(|out_point| self.utxo_provider
+                .transaction_output(&out_point, 0)
+                .map(|output| (out_point, output)))
+            .fold(Balance::default(), |mut balance, (out_point, output)| {
+                match self.storage.transaction_meta(&out_point.hash) {
+                    None => balance.unconfirmed += output.value,
+                    // coinbase can be spent only in block at least COINBASE_MATURITY blocks later
+                    Some(ref meta) if meta.is_coinbase() && best_height + 1 < meta.height() + COINBASE_MATURITY =>
+                        balance.immature += output.value,
+                    Some(_) => balance.confirmed += output.value,
+                }
+                balance
+            })
+    }
+
+    /// Unspent outputs of every wallet key and watch-only address
+    pub fn list_unspent(&self) -> Vec<UnspentOutput> {
+        let best_height = self.storage.best_block().number;
+        let wallet = self.wallet.read();
+        self.get_unspent_out_points(&wallet.tracked_address_hashes())
+            .into_iter()
+            .filter_map(|out_point| self.utxo_provider
+                .transaction_output(&out_point, 0)
+                .map(|output| (out_point, output)))
+            .map(|(out_point, output)| UnspentOutput {
+                confirmations: self.storage.transaction_meta(&out_point.hash)
+                    .map_or(0, |meta| best_height - meta.height() + 1),
+                spendable: output_address(&output).map_or(false, |address| !wallet.is_watch_only(&address.hash)),
+                out_point,
+                output,
+            })
+            .collect()
+    }
+
+    /// Scans blocks starting from `from_height` for wallet transactions and replaces records of
+    /// those blocks with found ones. Returns heights of the first and the last scanned blocks
+    pub fn rescan(&self, from_height: u32) -> Result<(u32, u32), RescanError> {
+        let _scan_guard = self.scan_lock.lock();
+        let best_height = self.storage.best_block().number;
+        if from_height > best_height {
+            return Err(RescanError::HeightAboveBestBlock);
+        }
+        self.scan_blocks(from_height, best_height, true)?;
+        Ok((from_height, best_height))
+    }
+
+    /// Scans blocks added since the last scan. Blocks which replaced scanned ones during
+    /// reorganization are scanned again
+    fn sync_transaction_records(&self) -> Result<(), WalletError> {
+        let _scan_guard = self.scan_lock.lock();
+        if self.wallet.read().tracked_address_hashes().is_empty() {
+            return Ok(());
+        }
+
+        let best_height = self.storage.best_block().number;
+        let (scanned_blocks, scanned_tip) = self.wallet.read().scanned_tip();
+        let from_height = if scanned_blocks == 0 {
+            0
+        } else if self.storage.as_block_provider().block_hash(scanned_blocks - 1) == Some(scanned_tip) {
+            scanned_blocks
+        } else {
+            warn!("Last scanned block {} is not in canonical chain anymore, scanning again", scanned_blocks - 1);
+            cmp::min(scanned_blocks, best_height + 1).saturating_sub(REORGANIZATION_RESCAN_DEPTH)
+        };
+        if from_height > best_height {
+            return Ok(());
+        }
+        self.scan_blocks(from_height, best_height, false)
+    }
+
+    /// Records wallet transactions of blocks from `from_height` to `to_height` in batches.
+    /// Records of blocks above `to_height` are dropped
+    fn scan_blocks(&self, from_height: u32, to_height: u32, report_progress: bool) -> Result<(), WalletError> {
+        let address_hashes: HashSet<AddressHash> = self.wallet.read().tracked_address_hashes().into_iter().collect();
+        let block_provider = self.storage.as_block_provider();
+
+        let mut batch_start = from_height;
+        loop {
+            let batch_end = cmp::min(batch_start.saturating_add(RESCAN_BATCH_SIZE), to_height + 1);
+            let mut records = vec![];
+            let mut scanned = None;
+            for height in batch_start..batch_end {
+                // chain may be reorganized during scan, then the rest is scanned by the next sync
+                let block_hash = match block_provider.block_hash(height) {
+                    Some(block_hash) => block_hash,
+                    None => break,
+                };
+                for transaction in block_provider.block_transactions(BlockRef::Hash(block_hash.clone())) {
+                    if self.history_entry(&transaction, Some(height), to_height, &address_hashes).is_some() {
+                        records.push(TransactionRecord {
+                            hash: transaction.hash(),
+                            height,
+                            block_hash: block_hash.clone(),
+                        });
+                    }
+                }
+                scanned = Some((height + 1, block_hash));
+            }
+
+            let (scanned_blocks, scanned_tip) = match scanned {
+                Some(scanned) => scanned,
+                None => {
+                    warn!("Block {} left canonical chain during scan, stopping", batch_start);
+                    return Ok(());
+                }
+            };
+            self.wallet.write().update_transaction_records(batch_start, records, scanned_blocks, scanned_tip)?;
+            if report_progress {
+                info!("Scanned blocks up to {} of {}", scanned_blocks - 1, to_height);
+            }
+
+            if scanned_blocks < batch_end {
+                warn!("Block {} left canonical chain during scan, stopping", scanned_blocks);
+                return Ok(());
+            }
+            if batch_end > to_height {
+                return Ok(());
+            }
+            batch_start = batch_end;
+        }
+    }
+
+    /// Transactions spending or receiving wallet coins. Recorded canonical chain transactions go
+    /// first in block order, followed by memory pool transactions in order of arrival
+    pub fn history(&self) -> Vec<HistoryEntry> {
+        if let Err(err) = self.sync_transaction_records() {
+            error!("Failed to scan new blocks for wallet transactions: {:?}", err);
+        }
+
+        let (address_hashes, records) = {
+            let wallet = self.wallet.read();
+            let address_hashes: HashSet<AddressHash> = wallet.tracked_address_hashes().into_iter().collect();
+            (address_hashes, wallet.transaction_records().to_vec())
+        };
+        let best_height = self.storage.best_block().number;
+
+        let block_provider = self.storage.as_block_provider();
+        let transaction_provider = self.storage.as_transaction_provider();
+        let confirmed = records
+            .into_iter()
+            .filter(|record| block_provider.block_hash(record.height).as_ref() == Some(&record.block_hash))
+            .filter_map(|record| transaction_provider
+                .transaction(&record.hash)
+                .map(|transaction| (transaction, Some(record.height))));
+
+        // collected before lookup of previous outputs, which locks memory pool once again
+        let unconfirmed: Vec<_> = self.mempool
+            .read()
+            .iter(MemoryPoolOrderingStrategy::ByTimestamp)
+            .map(|entry| (entry.transaction.clone(), None))
+            .collect();
+
+        confirmed
+            .chain(unconfirmed.into_iter())
+            .filter_map(|(transaction, height)| self.history_entry(&transaction, height, best_height, &address_hashes))
+            .collect()
+    }
+
+    /// Tells for each of `transactions` whether it pays to wallet addresses or spends wallet coins.
+    /// Wallet addresses are collected once for all of them
+    pub fn relevant(&self, transactions: &[&Transaction]) -> Vec<bool> {
+        let address_hashes: HashSet<AddressHash> = self.wallet.read().tracked_address_hashes().into_iter().collect();
+        let best_height = self.storage.best_block().number;
+        transactions
+            .iter()
+            .map(|transaction| self.history_entry(transaction, None, best_height, &address_hashes).is_some())
             .collect()
     }
 
-    //TODO accept fee
-    pub fn fund_transaction(&self, transaction: Transaction) -> Result<Transaction, FundError> {
-        let unspent_out_points = self.get_unspent_out_points();
+    /// History entry of wallet transaction with given hash
+    pub fn history_entry_by_hash(&self, hash: &H256) -> Option<HistoryEntry> {
+        self.history().into_iter().find(|entry| entry.hash == *hash)
+    }
+
+    /// Describes `transaction` from the point of view of wallet. None if it doesn't touch wallet coins
+    fn history_entry(
+        &self,
+        transaction: &Transaction,
+        height: Option<u32>,
+        best_height: u32,
+        address_hashes: &HashSet<AddressHash>,
+    ) -> Option<HistoryEntry> {
+        let is_mine = |output: &TransactionOutput| output_address(output)
+            .map_or(false, |address| address_hashes.contains(&address.hash));
+
+        let prevouts: Vec<Option<TransactionOutput>> = if transaction.is_coinbase() {
+            vec![]
+        } else {
+            transaction.inputs
+                .iter()
+                .map(|input| self.utxo_provider.transaction_output(&input.previous_output, 0))
+                .collect()
+        };
+
+        let sent: u64 = prevouts.iter()
+            .filter_map(|prevout| prevout.as_ref())
+            .filter(|prevout| is_mine(prevout))
+            .map(|prevout| prevout.value)
+            .sum();
+        let received: u64 = transaction.outputs.iter()
+            .filter(|output| is_mine(output))
+            .map(|output| output.value)
+            .sum();
+        if sent == 0 && received == 0 {
+            return None;
+        }
+
+        let spends_only_wallet_coins = !prevouts.is_empty() &&
+            prevouts.iter().all(|prevout| prevout.as_ref().map_or(false, |prevout| is_mine(prevout)));
+        let fee = if spends_only_wallet_coins {
+            sent.checked_sub(transaction.total_spends())
+        } else {
+            None
+        };
+
+        let kind = if transaction.is_coinbase() {
+            HistoryKind::Generated
+        } else if sent > 0 {
+            HistoryKind::Sent
+        } else {
+            HistoryKind::Received
+        };
+
+        let address = match kind {
+            HistoryKind::Sent => transaction.outputs.iter()
+                .filter(|output| !is_mine(output))
+                .filter_map(output_address)
+                .next(),
+            _ => prevouts.iter()
+                .filter_map(|prevout| prevout.as_ref())
+                .filter_map(output_address)
+                .next(),
+        };
+        let labelled_hash = match kind {
+            HistoryKind::Sent => address.as_ref().map(|address| address.hash.clone()),
+            _ => transaction.outputs.iter()
+                .filter(|output| is_mine(output))
+                .filter_map(output_address)
+                .map(|address| address.hash)
+                .next(),
+        };
+        let label = labelled_hash.and_then(|hash| self.wallet.read().label(&hash).map(|label| label.to_owned()));
+
+        Some(HistoryEntry {
+            hash: transaction.hash(),
+            kind,
+            amount: received as i64 - sent as i64 + fee.unwrap_or(0) as i64,
+            fee,
+            address,
+            label,
+            height,
+            confirmations: height.map_or(0, |height| best_height - height + 1),
+        })
+    }
+
+    /// Adds wallet outputs picked by `selection` as inputs, so they cover outputs of `transaction`
+    /// and fee for its estimated signed size. `fee_rate` is in satoshis per virtual byte. Change
+    /// which is not worth spending is left to the fee
+    pub fn fund_transaction(&self, transaction: Transaction, fee_rate: u64, selection: CoinSelection)
+        -> Result<Transaction, FundError>
+    {
+        // held until selected outputs are reserved, so concurrent funding doesn't pick them too
+        let mut locked = self.locked_out_points.lock();
+
+        // watch-only outputs can't be signed
+        let address_hashes = self.wallet.read().address_hashes();
+        let unspent_out_points: Vec<OutPoint> = self.get_unspent_out_points(&address_hashes)
+            .into_iter()
+            .filter(|out_point| !locked.contains(out_point))
+            .collect();
         if unspent_out_points.is_empty() {
             return Err(FundError::NoFunds);
         }
@@ -70,47 +656,322 @@ impl TransactionHelper {
         let needed_amount = transaction
             .outputs
             .iter()
-            .fold(0, |acc, output| acc + output.value);
+            .fold(Some(0u64), |acc, output| acc.and_then(|acc| acc.checked_add(output.value)))
+            .ok_or(FundError::Overflow)?;
 
-        let mut inputs: Vec<TransactionInput> = vec![];
+        let coins: Vec<Coin> = unspent_out_points
+            .into_iter()
+            .filter_map(|out_point| self.utxo_provider
+                .transaction_output(&out_point, 0)
+                .map(|output| (out_point, output)))
+            .filter_map(|(out_point, output)| {
+                let (script_sig_size, witness_size) = estimate_signature_size(&output);
+                let input_size = estimate_input_virtual_size(script_sig_size, witness_size);
+                match fee_rate.checked_mul(input_size).and_then(|fee| output.value.checked_sub(fee)) {
+                    Some(effective_value) if effective_value > 0 => Some(Coin { out_point, output, effective_value }),
+                    // spending this output costs more than it's worth
+                    _ => None,
+                }
+            })
+            .collect();
+
+        let mut transaction = Transaction {
+            inputs: vec![],
+            ..transaction
+        };
+
+        // one more byte accounts for segwit marker and flag. Target is never zero, since transaction
+        // needs at least one input even when it pays nothing and fee is free
+        let target = fee_rate.checked_mul(estimate_virtual_size(&transaction, 0, 0) + 1)
+            .and_then(|fee| fee.checked_add(needed_amount))
+            .ok_or(FundError::Overflow)?;
+        let target = cmp::max(target, 1);
+        if coins.iter().map(|coin| coin.effective_value).sum::<u64>() < target {
+            return Err(FundError::NotEnoughFunds);
+        }
+
+        let change_fee = fee_rate.checked_mul(P2WPKH_OUTPUT_SIZE).ok_or(FundError::Overflow)?;
+        let cost_of_change = fee_rate.checked_mul(P2WPKH_SPEND_VIRTUAL_SIZE)
+            .and_then(|fee| fee.checked_add(change_fee))
+            .ok_or(FundError::Overflow)?;
+        let selected = selection.selector()
+            .select(coins, target, cost_of_change)
+            .ok_or(FundError::NoSuitableCoins)?;
 
         let mut inputs_sum = 0;
-        for out_point in unspent_out_points {
-            let output = self.utxo_provider.transaction_output(&out_point, 0).unwrap();
-            let input = TransactionInput {
-                previous_output: out_point,
+        let mut script_sigs_size = 0;
+        let mut witnesses_size = 0;
+        for coin in selected {
+            let (script_sig_size, witness_size) = estimate_signature_size(&coin.output);
+            script_sigs_size += script_sig_size;
+            witnesses_size += witness_size;
+            inputs_sum += coin.output.value;
+
+            transaction.inputs.push(TransactionInput {
+                previous_output: coin.out_point,
                 script_sig: Default::default(),
-                sequence: SEQUENCE_LOCKTIME_DISABLE_FLAG, //TODO remove this for atomic swap probably
+                // lets fee be bumped later
+                sequence: SEQUENCE_MAX_REPLACEABLE,
                 script_witness: vec![],
-            };
-            inputs.push(input);
-
-            inputs_sum += output.value;
-            if inputs_sum >= needed_amount {
-                break;
-            }
+            });
         }
 
-        let mut outputs = transaction.outputs.clone();
+        let fee = fee_rate.checked_mul(estimate_virtual_size(&transaction, script_sigs_size, witnesses_size))
+            .ok_or(FundError::Overflow)?;
 
         //TODO create option to return leftovers to the same address
-        if inputs_sum > needed_amount {
-            let new_address = self.wallet.write().new_keypair();
+        let change = match needed_amount.checked_add(fee).and_then(|spent| inputs_sum.checked_sub(spent)) {
+            Some(change) => change,
+            None => return Err(FundError::NotEnoughFunds),
+        };
+        if change > cost_of_change {
+            let new_address = {
+                let mut wallet = self.wallet.write();
+                let new_address = wallet.new_keypair()?;
+                wallet.set_label(&new_address, CHANGE_LABEL.to_owned())?;
+                new_address
+            };
             let leftover = TransactionOutput {
-                value: inputs_sum - needed_amount,
+                value: change - change_fee,
                 script_pubkey: Builder::build_p2wpkh(&new_address.hash).to_bytes(),
             };
-            outputs.push(leftover);
-        } else if inputs_sum < needed_amount {
-            return Err(FundError::NotEnoughFunds);
+            transaction.outputs.push(leftover);
+        } else if change > 0 {
+            debug!("Change {} is dust and goes to fee", change);
+        }
+
+        locked.extend(transaction.inputs.iter().map(|input| input.previous_output.clone()));
+        Ok(transaction)
+    }
+
+    /// Funds and signs `transaction`. Outputs reserved by funding are released if signing fails
+    pub fn fund_and_sign_transaction(&self, transaction: Transaction, fee_rate: u64, selection: CoinSelection)
+        -> Result<Transaction, SignError>
+    {
+        let funded_transaction = self.fund_transaction(transaction, fee_rate, selection)?;
+        self.sign_funded_transaction(funded_transaction)
+    }
+
+    /// Signs transaction made by `fund_transaction`. Outputs reserved by funding are released if signing fails
+    fn sign_funded_transaction(&self, funded_transaction: Transaction) -> Result<Transaction, SignError> {
+        self.sign_transaction(funded_transaction.clone()).map_err(|err| {
+            self.release_inputs(&funded_transaction);
+            err
+        })
+    }
+
+    /// Checks if time locks of `transaction` let it be mined in the next block. Only height based
+    /// relative locks are checked, since wallet doesn't create time based ones
+    pub fn is_time_lock_mature(&self, transaction: &Transaction) -> bool {
+        let next_height = self.storage.best_block().number + 1;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs() as u32)
+            .unwrap_or(0);
+        if !transaction.is_final_in_block(next_height, now) {
+            return false;
+        }
+        // relative lock times are enforced since version 2
+        if transaction.version < 2 {
+            return true;
+        }
+
+        transaction.inputs
+            .iter()
+            .filter(|input| input.sequence & (SEQUENCE_LOCKTIME_DISABLE_FLAG | SEQUENCE_LOCKTIME_TYPE_FLAG) == 0)
+            .all(|input| {
+                let delay = input.sequence & SEQUENCE_LOCKTIME_MASK;
+                match self.storage.transaction_meta(&input.previous_output.hash) {
+                    Some(meta) => next_height - meta.height() >= delay,
+                    // input spends memory pool transaction
+                    None => delay == 0,
+                }
+            })
+    }
+
+    /// Builds replacement of wallet transaction `hash` from memory pool, which spends the same inputs and pays
+    /// higher fee out of change. Fee rate is increased by `BUMP_FEE_RATE_INCREMENT` unless `fee_rate` is given
+    pub fn bump_fee(&self, hash: &H256, fee_rate: Option<u64>) -> Result<Transaction, BumpFeeError> {
+        let mut transaction = match self.mempool.read().get(hash) {
+            Some(transaction) => transaction.clone(),
+            None => return Err(BumpFeeError::NotInMemoryPool),
+        };
+        if !transaction.signals_replacement() {
+            return Err(BumpFeeError::NotReplaceable);
+        }
+
+        // watch-only outputs can't be signed
+        let address_hashes: HashSet<AddressHash> = self.wallet.read().address_hashes().into_iter().collect();
+        let is_mine = |output: &TransactionOutput| output_address(output)
+            .map_or(false, |address| address_hashes.contains(&address.hash));
+
+        let mut inputs_sum = 0;
+        let mut script_sigs_size = 0;
+        let mut witnesses_size = 0;
+        for input in &mut transaction.inputs {
+            let prevout = match self.utxo_provider.transaction_output(&input.previous_output, 0) {
+                Some(ref prevout) if is_mine(prevout) => prevout.clone(),
+                _ => return Err(BumpFeeError::ForeignInputs),
+            };
+            let (script_sig_size, witness_size) = estimate_signature_size(&prevout);
+            script_sigs_size += script_sig_size;
+            witnesses_size += witness_size;
+            inputs_sum += prevout.value;
+
+            input.script_sig = Bytes::new();
+            input.script_witness = vec![];
+        }
+
+        let change_index = match transaction.outputs.iter().rposition(|output| is_mine(output)) {
+            Some(index) => index,
+            None => return Err(BumpFeeError::NoChange),
+        };
+
+        let virtual_size = estimate_virtual_size(&transaction, script_sigs_size, witnesses_size);
+        let old_fee = inputs_sum.saturating_sub(transaction.total_spends());
+        let old_fee_rate = old_fee / virtual_size;
+        let new_fee_rate = fee_rate.unwrap_or(old_fee_rate + BUMP_FEE_RATE_INCREMENT);
+        // fee which doesn't fit into 64 bits can't be paid out of change anyway
+        let new_fee = new_fee_rate.checked_mul(virtual_size).ok_or(BumpFeeError::NotEnoughChange)?;
+        if new_fee <= old_fee {
+            return Err(BumpFeeError::FeeTooLow);
         }
 
-        Ok(Transaction {
+        let change = match transaction.outputs[change_index].value.checked_sub(new_fee - old_fee) {
+            Some(change) => change,
+            None => return Err(BumpFeeError::NotEnoughChange),
+        };
+        if change > new_fee_rate.saturating_mul(P2WPKH_SPEND_VIRTUAL_SIZE) {
+            transaction.outputs[change_index].value = change;
+        } else if transaction.outputs.len() > 1 {
+            debug!("Change {} is dust and goes to fee", change);
+            transaction.outputs.remove(change_index);
+        } else {
+            return Err(BumpFeeError::NotEnoughChange);
+        }
+
+        Ok(self.sign_transaction(transaction)?)
+    }
+
+    /// Builds transaction moving every P2PKH and P2WPKH output of `private` key to new wallet address.
+    /// Key signs the transaction, but is not added to wallet
+    pub fn sweep_private_key(&self, private: Private, fee_rate: u64) -> Result<Transaction, FundError> {
+        let keypair = KeyPair::from_private(private).map_err(WalletError::from)?;
+        let address_hash = keypair.public().address_hash();
+        let p2pkh_script = Builder::build_p2pkh(&address_hash);
+        let p2pkh_bytes = p2pkh_script.to_bytes();
+        let p2wpkh_bytes = Builder::build_p2wpkh(&address_hash).to_bytes();
+
+        let prevouts: Vec<(OutPoint, TransactionOutput)> = self.utxo_provider
+            .transaction_with_output_address(&address_hash)
+            .into_iter()
+            .filter_map(|out_point| self.utxo_provider
+                .transaction_output(&out_point, 0)
+                .map(|output| (out_point, output)))
+            .filter(|&(_, ref output)| output.script_pubkey == p2pkh_bytes || output.script_pubkey == p2wpkh_bytes)
+            .collect();
+        if prevouts.is_empty() {
+            return Err(FundError::NoFunds);
+        }
+
+        // output pays to wallet key once sweep is known to be affordable, so failed sweep doesn't use up a key.
+        // Until then it has placeholder script of the same size
+        let mut transaction = Transaction {
             version: 0,
-            inputs,
-            outputs,
+            inputs: vec![],
+            outputs: vec![TransactionOutput {
+                value: 0,
+                script_pubkey: Builder::build_p2wpkh(&AddressHash::default()).to_bytes(),
+            }],
             lock_time: 0,
-        })
+        };
+
+        let mut inputs_sum = 0;
+        let mut script_sigs_size = 0;
+        let mut witnesses_size = 0;
+        for &(ref out_point, ref output) in &prevouts {
+            let (script_sig_size, witness_size) = estimate_signature_size(output);
+            script_sigs_size += script_sig_size;
+            witnesses_size += witness_size;
+            inputs_sum += output.value;
+
+            transaction.inputs.push(TransactionInput {
+                previous_output: out_point.clone(),
+                script_sig: Default::default(),
+                sequence: SEQUENCE_MAX_REPLACEABLE,
+                script_witness: vec![],
+            });
+        }
+
+        let fee = fee_rate.checked_mul(estimate_virtual_size(&transaction, script_sigs_size, witnesses_size))
+            .ok_or(FundError::Overflow)?;
+        let spend_fee = fee_rate.checked_mul(P2WPKH_SPEND_VIRTUAL_SIZE).ok_or(FundError::Overflow)?;
+        match inputs_sum.checked_sub(fee) {
+            Some(value) if value > spend_fee => transaction.outputs[0].value = value,
+            _ => return Err(FundError::NotEnoughFunds),
+        }
+
+        let new_address = self.wallet.write().new_keypair()?;
+        transaction.outputs[0].script_pubkey = Builder::build_p2wpkh(&new_address.hash).to_bytes();
+
+        let signer: TransactionInputSigner = transaction.clone().into();
+        transaction.inputs = prevouts.iter()
+            .enumerate()
+            .map(|(index, &(_, ref output))| if output.script_pubkey == p2pkh_bytes {
+                signer.signed_input(&keypair, index, output.value, &p2pkh_script, SignatureVersion::Base, SighashBase::All.into())
+            } else {
+                // witness v0 key hash is signed as if it was P2PKH
+                signer.signed_input(&keypair, index, output.value, &p2pkh_script, SignatureVersion::WitnessV0, SighashBase::All.into())
+            })
+            .collect();
+        Ok(transaction)
+    }
+
+    /// Creates signed transaction paying `amount` to `script_pubkey`. Time lock is set as transaction lock time
+    /// or as relative lock of every input
+    pub fn create_payment(&self, script_pubkey: Bytes, amount: u64, fee_rate: u64, selection: CoinSelection,
+        time_lock: Option<TimeLock>) -> Result<Transaction, SignError>
+    {
+        let transaction = Transaction {
+            // relative lock times are enforced since version 2 (BIP 68)
+            version: match time_lock {
+                Some(TimeLock::Relative(_)) => 2,
+                _ => 0,
+            },
+            inputs: vec![],
+            outputs: vec![
+                TransactionOutput {
+                    value: amount,
+                    script_pubkey,
+                },
+            ],
+            // funded inputs signal replacement, so lock time is not disabled by final sequences
+            lock_time: match time_lock {
+                Some(TimeLock::Absolute(lock_time)) => lock_time,
+                _ => 0,
+            },
+        };
+
+        let mut transaction = self.fund_transaction(transaction, fee_rate, selection)?;
+        if let Some(TimeLock::Relative(blocks)) = time_lock {
+            for input in &mut transaction.inputs {
+                input.sequence = blocks as u32;
+            }
+        }
+        self.sign_funded_transaction(transaction)
+    }
+
+    /// Creates transaction storing `data` in zero value null data (OP_RETURN) output. Fails if output
+    /// script exceeds standard size, since such transaction wouldn't be relayed
+    pub fn create_data_transaction(&self, data: &[u8], fee_rate: u64, selection: CoinSelection)
+        -> Result<Transaction, SignError>
+    {
+        let script_pubkey = Builder::build_nulldata(data);
+        if script_pubkey.len() > MAX_NULL_DATA_SIZE {
+            return Err(FundError::NullDataTooLarge(script_pubkey.len()).into());
+        }
+
+        self.create_payment(script_pubkey.to_bytes(), 0, fee_rate, selection, None)
     }
 
     // createSig creates and returns the serialized raw signature and compressed
@@ -147,47 +1008,237 @@ impl TransactionHelper {
         }
     }
 
-    pub fn sign_input(
+    /// Adds wallet signatures to inputs of partially signed `transaction`. Inputs spending P2WSH
+    /// multisig outputs get signatures of wallet keys, other unsigned inputs are signed as usual.
+    /// Returns transaction and whether it has every signature it needs
+    pub fn cosign_transaction(&self, transaction: Transaction) -> Result<(Transaction, bool), SignError> {
+        let signer: TransactionInputSigner = transaction.clone().into();
+        let mut complete = true;
+        let mut inputs = vec![];
+        for (index, input) in transaction.inputs.into_iter().enumerate() {
+            let prevout = match self.utxo_provider.transaction_output(&input.previous_output, 0) {
+                Some(prevout) => prevout,
+                None => return Err(SignError::NoSuchPrevout),
+            };
+            let prevout_script = Script::new(prevout.script_pubkey.clone());
+            let witness_script = if prevout_script.is_pay_to_witness_script_hash() {
+                self.wallet.read().find_witness_script(&prevout_script[2..34].into())
+            } else {
+                None
+            };
+
+            match witness_script {
+                Some(witness_script) => {
+                    let (input, input_complete) = self.sign_multisig_input(input, index, &signer, prevout.value, witness_script.into())?;
+                    complete &= input_complete;
+                    inputs.push(input);
+                }
+                // signed by someone else
+                None if !input.script_witness.is_empty() || !input.script_sig.is_empty() => inputs.push(input),
+                None => inputs.push(self.sign_input(input, index, &signer)?),
+            }
+        }
+
+        Ok((Transaction { inputs, ..transaction }, complete))
+    }
+
+    /// Adds signatures of wallet keys to input spending P2WSH multisig output. Witness is always a
+    /// valid stack: dummy element, signatures in order of their public keys and witness script.
+    /// Returns signed input and whether it has enough signatures
+    fn sign_multisig_input(
         &self,
         input: TransactionInput,
         input_index: usize,
         signer: &TransactionInputSigner,
-    ) -> Result<TransactionInput, SignError> {
-        let prevout = self.utxo_provider.transaction_output(&input.previous_output, 0);
-        if prevout.is_none() {
-            return Err(SignError::NoSuchPrevout);
+        input_amount: u64,
+        witness_script: Script,
+    ) -> Result<(TransactionInput, bool), SignError> {
+        let (required, publics) = match witness_script.multisig_public_keys() {
+            Some(multisig) => multisig,
+            None => return Err(SignError::UnsupportedWitnessScript),
+        };
+        let sighash: u32 = SighashBase::All.into();
+        let hash = signer.signature_hash(input_index, input_amount, &witness_script, SignatureVersion::WitnessV0, sighash);
+
+        // match present signatures with their public keys, so new ones can be put in the right place
+        let mut signatures: Vec<Option<Bytes>> = publics.iter().map(|_| None).collect();
+        let witness_len = input.script_witness.len();
+        if witness_len > 2 {
+            for signature in input.script_witness[1..witness_len - 1].iter().filter(|signature| !signature.is_empty()) {
+                // last byte is sighash type
+                let der: Signature = signature[..signature.len() - 1].into();
+                if let Some(index) = publics.iter().position(|public| public.verify(&hash, &der).unwrap_or(false)) {
+                    signatures[index] = Some(signature.clone());
+                }
+            }
         }
-        let prevout = prevout.unwrap();
-        let prevout_script = Script::new(prevout.script_pubkey.clone());
-        let prevout_witness = prevout_script.parse_witness_program();
-        if prevout_witness == None {
-            return Err(SignError::PrevoutWitnessParseError);
+
+        let wallet = self.wallet.read();
+        if wallet.is_locked() {
+            return Err(SignError::WalletLocked);
+        }
+        for (index, public) in publics.iter().enumerate() {
+            if signatures.iter().filter(|signature| signature.is_some()).count() >= required {
+                break;
+            }
+            if signatures[index].is_some() {
+                continue;
+            }
+            if let Some(keypair) = wallet.find_keypair_with_public_hash(&public.address_hash()) {
+                let (signature, _) = signer.compute_signature_for_input(
+                    keypair, input_index, input_amount, &witness_script, SignatureVersion::WitnessV0, sighash);
+                signatures[index] = Some(signature);
+            }
         }
 
-        let prevout_witness_version = prevout_witness.unwrap().0;
-        if prevout_witness_version != 0 {
-            return Err(SignError::PrevoutWitnessVersionTooHigh);
+        let signatures: Vec<Bytes> = signatures.into_iter().filter_map(|signature| signature).collect();
+        let complete = signatures.len() >= required;
+        let mut script_witness = vec![Bytes::new()];
+        script_witness.extend(signatures);
+        script_witness.push(witness_script.to_bytes());
+
+        Ok((TransactionInput { script_witness, ..input }, complete))
+    }
+
+    /// Adds data signers need: outputs spent by inputs and witness scripts known to wallet.
+    /// Whole previous transaction is added for inputs spending non-witness outputs
+    pub fn update_psbt(&self, mut psbt: Psbt) -> Psbt {
+        for index in 0..psbt.inputs.len() {
+            if psbt.prevout(index).is_none() {
+                let previous_output = psbt.unsigned_transaction.inputs[index].previous_output.clone();
+                if let Some(prevout) = self.utxo_provider.transaction_output(&previous_output, 0) {
+                    let script: Script = prevout.script_pubkey.clone().into();
+                    if script.parse_witness_program().is_some() {
+                        psbt.inputs[index].witness_utxo = Some(prevout);
+                    } else {
+                        psbt.inputs[index].non_witness_utxo = self.find_transaction(&previous_output.hash);
+                    }
+                }
+            }
+            if psbt.inputs[index].witness_script.is_none() {
+                psbt.inputs[index].witness_script = psbt.prevout(index)
+                    .and_then(|prevout| self.known_witness_script(&prevout));
+            }
         }
 
-        let prevout_witness_program = prevout_witness.unwrap().1;
+        for (output, psbt_output) in psbt.unsigned_transaction.outputs.iter().zip(psbt.outputs.iter_mut()) {
+            if psbt_output.witness_script.is_none() {
+                psbt_output.witness_script = self.known_witness_script(output);
+            }
+        }
+        psbt
+    }
+
+    /// Adds signatures of wallet keys to PSBT inputs they can sign. Returns PSBT and number of added signatures
+    pub fn sign_psbt(&self, mut psbt: Psbt) -> Result<(Psbt, usize), SignError> {
         let wallet = self.wallet.read();
-        let keys = wallet
-            .find_keypair_with_public_hash(&prevout_witness_program.into());
-        if keys.is_none() {
-            return Err(SignError::NoKeysToUnlockPrevout);
+        if wallet.is_locked() {
+            return Err(SignError::WalletLocked);
         }
-        let keys = keys.unwrap();
-        let script_pubkey = Builder::build_p2pkh(&prevout_witness_program.into());
 
-        let signed_input = signer.signed_input(
-            keys,
-            input_index,
-            prevout.value,
-            &script_pubkey,
-            SignatureVersion::WitnessV0,
-            SighashBase::All.into(),
-        );
+        let mut signed = 0;
+        for index in 0..psbt.inputs.len() {
+            for keypair in wallet.keypairs() {
+                if sign_psbt_input(&mut psbt, index, keypair) {
+                    signed += 1;
+                }
+            }
+        }
+        Ok((psbt, signed))
+    }
+
+    /// Witness script of P2WSH `output` if wallet remembers it
+    fn known_witness_script(&self, output: &TransactionOutput) -> Option<Bytes> {
+        let script: Script = output.script_pubkey.clone().into();
+        if script.is_pay_to_witness_script_hash() {
+            self.wallet.read().find_witness_script(&script[2..34].into())
+        } else {
+            None
+        }
+    }
+
+    /// Transaction from memory pool or canonical chain
+    fn find_transaction(&self, hash: &H256) -> Option<Transaction> {
+        let transaction = self.mempool.read().get(hash).cloned();
+        transaction.or_else(|| self.storage.as_transaction_provider().transaction(hash))
+    }
+
+    /// Signs input spending wallet output. The way it is signed is chosen by prevout script:
+    /// P2PKH gets legacy signature in script_sig, P2WPKH and P2SH-P2WPKH get witness signature
+    /// and P2WSH multisig gets signatures of wallet keys, which must be enough to spend it
+    pub fn sign_input(
+        &self,
+        input: TransactionInput,
+        input_index: usize,
+        signer: &TransactionInputSigner,
+    ) -> Result<TransactionInput, SignError> {
+        let prevout = match self.utxo_provider.transaction_output(&input.previous_output, 0) {
+            Some(prevout) => prevout,
+            None => return Err(SignError::NoSuchPrevout),
+        };
+        let prevout_script = Script::new(prevout.script_pubkey.clone());
+        let sighash: u32 = SighashBase::All.into();
+
+        match prevout_script.script_type() {
+            ScriptType::PubKeyHash => {
+                let wallet = self.wallet.read();
+                let keys = find_signing_keypair(&wallet, &prevout_script[3..23].into())?;
+                Ok(signer.signed_input(keys, input_index, prevout.value, &prevout_script, SignatureVersion::Base, sighash))
+            }
+            ScriptType::WitnessKey => {
+                let public_hash: AddressHash = prevout_script[2..22].into();
+                let wallet = self.wallet.read();
+                let keys = find_signing_keypair(&wallet, &public_hash)?;
+                let script_code = Builder::build_p2pkh(&public_hash);
+                Ok(signer.signed_input(keys, input_index, prevout.value, &script_code, SignatureVersion::WitnessV0, sighash))
+            }
+            ScriptType::ScriptHash => {
+                // only P2WPKH of wallet key nested in P2SH is known to wallet
+                let script_hash: AddressHash = prevout_script[2..22].into();
+                let wallet = self.wallet.read();
+                let public_hash = match wallet.keypairs()
+                    .iter()
+                    .map(|keypair| keypair.public().address_hash())
+                    .find(|public_hash| dhash160(&Builder::build_p2wpkh(public_hash).to_bytes()) == script_hash)
+                {
+                    Some(public_hash) => public_hash,
+                    None => return Err(SignError::NoKeysToUnlockPrevout),
+                };
+                let keys = find_signing_keypair(&wallet, &public_hash)?;
+                let script_code = Builder::build_p2pkh(&public_hash);
+                let signed_input = signer.signed_input(keys, input_index, prevout.value, &script_code, SignatureVersion::WitnessV0, sighash);
+                let redeem_script = Builder::build_p2wpkh(&public_hash);
 
-        Ok(signed_input)
+                Ok(TransactionInput {
+                    script_sig: Builder::default().push_data(&redeem_script.to_bytes()).into_bytes(),
+                    ..signed_input
+                })
+            }
+            ScriptType::WitnessScript => {
+                let witness_script = match self.wallet.read().find_witness_script(&prevout_script[2..34].into()) {
+                    Some(witness_script) => witness_script,
+                    None => return Err(SignError::UnknownWitnessScript),
+                };
+                match self.sign_multisig_input(input, input_index, signer, prevout.value, witness_script.into())? {
+                    (signed_input, true) => Ok(signed_input),
+                    (_, false) => Err(SignError::NotEnoughSignatures),
+                }
+            }
+            _ => Err(SignError::UnsupportedPrevout),
+        }
+    }
+}
+
+/// Wallet key with `public_hash` which can sign right now
+fn find_signing_keypair<'a>(wallet: &'a Wallet, public_hash: &AddressHash) -> Result<&'a KeyPair, SignError> {
+    if wallet.is_watch_only(public_hash) {
+        return Err(SignError::WatchOnlyPrevout);
+    }
+    if wallet.is_locked() {
+        return Err(SignError::WalletLocked);
+    }
+    match wallet.find_keypair_with_public_hash(public_hash) {
+        Some(keys) => Ok(keys),
+        None => Err(SignError::NoKeysToUnlockPrevout),
     }
 }
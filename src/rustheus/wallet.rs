@@ -1,63 +1,1142 @@
-use keys::generator::{Random, Generator};
+use keys::generator::Random;
 use keys::network::Network;
-use keys::{KeyPair, Private, Error, Address};
-use primitives::hash::H160;
+use keys::{KeyPair, Private, Address, AddressHash, DisplayLayout, ExtendedPrivate, ChildNumber, Mnemonic, Type};
+use keys::Error as KeyError;
+use keys::hex::ToHex;
+use chain::Transaction;
+use crypto::{pbkdf2_sha256, aes256_cbc_encrypt, aes256_cbc_decrypt, dhash256, sha256};
+use primitives::hash::{H160, H256};
+use primitives::bytes::Bytes;
+use ser::{serialize, serialize_with_flags, Reader, Error as ReaderError, SERIALIZE_TRANSACTION_WITNESS};
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 use parking_lot::RwLock;
 
 pub type WalletRef = Arc<RwLock<Wallet>>;
 
-pub struct Wallet
-{
-    pub keys: Vec<KeyPair>
+const WALLET_VERSION: u32 = 8;
+const KDF_ITERATIONS: u32 = 25_000;
+const SALT_SIZE: usize = 16;
+const IV_SIZE: usize = 16;
+/// Number of consecutive unused keys after which restore stops looking for used ones
+const GAP_LIMIT: u32 = 20;
+/// Wallet keys are derived as m/0'/0'/i' like Bitcoin Core does
+const KEYCHAIN_ACCOUNT: u32 = 0;
+const KEYCHAIN_EXTERNAL: u32 = 0;
+
+#[derive(Debug)]
+pub enum WalletError {
+    Locked,
+    WrongPassphrase,
+    NoSeed,
+    SeedExists,
+    Corrupted,
+    /// Wallet file is written by newer node, which uses unknown format version
+    UnsupportedVersion(u32),
+    /// Line of wallet dump with given number can't be parsed
+    InvalidDumpLine(usize),
+    Io(io::Error),
+    Key(KeyError),
+}
+
+impl From<io::Error> for WalletError {
+    fn from(err: io::Error) -> WalletError {
+        WalletError::Io(err)
+    }
+}
+
+impl From<ReaderError> for WalletError {
+    fn from(_: ReaderError) -> WalletError {
+        WalletError::Corrupted
+    }
+}
+
+impl From<KeyError> for WalletError {
+    fn from(err: KeyError) -> WalletError {
+        WalletError::Key(err)
+    }
+}
+
+/// Key as it is stored on disk. Private part is encrypted with passphrase derived key
+#[derive(Debug, Clone, Serializable, Deserializable)]
+struct KeyRecord {
+    address_hash: AddressHash,
+    /// Unix time when key was added to wallet
+    created: u32,
+    /// Initialization vector followed by encrypted private key layout
+    encrypted_private: Bytes,
+}
+
+/// Key as it is stored by wallet versions before 6, which didn't keep key creation time
+#[derive(Debug, Deserializable)]
+struct KeyRecordV1 {
+    address_hash: AddressHash,
+    encrypted_private: Bytes,
+}
+
+impl From<KeyRecordV1> for KeyRecord {
+    fn from(record: KeyRecordV1) -> Self {
+        KeyRecord {
+            address_hash: record.address_hash,
+            created: 0,
+            encrypted_private: record.encrypted_private,
+        }
+    }
+}
+
+/// User given name of wallet address
+#[derive(Debug, Clone, Serializable, Deserializable)]
+struct AddressLabel {
+    /// Layout of labelled address
+    address: Bytes,
+    label: String,
+}
+
+/// Label as it is stored by wallet version 6, which kept only hash of labelled address
+#[derive(Debug, Deserializable)]
+struct AddressLabelV6 {
+    address_hash: AddressHash,
+    label: String,
+}
+
+/// Wallet transaction found in canonical chain block
+#[derive(Debug, Clone, PartialEq, Serializable, Deserializable)]
+pub struct TransactionRecord {
+    pub hash: H256,
+    pub height: u32,
+    /// Hash of block containing transaction, so records of blocks which left canonical chain can be told apart
+    pub block_hash: H256,
+}
+
+/// Wallet file contents
+#[derive(Debug, Default, Serializable, Deserializable)]
+struct WalletData {
+    version: u32,
+    salt: Bytes,
+    iterations: u32,
+    /// Hash of passphrase derived key. Zero until passphrase is set
+    key_check: H256,
+    /// Initialization vector followed by encrypted BIP39 seed. Empty until wallet is created or restored
+    encrypted_seed: Bytes,
+    /// Index of the next key derived from seed
+    next_child: u32,
+    records: Vec<KeyRecord>,
+    /// Layouts of addresses tracked without private keys
+    watch_only: Vec<Bytes>,
+    /// Witness scripts of multisig addresses which wallet co-signs
+    witness_scripts: Vec<Bytes>,
+    labels: Vec<AddressLabel>,
+    /// Wallet transactions of scanned blocks in block order
+    transactions: Vec<TransactionRecord>,
+    /// Number of blocks from genesis scanned for wallet transactions
+    scanned_blocks: u32,
+    /// Hash of the last scanned block
+    scanned_tip: H256,
+    /// Signed payments waiting for their time lock to expire, serialized with witness
+    pending: Vec<Bytes>,
+}
+
+pub struct Wallet {
+    path: PathBuf,
+    data: WalletData,
+    /// Passphrase derived key, present only while wallet is unlocked
+    encryption_key: Option<H256>,
+    /// Master key derived from seed, present only while wallet is unlocked
+    master: Option<ExtendedPrivate>,
+    /// Decrypted keys, present only while wallet is unlocked
+    keys: Vec<KeyPair>,
+    /// Addresses which count in balance and history, but can't be spent from
+    watch_only: Vec<Address>,
+    /// Addresses of `data.labels` in the same order
+    labelled: Vec<Address>,
 }
 
 impl Wallet
 {
-    pub fn new() -> Self
+    /// Loads wallet from file at `path` or creates an empty one if there is no such file.
+    /// Empty wallet is written to disk as soon as passphrase is set
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, WalletError>
+    {
+        let path = path.as_ref().to_path_buf();
+        let data = if path.exists() {
+            let mut buffer = vec![];
+            fs::File::open(&path)?.read_to_end(&mut buffer)?;
+            let data = read_wallet_data(&buffer)?;
+            info!("Loaded wallet with {} keys and {} watch-only addresses from {}",
+                data.records.len(), data.watch_only.len(), path.display());
+            data
+        } else {
+            info!("There is no wallet at {}. Use `walletpassphrase` to create one", path.display());
+            WalletData {
+                version: WALLET_VERSION,
+                ..Default::default()
+            }
+        };
+
+        let watch_only: Result<Vec<_>, _> = data.watch_only
+            .iter()
+            .map(|layout| Address::from_layout(layout))
+            .collect();
+        let labelled: Result<Vec<_>, _> = data.labels
+            .iter()
+            .map(|label| Address::from_layout(&label.address))
+            .collect();
+
+        Ok(Wallet {
+            path,
+            data,
+            encryption_key: None,
+            master: None,
+            keys: vec![],
+            watch_only: watch_only.map_err(|_| WalletError::Corrupted)?,
+            labelled: labelled.map_err(|_| WalletError::Corrupted)?,
+        })
+    }
+
+    pub fn has_passphrase(&self) -> bool {
+        !self.data.key_check.is_zero()
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.encryption_key.is_none()
+    }
+
+    pub fn has_seed(&self) -> bool {
+        !self.data.encrypted_seed.is_empty()
+    }
+
+    /// Decrypts wallet keys with passphrase. The very first call sets passphrase of a new wallet
+    pub fn unlock(&mut self, passphrase: &str) -> Result<(), WalletError> {
+        if !self.has_passphrase() {
+            let mut salt = [0u8; SALT_SIZE];
+            Random::generate_bytes(&mut salt)?;
+            self.data.salt = salt[..].into();
+            self.data.iterations = KDF_ITERATIONS;
+            let key = self.derive_key(passphrase);
+            self.data.key_check = dhash256(&key[..]);
+            self.encryption_key = Some(key);
+            self.save()?;
+            info!("Wallet passphrase is set. Wallet is stored at {}", self.path.display());
+            return Ok(());
+        }
+
+        let key = self.derive_key(passphrase);
+        if dhash256(&key[..]) != self.data.key_check {
+            return Err(WalletError::WrongPassphrase);
+        }
+
+        let keys: Result<Vec<_>, _> = self.data.records
+            .iter()
+            .map(|record| Self::decrypt_record(&key, record))
+            .collect();
+        if self.has_seed() {
+            let seed = Self::decrypt(&key, &self.data.encrypted_seed)?;
+            self.master = Some(ExtendedPrivate::from_seed(&seed, Network::Mainnet)?);
+        }
+        self.keys = keys?;
+        self.encryption_key = Some(key);
+        info!("Wallet unlocked");
+        Ok(())
+    }
+
+    /// Forgets decrypted keys until next `unlock`
+    pub fn lock(&mut self) {
+        self.encryption_key = None;
+        self.master = None;
+        self.keys.clear();
+        info!("Wallet locked");
+    }
+
+    /// Generates new mnemonic with `word_count` words and makes its seed the source of all new wallet keys
+    pub fn create_seed(&mut self, word_count: usize) -> Result<Mnemonic, WalletError> {
+        let mnemonic = Mnemonic::generate(word_count)?;
+        self.set_seed(&mnemonic)?;
+        Ok(mnemonic)
+    }
+
+    /// Sets seed from `mnemonic` and adds keys derived from it. Keys are derived until there are
+    /// `GAP_LIMIT` consecutive keys for which `is_used` returns false. Returns number of added keys
+    pub fn restore_seed<F>(&mut self, mnemonic: &Mnemonic, is_used: F) -> Result<u32, WalletError>
+        where F: Fn(&AddressHash) -> bool
     {
-       Wallet { keys: vec![] }
+        self.set_seed(mnemonic)?;
+
+        let mut index = 0;
+        let mut unused_in_row = 0;
+        let mut keys = vec![];
+        while unused_in_row < GAP_LIMIT {
+            let keypair = self.derive_keypair(index)?;
+            if is_used(&keypair.public().address_hash()) {
+                keys.push(keypair);
+                unused_in_row = 0;
+            } else {
+                unused_in_row += 1;
+            }
+            index += 1;
+        }
+
+        let restored = keys.len() as u32;
+        // next key is the one after the last used
+        self.data.next_child = index - GAP_LIMIT;
+        for keypair in keys {
+            self.add_keypair(keypair, now())?;
+        }
+        self.save()?;
+        Ok(restored)
     }
 
-    pub fn new_keypair(&mut self) -> Address {
+    fn set_seed(&mut self, mnemonic: &Mnemonic) -> Result<(), WalletError> {
+        if self.has_seed() {
+            return Err(WalletError::SeedExists);
+        }
+
+        let seed = mnemonic.to_seed("");
+        self.data.encrypted_seed = match self.encryption_key {
+            Some(ref key) => Self::encrypt(key, &*seed)?,
+            None => return Err(WalletError::Locked),
+        };
+        self.data.next_child = 0;
         //TODO testnet support
-        let generator = Random::new(Network::Mainnet);
-        let keypair = generator.generate().expect("Could not generate keypair");
+        self.master = Some(ExtendedPrivate::from_seed(&*seed, Network::Mainnet)?);
+        self.save()
+    }
+
+    fn derive_keypair(&self, index: u32) -> Result<KeyPair, WalletError> {
+        match self.master {
+            Some(ref master) => Ok(master
+                .derive_child(ChildNumber::Hardened(KEYCHAIN_ACCOUNT))?
+                .derive_child(ChildNumber::Hardened(KEYCHAIN_EXTERNAL))?
+                .derive_child(ChildNumber::Hardened(index))?
+                .keypair()?),
+            None if self.is_locked() => Err(WalletError::Locked),
+            None => Err(WalletError::NoSeed),
+        }
+    }
+
+    /// Derives next key from wallet seed
+    pub fn new_keypair(&mut self) -> Result<Address, WalletError> {
+        let keypair = self.derive_keypair(self.data.next_child)?;
+        info!("Derived keypair {} with index {}", keypair, self.data.next_child);
+        self.data.next_child += 1;
+        self.add_keypair(keypair, now())
+    }
+
+    pub fn add_keypair_from_private(&mut self, private: Private) -> Result<Address, WalletError>
+    {
+        let keypair = KeyPair::from_private(private)?;
+        info!("Added keys {}", keypair);
+        self.add_keypair(keypair, now())
+    }
+
+    fn add_keypair(&mut self, keypair: KeyPair, created: u32) -> Result<Address, WalletError> {
         let address = keypair.address();
-        info!("Generated keypair {}", keypair);
+        if self.find_keypair_with_public_hash(&address.hash).is_some() {
+            return Ok(address);
+        }
+
+        let record = match self.encryption_key {
+            Some(ref key) => Self::encrypt_keypair(key, &keypair, created)?,
+            None => return Err(WalletError::Locked),
+        };
+
         info!("Public key hash is {}", address.hash);
         info!("Address is {}", address);
+        self.data.records.push(record);
         self.keys.push(keypair);
-        address
+        // address becomes spendable once its key is known
+        if let Some(index) = self.watch_only.iter().position(|watched| watched.hash == address.hash) {
+            self.watch_only.remove(index);
+            self.data.watch_only.remove(index);
+        }
+        self.save()?;
+        Ok(address)
     }
 
-    pub fn add_keypair_from_private(&mut self, private: Private) -> Result<Address, Error>
-    {
-        match KeyPair::from_private(private)
-        {
-            Ok(keypair) =>
-            {
-                let address = keypair.address();
-                info!("Added keys {}", keypair);
-                info!("Public key hash is {}", address.hash);
-                info!("Address is {}", address);
-                self.keys.push(keypair);
-                Ok(address)
-            } 
-            Err(error) => Err(error)
+    /// Starts tracking `address` without its private key. Doesn't need wallet to be unlocked
+    pub fn import_address(&mut self, address: Address) -> Result<(), WalletError> {
+        if self.data.records.iter().any(|record| record.address_hash == address.hash) {
+            info!("Wallet already has key for {}", address);
+            return Ok(());
+        }
+        if self.is_watch_only(&address.hash) {
+            return Ok(());
+        }
+
+        self.data.watch_only.push(address.layout().to_vec().into());
+        self.watch_only.push(address.clone());
+        self.save()?;
+        info!("Watching address {}", address);
+        Ok(())
+    }
+
+    /// Remembers multisig witness script, so inputs spending its P2WSH outputs can be co-signed
+    pub fn add_witness_script(&mut self, script: Bytes) -> Result<(), WalletError> {
+        if self.data.witness_scripts.contains(&script) {
+            return Ok(());
+        }
+        self.data.witness_scripts.push(script);
+        self.save()
+    }
+
+    /// Witness script with sha256 hash equal to `script_hash`
+    pub fn find_witness_script(&self, script_hash: &H256) -> Option<Bytes> {
+        self.data.witness_scripts.iter().find(|script| sha256(script) == *script_hash).cloned()
+    }
+
+    /// Keeps signed `transaction` until its time lock lets it be broadcast
+    pub fn add_pending_transaction(&mut self, transaction: &Transaction) -> Result<(), WalletError> {
+        self.data.pending.push(serialize_with_flags(transaction, SERIALIZE_TRANSACTION_WITNESS));
+        self.save()
+    }
+
+    /// Payments added by `add_pending_transaction` which are not broadcast yet
+    pub fn pending_transactions(&self) -> Vec<Transaction> {
+        self.data.pending
+            .iter()
+            .filter_map(|raw| Reader::new(raw).read().ok())
+            .collect()
+    }
+
+    /// Forgets pending payment once it is broadcast
+    pub fn remove_pending_transaction(&mut self, hash: &H256) -> Result<(), WalletError> {
+        let before = self.data.pending.len();
+        self.data.pending.retain(|raw| match Reader::new(raw).read::<Transaction>() {
+            Ok(transaction) => transaction.hash() != *hash,
+            Err(_) => true,
+        });
+        if self.data.pending.len() == before {
+            return Ok(());
+        }
+        self.save()
+    }
+
+    /// Label of wallet address, if user gave it one
+    pub fn label(&self, address_hash: &AddressHash) -> Option<&str> {
+        self.labelled
+            .iter()
+            .position(|address| address.hash == *address_hash)
+            .map(|index| self.data.labels[index].label.as_str())
+    }
+
+    /// Names address with `label`. Empty label removes the name
+    pub fn set_label(&mut self, address: &Address, label: String) -> Result<(), WalletError> {
+        if let Some(index) = self.labelled.iter().position(|labelled| labelled.hash == address.hash) {
+            self.labelled.remove(index);
+            self.data.labels.remove(index);
+        }
+        if !label.is_empty() {
+            self.data.labels.push(AddressLabel {
+                address: address.layout().to_vec().into(),
+                label,
+            });
+            self.labelled.push(address.clone());
+        }
+        self.save()
+    }
+
+    /// Distinct labels in alphabetical order
+    pub fn labels(&self) -> Vec<String> {
+        let mut labels: Vec<String> = self.data.labels.iter().map(|label| label.label.clone()).collect();
+        labels.sort();
+        labels.dedup();
+        labels
+    }
+
+    /// Addresses named with `label`
+    pub fn addresses_by_label(&self, label: &str) -> Vec<Address> {
+        self.labelled
+            .iter()
+            .zip(self.data.labels.iter())
+            .filter(|&(_, existing)| existing.label == label)
+            .map(|(address, _)| address.clone())
+            .collect()
+    }
+
+    /// Writes every wallet key to new text file at `path` as WIF with its creation time, label and address,
+    /// followed by watch-only addresses and multisig witness scripts. HD master key is written as a comment,
+    /// since seed it is derived from can't be restored from it. Returns number of written keys
+    pub fn dump<P: AsRef<Path>>(&self, path: P) -> Result<usize, WalletError> {
+        if self.is_locked() {
+            return Err(WalletError::Locked);
+        }
+
+        let mut file = create_private_file(path.as_ref())?;
+        writeln!(file, "# Wallet dump created by rustheus")?;
+        if let Some(ref master) = self.master {
+            writeln!(file, "# extended private masterkey: {}", master)?;
+        }
+        writeln!(file, "# Keys are listed as: <private key in WIF> <creation unix time> label=<label> # addr=<address>")?;
+        for (keypair, record) in self.keys.iter().zip(self.data.records.iter()) {
+            let address = keypair.address();
+            let label = self.label(&address.hash).unwrap_or("");
+            writeln!(file, "{} {} label={} # addr={}", keypair.private(), record.created, encode_label(label), address)?;
+        }
+        writeln!(file, "# Watch-only addresses are listed as: watch=<address> label=<label>")?;
+        for address in &self.watch_only {
+            let label = self.label(&address.hash).unwrap_or("");
+            writeln!(file, "watch={} label={}", address, encode_label(label))?;
+        }
+        writeln!(file, "# Multisig witness scripts are listed as: script=<hex>")?;
+        for script in &self.data.witness_scripts {
+            writeln!(file, "script={}", script.to_hex())?;
+        }
+        file.sync_all()?;
+        Ok(self.keys.len())
+    }
+
+    /// Adds keys, watch-only addresses, witness scripts and labels from file written by `dump`. Keys which
+    /// wallet already has only get their labels. Returns number of added keys
+    pub fn import_dump<P: AsRef<Path>>(&mut self, path: P) -> Result<usize, WalletError> {
+        if self.is_locked() {
+            return Err(WalletError::Locked);
+        }
+
+        let file = fs::File::open(path)?;
+        let mut entries = vec![];
+        for (index, line) in BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            // everything after '#' is a comment
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let entry = parse_dump_line(line).ok_or(WalletError::InvalidDumpLine(index + 1))?;
+            entries.push(entry);
+        }
+
+        let mut added = 0;
+        for entry in entries {
+            match entry {
+                DumpEntry::Key(private, created, label) => {
+                    let keypair = KeyPair::from_private(private)?;
+                    let keypair_address = keypair.address();
+                    if self.find_keypair_with_public_hash(&keypair_address.hash).is_none() {
+                        self.add_keypair(keypair, created)?;
+                        added += 1;
+                    }
+                    if !label.is_empty() {
+                        self.set_label(&keypair_address, label)?;
+                    }
+                }
+                DumpEntry::WatchOnly(address, label) => {
+                    self.import_address(address.clone())?;
+                    if !label.is_empty() {
+                        self.set_label(&address, label)?;
+                    }
+                }
+                DumpEntry::WitnessScript(script) => self.add_witness_script(script)?,
+            }
         }
+        Ok(added)
+    }
+
+    /// Wallet transactions found by scanning blocks
+    pub fn transaction_records(&self) -> &[TransactionRecord] {
+        &self.data.transactions
+    }
+
+    /// Number of scanned blocks and hash of the last one
+    pub fn scanned_tip(&self) -> (u32, H256) {
+        (self.data.scanned_blocks, self.data.scanned_tip.clone())
+    }
+
+    /// Replaces records of blocks starting from `from_height` with `records` found in blocks up to `scanned_blocks`
+    pub fn update_transaction_records(
+        &mut self,
+        from_height: u32,
+        records: Vec<TransactionRecord>,
+        scanned_blocks: u32,
+        scanned_tip: H256,
+    ) -> Result<(), WalletError> {
+        self.data.transactions.retain(|record| record.height < from_height);
+        self.data.transactions.extend(records);
+        self.data.scanned_blocks = scanned_blocks;
+        self.data.scanned_tip = scanned_tip;
+        self.save()
+    }
+
+    pub fn is_watch_only(&self, address_hash: &AddressHash) -> bool {
+        self.watch_only.iter().any(|address| address.hash == *address_hash)
     }
 
     pub fn find_keypair_with_public_hash(&self, pubkey_hash: &H160) -> Option<&KeyPair> {
         self.keys.iter().find(|&keypair| keypair.public().address_hash() == *pubkey_hash)
     }
-    /// Checks if wallet has at least one key and shows error message if not
+
+    /// Decrypted wallet keys. Empty while wallet is locked
+    pub fn keypairs(&self) -> &[KeyPair] {
+        &self.keys
+    }
+
+    /// Public key hashes of every wallet key. Available even if wallet is locked
+    pub fn address_hashes(&self) -> Vec<AddressHash> {
+        self.data.records.iter().map(|record| record.address_hash.clone()).collect()
+    }
+
+    /// Hashes of wallet keys followed by hashes of watch-only addresses
+    pub fn tracked_address_hashes(&self) -> Vec<AddressHash> {
+        let mut hashes = self.address_hashes();
+        hashes.extend(self.watch_only.iter().map(|address| address.hash.clone()));
+        hashes
+    }
+
+    /// Checks if wallet has at least one key or watch-only address and shows error message if not
     pub fn is_ready(&self) -> bool {
-        if self.keys.is_empty() {
-            error!("No wallet was created or loaded. Use `walletcreate` or `walletload` to create one.");
+        if self.data.records.is_empty() && self.watch_only.is_empty() {
+            error!("No wallet was created or loaded. Use `walletcreate`, `walletload` or `importaddress` to create one.");
             false
         } else {
             true
         }
     }
-}
\ No newline at end of file
+
+    /// Checks if wallet has keys and they are decrypted. Shows error message if not
+    pub fn is_ready_to_sign(&self) -> bool {
+        if self.data.records.is_empty() {
+            error!("No wallet keys were created or loaded. Use `walletcreate` or `walletload` to create one.");
+            false
+        } else if self.is_locked() {
+            error!("Wallet is locked. Use `walletpassphrase` to unlock it.");
+            false
+        } else {
+            true
+        }
+    }
+
+    fn derive_key(&self, passphrase: &str) -> H256 {
+        let mut key = H256::default();
+        pbkdf2_sha256(passphrase.as_bytes(), &self.data.salt, self.data.iterations, &mut key[..]);
+        key
+    }
+
+    /// Encrypts data with random initialization vector which is prepended to the result
+    fn encrypt(key: &H256, data: &[u8]) -> Result<Bytes, WalletError> {
+        let mut iv = [0u8; IV_SIZE];
+        Random::generate_bytes(&mut iv)?;
+        let encrypted = aes256_cbc_encrypt(&key[..], &iv, data).map_err(|_| WalletError::Corrupted)?;
+
+        let mut result = iv.to_vec();
+        result.extend(encrypted);
+        Ok(result.into())
+    }
+
+    fn decrypt(key: &H256, data: &Bytes) -> Result<Vec<u8>, WalletError> {
+        if data.len() <= IV_SIZE {
+            return Err(WalletError::Corrupted);
+        }
+        let (iv, encrypted) = data.split_at(IV_SIZE);
+        aes256_cbc_decrypt(&key[..], iv, encrypted).map_err(|_| WalletError::Corrupted)
+    }
+
+    fn encrypt_keypair(key: &H256, keypair: &KeyPair, created: u32) -> Result<KeyRecord, WalletError> {
+        Ok(KeyRecord {
+            address_hash: keypair.public().address_hash(),
+            created,
+            encrypted_private: Self::encrypt(key, &keypair.private().layout())?,
+        })
+    }
+
+    fn decrypt_record(key: &H256, record: &KeyRecord) -> Result<KeyPair, WalletError> {
+        let layout = Self::decrypt(key, &record.encrypted_private)?;
+        let private = Private::from_layout(&layout)?;
+        Ok(KeyPair::from_private(private)?)
+    }
+
+    /// Writes wallet to a temporary file first, so crash during write does not destroy existing wallet
+    fn save(&self) -> Result<(), WalletError> {
+        let temp_path = self.path.with_extension("tmp");
+        {
+            let mut file = fs::File::create(&temp_path)?;
+            file.write_all(&serialize(&self.data))?;
+            file.sync_all()?;
+        }
+        fs::rename(&temp_path, &self.path)?;
+        Ok(())
+    }
+}
+
+/// Reads wallet file of current or any older version. Fields which older versions didn't have are left empty,
+/// so the file is upgraded to current version by the next save
+fn read_wallet_data(buffer: &[u8]) -> Result<WalletData, WalletError> {
+    let mut reader = Reader::new(buffer);
+    let version: u32 = reader.read()?;
+    if version == 0 || version > WALLET_VERSION {
+        return Err(WalletError::UnsupportedVersion(version));
+    }
+
+    let mut data = WalletData {
+        version: WALLET_VERSION,
+        salt: reader.read()?,
+        iterations: reader.read()?,
+        key_check: reader.read()?,
+        ..Default::default()
+    };
+    // version 2 added HD seed
+    if version >= 2 {
+        data.encrypted_seed = reader.read()?;
+        data.next_child = reader.read()?;
+    }
+    // version 6 added key creation time
+    data.records = if version >= 6 {
+        reader.read_list()?
+    } else {
+        reader.read_list::<KeyRecordV1>()?.into_iter().map(KeyRecord::from).collect()
+    };
+    // version 3 added watch-only addresses
+    if version >= 3 {
+        data.watch_only = reader.read_list()?;
+    }
+    // version 4 added multisig witness scripts
+    if version >= 4 {
+        data.witness_scripts = reader.read_list()?;
+    }
+    // version 6 added address labels, version 7 stores whole labelled address instead of its hash
+    if version >= 7 {
+        data.labels = reader.read_list()?;
+    } else if version == 6 {
+        let watch_only: Vec<Address> = data.watch_only
+            .iter()
+            .filter_map(|layout| Address::from_layout(layout).ok())
+            .collect();
+        data.labels = reader.read_list::<AddressLabelV6>()?
+            .into_iter()
+            .map(|AddressLabelV6 { address_hash, label }| {
+                // hash which is not watched belongs to wallet key, which address is P2PKH
+                let address = watch_only
+                    .iter()
+                    .find(|address| address.hash == address_hash)
+                    .cloned()
+                    .unwrap_or_else(|| Address {
+                        kind: Type::P2PKH,
+                        network: Network::Mainnet,
+                        hash: address_hash,
+                    });
+                AddressLabel {
+                    address: address.layout().to_vec().into(),
+                    label,
+                }
+            })
+            .collect();
+    }
+    // version 5 added wallet transaction records
+    if version >= 5 {
+        data.transactions = reader.read_list()?;
+        data.scanned_blocks = reader.read()?;
+        data.scanned_tip = reader.read()?;
+    }
+    // version 8 added time-locked payments waiting to be broadcast
+    if version >= 8 {
+        data.pending = reader.read_list()?;
+    }
+    if !reader.is_finished() {
+        return Err(WalletError::Corrupted);
+    }
+
+    if version != WALLET_VERSION {
+        info!("Wallet of version {} is upgraded to version {}", version, WALLET_VERSION);
+    }
+    Ok(data)
+}
+
+fn now() -> u32 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as u32)
+        .unwrap_or(0)
+}
+
+/// Percent-encodes characters which would break wallet dump line: whitespace, '#' and '%' itself
+fn encode_label(label: &str) -> String {
+    let mut encoded = String::new();
+    for c in label.chars() {
+        if c.is_whitespace() || c == '#' || c == '%' {
+            for byte in c.to_string().bytes() {
+                encoded.push_str(&format!("%{:02X}", byte));
+            }
+        } else {
+            encoded.push(c);
+        }
+    }
+    encoded
+}
+
+fn decode_label(encoded: &str) -> Option<String> {
+    let mut bytes = vec![];
+    let mut rest = encoded.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            if tail.len() < 2 {
+                return None;
+            }
+            let hex = ::std::str::from_utf8(&tail[..2]).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// Line of wallet dump
+enum DumpEntry {
+    /// Private key with its creation time and label
+    Key(Private, u32, String),
+    /// Watch-only address with its label
+    WatchOnly(Address, String),
+    /// Multisig witness script
+    WitnessScript(Bytes),
+}
+
+/// Parses `<wif> <creation time> [label=<label>]`, `watch=<address> [label=<label>]` or `script=<hex>` line
+/// of wallet dump with comment already removed
+fn parse_dump_line(line: &str) -> Option<DumpEntry> {
+    let mut fields = line.split_whitespace();
+    let first = fields.next()?;
+    if first.starts_with("watch=") {
+        let address = Address::from_str(&first["watch=".len()..]).ok()?;
+        Some(DumpEntry::WatchOnly(address, parse_dump_label(fields.next())?))
+    } else if first.starts_with("script=") {
+        let script = Bytes::from_str(&first["script=".len()..]).ok()?;
+        Some(DumpEntry::WitnessScript(script))
+    } else {
+        let private = Private::from_str(first).ok()?;
+        let created = fields.next()?.parse().ok()?;
+        Some(DumpEntry::Key(private, created, parse_dump_label(fields.next())?))
+    }
+}
+
+/// Parses optional `label=<label>` field of wallet dump line. Missing field means empty label
+fn parse_dump_label(field: Option<&str>) -> Option<String> {
+    match field {
+        Some(field) if field.starts_with("label=") => decode_label(&field["label=".len()..]),
+        Some(_) => None,
+        None => Some(String::new()),
+    }
+}
+
+/// Creates new file readable only by its owner, since wallet dump holds private keys
+#[cfg(unix)]
+fn create_private_file(path: &Path) -> io::Result<fs::File> {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    fs::OpenOptions::new().write(true).create_new(true).mode(0o600).open(path)
+}
+
+#[cfg(not(unix))]
+fn create_private_file(path: &Path) -> io::Result<fs::File> {
+    fs::OpenOptions::new().write(true).create_new(true).open(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use chain::{OutPoint, Transaction, TransactionInput, TransactionOutput};
+    use crypto::sha256;
+    use keys::{Address, AddressHash, DisplayLayout, Private, Type};
+    use keys::network::Network;
+    use primitives::bytes::Bytes;
+    use primitives::hash::H256;
+    use ser::{serialize, serialize_with_flags, CompactInteger, Stream, SERIALIZE_TRANSACTION_WITNESS};
+    use std::path::PathBuf;
+    use std::str::FromStr;
+    use std::{env, fs, process};
+    use super::{read_wallet_data, Wallet, TransactionRecord, WalletError, WALLET_VERSION, KDF_ITERATIONS, SALT_SIZE};
+
+    /// Path in temporary directory which is unique for test `name`
+    fn temp_path(name: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("rustheus-wallet-{}-{}", process::id(), name));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    const SECRET: &'static str = "Kwr371tjA9u2rFSMZjTNun2PXXP3WPZu2afRHTcta6KxEUdm1vEw";
+
+    fn key_hash() -> AddressHash {
+        AddressHash::from(3)
+    }
+
+    fn watched() -> Address {
+        Address {
+            kind: Type::P2SH,
+            network: Network::Mainnet,
+            hash: AddressHash::from(4),
+        }
+    }
+
+    fn layout(address: &Address) -> Bytes {
+        address.layout().to_vec().into()
+    }
+
+    fn record() -> TransactionRecord {
+        TransactionRecord {
+            hash: H256::from(5),
+            height: 10,
+            block_hash: H256::from(6),
+        }
+    }
+
+    fn pending() -> Transaction {
+        Transaction {
+            version: 2,
+            inputs: vec![TransactionInput {
+                previous_output: OutPoint {
+                    hash: H256::from(12),
+                    index: 1,
+                },
+                script_sig: Bytes::new(),
+                sequence: 10,
+                script_witness: vec![Bytes::from(vec![1u8, 2, 3])],
+            }],
+            outputs: vec![TransactionOutput {
+                value: 1000,
+                script_pubkey: Bytes::from(vec![0x51u8]),
+            }],
+            lock_time: 0,
+        }
+    }
+
+    /// Writes wallet file of given version with one entry of every kind this version stores
+    fn wallet_file(version: u32) -> Bytes {
+        let mut stream = Stream::new();
+        stream
+            .append(&version)
+            .append(&Bytes::from(vec![1u8; SALT_SIZE]))
+            .append(&KDF_ITERATIONS)
+            .append(&H256::from(2));
+        if version >= 2 {
+            stream.append(&Bytes::from(vec![7u8; 48])).append(&8u32);
+        }
+        stream.append(&CompactInteger::from(1usize)).append(&key_hash());
+        if version >= 6 {
+            stream.append(&1_500_000_000u32);
+        }
+        stream.append(&Bytes::from(vec![9u8; 48]));
+        if version >= 3 {
+            stream.append_list::<Bytes, Bytes>(&[layout(&watched())]);
+        }
+        if version >= 4 {
+            stream.append_list::<Bytes, Bytes>(&[Bytes::from(vec![0x51u8, 0xae])]);
+        }
+        if version == 6 {
+            stream
+                .append(&CompactInteger::from(2usize))
+                .append(&key_hash())
+                .append(&String::from("key"))
+                .append(&watched().hash)
+                .append(&String::from("watched"));
+        } else if version >= 7 {
+            stream
+                .append(&CompactInteger::from(1usize))
+                .append(&layout(&watched()))
+                .append(&String::from("watched"));
+        }
+        if version >= 5 {
+            stream
+                .append_list::<TransactionRecord, TransactionRecord>(&[record()])
+                .append(&100u32)
+                .append(&H256::from(11));
+        }
+        if version >= 8 {
+            stream.append_list::<Bytes, Bytes>(&[serialize_with_flags(&pending(), SERIALIZE_TRANSACTION_WITNESS)]);
+        }
+        stream.out()
+    }
+
+    #[test]
+    fn upgrades_version_1_wallet() {
+        let data = read_wallet_data(&wallet_file(1)).unwrap();
+        assert_eq!(data.version, WALLET_VERSION);
+        assert_eq!(data.iterations, KDF_ITERATIONS);
+        assert_eq!(data.key_check, H256::from(2));
+        assert_eq!(data.records.len(), 1);
+        assert_eq!(data.records[0].address_hash, key_hash());
+        assert_eq!(data.records[0].created, 0);
+        assert_eq!(data.records[0].encrypted_private, Bytes::from(vec![9u8; 48]));
+        assert!(data.encrypted_seed.is_empty());
+        assert_eq!(data.next_child, 0);
+    }
+
+    #[test]
+    fn upgrades_version_2_wallet() {
+        let data = read_wallet_data(&wallet_file(2)).unwrap();
+        assert_eq!(data.version, WALLET_VERSION);
+        assert_eq!(data.encrypted_seed, Bytes::from(vec![7u8; 48]));
+        assert_eq!(data.next_child, 8);
+        assert_eq!(data.records[0].address_hash, key_hash());
+        assert!(data.watch_only.is_empty());
+    }
+
+    #[test]
+    fn upgrades_version_3_wallet() {
+        let data = read_wallet_data(&wallet_file(3)).unwrap();
+        assert_eq!(data.version, WALLET_VERSION);
+        assert_eq!(data.watch_only, vec![layout(&watched())]);
+        assert!(data.witness_scripts.is_empty());
+    }
+
+    #[test]
+    fn upgrades_version_4_wallet() {
+        let data = read_wallet_data(&wallet_file(4)).unwrap();
+        assert_eq!(data.version, WALLET_VERSION);
+        assert_eq!(data.witness_scripts, vec![Bytes::from(vec![0x51u8, 0xae])]);
+        assert!(data.transactions.is_empty());
+        assert_eq!(data.scanned_blocks, 0);
+        assert_eq!(data.scanned_tip, H256::default());
+    }
+
+    #[test]
+    fn upgrades_version_5_wallet() {
+        let data = read_wallet_data(&wallet_file(5)).unwrap();
+        assert_eq!(data.version, WALLET_VERSION);
+        assert_eq!(data.transactions, vec![record()]);
+        assert_eq!(data.scanned_blocks, 100);
+        assert_eq!(data.scanned_tip, H256::from(11));
+        assert_eq!(data.records[0].created, 0);
+        assert!(data.labels.is_empty());
+    }
+
+    #[test]
+    fn upgrades_version_6_wallet() {
+        let data = read_wallet_data(&wallet_file(6)).unwrap();
+        assert_eq!(data.version, WALLET_VERSION);
+        assert_eq!(data.records[0].created, 1_500_000_000);
+        assert_eq!(data.labels.len(), 2);
+        let key_address = Address {
+            kind: Type::P2PKH,
+            network: Network::Mainnet,
+            hash: key_hash(),
+        };
+        assert_eq!(data.labels[0].address, layout(&key_address));
+        assert_eq!(data.labels[0].label, "key");
+        assert_eq!(data.labels[1].address, layout(&watched()));
+        assert_eq!(data.labels[1].label, "watched");
+        assert_eq!(data.transactions, vec![record()]);
+    }
+
+    #[test]
+    fn upgrades_version_7_wallet() {
+        let data = read_wallet_data(&wallet_file(7)).unwrap();
+        assert_eq!(data.version, WALLET_VERSION);
+        assert_eq!(data.labels.len(), 1);
+        assert_eq!(data.labels[0].address, layout(&watched()));
+        assert!(data.pending.is_empty());
+    }
+
+    #[test]
+    fn reads_current_wallet_as_written() {
+        let file = wallet_file(WALLET_VERSION);
+        let data = read_wallet_data(&file).unwrap();
+        assert_eq!(serialize(&data), file);
+    }
+
+    #[test]
+    fn rejects_unsupported_wallet_version() {
+        for &version in &[0, WALLET_VERSION + 1] {
+            match read_wallet_data(&wallet_file(version)) {
+                Err(WalletError::UnsupportedVersion(v)) => assert_eq!(v, version),
+                other => panic!("unexpected result {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_wallet_with_trailing_bytes() {
+        let mut file = wallet_file(WALLET_VERSION).take();
+        file.push(0);
+        match read_wallet_data(&file) {
+            Err(WalletError::Corrupted) => (),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn keeps_pending_transactions_with_witness() {
+        let path = temp_path("pending");
+        {
+            let mut wallet = Wallet::open(&path).unwrap();
+            wallet.add_pending_transaction(&pending()).unwrap();
+        }
+
+        let mut wallet = Wallet::open(&path).unwrap();
+        assert_eq!(wallet.pending_transactions(), vec![pending()]);
+        wallet.remove_pending_transaction(&pending().hash()).unwrap();
+        assert!(wallet.pending_transactions().is_empty());
+        assert!(Wallet::open(&path).unwrap().pending_transactions().is_empty());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn imports_dumped_wallet() {
+        let dump_path = temp_path("dump");
+        let key_label = "100% sure #1\nsecond line";
+        let watched_label = "cold  storage";
+        let script = Bytes::from(vec![0x51u8, 0xae]);
+
+        let source_path = temp_path("dump-source");
+        let (key_address, created) = {
+            let mut wallet = Wallet::open(&source_path).unwrap();
+            wallet.unlock("passphrase").unwrap();
+            let key_address = wallet.add_keypair_from_private(Private::from_str(SECRET).unwrap()).unwrap();
+            wallet.set_label(&key_address, key_label.to_owned()).unwrap();
+            wallet.import_address(watched()).unwrap();
+            wallet.set_label(&watched(), watched_label.to_owned()).unwrap();
+            wallet.add_witness_script(script.clone()).unwrap();
+            assert_eq!(wallet.dump(&dump_path).unwrap(), 1);
+            (key_address, wallet.data.records[0].created)
+        };
+
+        let target_path = temp_path("dump-target");
+        let mut wallet = Wallet::open(&target_path).unwrap();
+        wallet.unlock("passphrase").unwrap();
+        assert_eq!(wallet.import_dump(&dump_path).unwrap(), 1);
+        assert!(wallet.find_keypair_with_public_hash(&key_address.hash).is_some());
+        assert_eq!(wallet.data.records[0].created, created);
+        assert_eq!(wallet.label(&key_address.hash), Some(key_label));
+        assert!(wallet.is_watch_only(&watched().hash));
+        assert_eq!(wallet.label(&watched().hash), Some(watched_label));
+        assert_eq!(wallet.find_witness_script(&sha256(&script)), Some(script));
+
+        for path in &[dump_path, source_path, target_path] {
+            fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_dump_lines() {
+        let wallet_path = temp_path("malformed");
+        let dump_path = temp_path("malformed-dump");
+        let mut wallet = Wallet::open(&wallet_path).unwrap();
+        wallet.unlock("passphrase").unwrap();
+
+        let malformed = [
+            "notakey 1500000000".to_owned(),
+            SECRET.to_owned(),
+            format!("{} notatime", SECRET),
+            format!("{} 1500000000 name", SECRET),
+            format!("{} 1500000000 label=%zz", SECRET),
+            format!("{} 1500000000 label=%4", SECRET),
+            format!("{} 1500000000 label=%C3", SECRET),
+            format!("{} 1500000000 label=%é", SECRET),
+            "watch=notanaddress".to_owned(),
+            format!("watch={} name", watched()),
+            "script=xyz".to_owned(),
+        ];
+        for line in &malformed {
+            fs::write(&dump_path, format!("# comment\n{}\n", line)).unwrap();
+            match wallet.import_dump(&dump_path) {
+                Err(WalletError::InvalidDumpLine(2)) => (),
+                other => panic!("line {:?} gives unexpected result {:?}", line, other),
+            }
+            fs::remove_file(&dump_path).unwrap();
+        }
+        assert!(wallet.data.records.is_empty());
+        assert!(wallet.data.watch_only.is_empty());
+
+        fs::remove_file(&wallet_path).unwrap();
+    }
+}
@@ -1,139 +1,714 @@
-use chain::constants::SEQUENCE_LOCKTIME_DISABLE_FLAG;
-use chain::{OutPoint, Transaction};
+use chain::{OutPoint, Transaction, Psbt};
 //use chain_builder::TransactionBuilder;
-use db::SharedStore;
-use keys::{Address, Private};
+use db::{SharedStore, BlockRef};
+use keys::{Address, AddressHash, Private, Public, Mnemonic, SegwitAddress, Type, sign_message, verify_message};
+use keys::hex::ToHex;
+use keys::network::Network;
+use keys::Error as KeyError;
+use crypto::sha256;
+use primitives::bytes::Bytes;
+use ser::{deserialize, serialize_with_flags, Reader, SERIALIZE_TRANSACTION_WITNESS};
+use std::fmt;
+use std::str::FromStr;
 use memory_pool::MemoryPoolRef;
 use message::types::Tx;
-use script::{Builder, Script, SighashBase, SignatureVersion, TransactionInputSigner};
+use script::{Builder, Script, SighashBase, SignatureVersion, TransactionInputSigner, finalize_psbt};
 use service::Service;
-use std::sync::mpsc::Receiver;
-use sync::MessageWrapper;
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
+use std::time::{Duration, Instant};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use sync::{AcceptorRef, MessageWrapper};
+use futures::Future;
+use primitives::hash::H256;
 use wallet::{Wallet, WalletRef};
-use transaction_helper::TransactionHelperRef;
-use chain::{TransactionInput, TransactionOutput};
+use wallets::{WalletsRef, LoadedWallet, DEFAULT_WALLET};
+use transaction_helper::{TransactionHelperRef, TimeLock};
+use coin_selection::CoinSelection;
+use chain::TransactionInput;
 
-#[derive(Debug, PartialEq)]
+/// Seconds between relays of wallet transactions which are not mined yet
+const REBROADCAST_INTERVAL: u64 = 10 * 60;
+
+/// Destination of wallet payment
+#[derive(Debug, PartialEq, Clone)]
+pub enum Recipient {
+    /// Legacy address. Public key hash is paid as witness public key hash, script hash as it is
+    Address(Address),
+    Segwit(SegwitAddress),
+}
+
+impl Recipient {
+    pub fn script_pubkey(&self) -> Script {
+        match *self {
+            Recipient::Address(ref address) if address.kind == Type::P2SH => Builder::build_p2sh(&address.hash),
+            Recipient::Address(ref address) => Builder::build_p2wpkh(&address.hash),
+            Recipient::Segwit(ref address) if address.program.len() == 32 =>
+                Builder::build_p2wsh(&address.program[..].into()),
+            Recipient::Segwit(ref address) => Builder::build_p2wpkh(&address.program[..].into()),
+        }
+    }
+}
+
+impl FromStr for Recipient {
+    type Err = KeyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Address::from_str(s)
+            .map(Recipient::Address)
+            .or_else(|_| SegwitAddress::from_str(s).map(Recipient::Segwit))
+    }
+}
+
+impl fmt::Display for Recipient {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Recipient::Address(ref address) => address.fmt(f),
+            Recipient::Segwit(ref address) => address.fmt(f),
+        }
+    }
+}
+
+#[derive(Debug)]
 pub enum Task {
-    CreateWallet(),
-    SendCash(Address, u64),
+    CreateWallet(usize),
+    RestoreWallet(Mnemonic),
+    SendCash(Recipient, u64, u64, CoinSelection, Option<TimeLock>),
+    SendData(Bytes, u64),
     LoadWallet(Private),
+    ImportAddress(Address),
+    /// Name address with label. Empty label removes the name
+    SetLabel(Address, String),
+    CreateMultisig(u8, Vec<Public>),
+    Cosign(Bytes),
+    CreatePsbt(Bytes),
+    UpdatePsbt(Psbt),
+    SignPsbt(Psbt),
+    CombinePsbt(Vec<Psbt>),
+    FinalizePsbt(Psbt),
+    ExtractPsbt(Psbt),
+    SignMessage(Address, String),
+    VerifyMessage(Address, String, String),
     CalculateBalance(),
+    ShowHistory(),
+    Rescan(u32),
+    /// Lock outputs if flag is false, unlock them otherwise. Empty list unlocks every output
+    LockUnspent(bool, Vec<OutPoint>),
+    ListLockUnspent(),
+    /// Replace memory pool transaction with one paying higher fee, optionally with given fee rate
+    BumpFee(H256, Option<u64>),
+    /// Move every coin of external key to wallet with given fee rate
+    SweepPrivateKey(Private, u64),
+    /// Write every wallet key with its creation time and label to file
+    DumpWallet(PathBuf),
+    /// Add keys from file written by `DumpWallet` and rescan blockchain
+    ImportWallet(PathBuf),
+    Unlock(String),
+    Lock(),
+    /// Load named wallet, creating it if there is none yet. Whether it is loaded is sent back
+    OpenWallet(String, Sender<bool>),
+    /// Unload named wallet
+    UnloadWallet(String),
+    /// List loaded wallets, marking the one request is made for
+    ListWallets(),
 }
 
+/// Task together with name of loaded wallet to run it against, like RPC path `/wallet/<name>` selects one.
+/// Default wallet is used if there is no name
+pub type Request = (Option<String>, Task);
+
 pub struct WalletManager {
-    receiver: Receiver<Task>,
+    receiver: Receiver<Request>,
     mempool: MemoryPoolRef,
     wrapper: MessageWrapper,
+    wallets: WalletsRef,
+    /// Wallet of request being run
     wallet: WalletRef,
     storage: SharedStore,
     transaction_helper: TransactionHelperRef,
+    acceptor: AcceptorRef,
+    last_rebroadcast: Instant,
 }
 
 impl WalletManager {
     pub fn new(
         mempool: MemoryPoolRef,
         storage: SharedStore,
-        receiver: Receiver<Task>,
+        receiver: Receiver<Request>,
         wrapper: MessageWrapper,
-        wallet: WalletRef,
-        transaction_helper: TransactionHelperRef,    
+        wallets: WalletsRef,
+        acceptor: AcceptorRef,
     ) -> Self {
+        let LoadedWallet { wallet, transaction_helper } = wallets.default_wallet();
         WalletManager {
             receiver,
             mempool,
             wrapper,
             storage,
+            wallets,
             wallet,
             transaction_helper,
+            acceptor,
+            last_rebroadcast: Instant::now(),
+        }
+    }
+
+    fn open_wallet(&self, name: String, loaded: Sender<bool>) {
+        let result = self.wallets.load(&name);
+        match result {
+            Ok(_) => info!("Wallet {} is loaded", name),
+            Err(ref err) => error!("Failed to load wallet {}: {:?}", name, err),
+        }
+        // requester may be gone already
+        let _ = loaded.send(result.is_ok());
+    }
+
+    fn unload_wallet(&self, name: String) {
+        match self.wallets.unload(&name) {
+            Ok(_) => info!("Wallet {} is unloaded", name),
+            Err(err) => error!("Failed to unload wallet {}: {:?}", name, err),
+        }
+    }
+
+    fn list_wallets(&self, selected: &str) {
+        for name in self.wallets.names() {
+            let marker = if name == selected { "*" } else { " " };
+            info!("{} {}", marker, name);
+        }
+    }
+
+    /// Runs `task` against loaded wallet `name`
+    fn run_task(&mut self, name: &str, task: Task) {
+        // wallets are managed without being loaded themselves
+        match task {
+            Task::OpenWallet(wallet_name, loaded) => return self.open_wallet(wallet_name, loaded),
+            Task::UnloadWallet(wallet_name) => return self.unload_wallet(wallet_name),
+            Task::ListWallets() => return self.list_wallets(name),
+            _ => {}
+        }
+
+        let LoadedWallet { wallet, transaction_helper } = match self.wallets.get(name) {
+            Ok(loaded) => loaded,
+            Err(err) => {
+                error!("Can't use wallet {}: {:?}. Use `loadwallet` to load it", name, err);
+                return;
+            }
+        };
+        self.wallet = wallet;
+        self.transaction_helper = transaction_helper;
+
+        match task {
+            Task::CreateWallet(word_count) => self.create_wallet(word_count),
+            Task::RestoreWallet(mnemonic) => self.restore_wallet(mnemonic),
+            Task::LoadWallet(private) => self.load_from_key(private),
+            Task::ImportAddress(address) => self.import_address(address),
+            Task::SetLabel(address, label) => self.set_label(address, label),
+            Task::CreateMultisig(required, publics) => self.create_multisig(required, publics),
+            Task::Cosign(raw_transaction) => self.cosign(raw_transaction),
+            Task::CreatePsbt(raw_transaction) => self.create_psbt(raw_transaction),
+            Task::UpdatePsbt(psbt) => self.update_psbt(psbt),
+            Task::SignPsbt(psbt) => self.sign_psbt(psbt),
+            Task::CombinePsbt(psbts) => self.combine_psbt(psbts),
+            Task::FinalizePsbt(psbt) => self.finalize_psbt(psbt),
+            Task::ExtractPsbt(psbt) => self.extract_psbt(psbt),
+            Task::SignMessage(address, message) => self.sign_message(address, message),
+            Task::VerifyMessage(address, signature, message) => self.verify_message(address, signature, message),
+            Task::CalculateBalance() => self.calculate_balance(),
+            Task::ShowHistory() => self.show_history(),
+            Task::Rescan(from_height) => self.rescan(from_height),
+            Task::LockUnspent(unlock, out_points) => self.lock_unspent(unlock, out_points),
+            Task::ListLockUnspent() => self.list_lock_unspent(),
+            Task::BumpFee(hash, fee_rate) => self.bump_fee(hash, fee_rate),
+            Task::SweepPrivateKey(private, fee_rate) => self.sweep_private_key(private, fee_rate),
+            Task::DumpWallet(path) => self.dump_wallet(path),
+            Task::ImportWallet(path) => self.import_wallet(path),
+            Task::SendCash(to, amount, fee_rate, selection, time_lock) => self.send_cash(to, amount, fee_rate, selection, time_lock),
+            Task::SendData(data, fee_rate) => self.send_data(data, fee_rate),
+            Task::Unlock(passphrase) => self.unlock(passphrase),
+            Task::Lock() => self.lock(),
+            Task::OpenWallet(..) | Task::UnloadWallet(_) | Task::ListWallets() => unreachable!("handled above; qed"),
+        }
+    }
+
+    /// Creates wallet seed if there is none yet and derives new address from it
+    fn create_wallet(&self, word_count: usize) {
+        let mut wallet = self.wallet.write();
+        if !wallet.has_seed() {
+            match wallet.create_seed(word_count) {
+                Ok(mnemonic) => info!("Wallet seed is created. Write down these words to be able to restore wallet with `walletrestore`:\n{}", mnemonic),
+                Err(err) => {
+                    error!("Failed to create wallet seed: {:?}", err);
+                    return;
+                }
+            }
+        }
+
+        match wallet.new_keypair() {
+            Ok(_) => {}
+            Err(err) => error!("Failed to create keypair: {:?}", err),
+        }
+    }
+
+    fn restore_wallet(&self, mnemonic: Mnemonic) {
+        let used_addresses = self.used_address_hashes();
+        let restored = self.wallet.write().restore_seed(&mnemonic, |hash| used_addresses.contains(hash));
+        match restored {
+            Ok(restored) => {
+                info!("Wallet is restored with {} used keys", restored);
+                self.rescan(0);
+            }
+            Err(err) => error!("Failed to restore wallet: {:?}", err),
+        }
+    }
+
+    fn dump_wallet(&self, path: PathBuf) {
+        if !self.wallet.read().is_ready_to_sign() { return; }
+
+        match self.wallet.read().dump(&path) {
+            Ok(dumped) => info!("{} wallet keys are written to {}. Keep this file secret", dumped, path.display()),
+            Err(err) => error!("Failed to dump wallet: {:?}", err),
         }
     }
 
-    fn create_wallet(&self) {
-        self.wallet.write().new_keypair();
+    fn import_wallet(&self, path: PathBuf) {
+        if self.wallet.read().is_locked() {
+            error!("Wallet is locked. Use `walletpassphrase` to unlock it.");
+            return;
+        }
+
+        let imported = self.wallet.write().import_dump(&path);
+        match imported {
+            Ok(imported) => {
+                info!("{} keys are imported from {}", imported, path.display());
+                self.rescan(0);
+            }
+            Err(err) => error!("Failed to import wallet: {:?}", err),
+        }
+    }
+
+    /// Collects hashes of all addresses which ever received coins in canonical chain
+    fn used_address_hashes(&self) -> HashSet<AddressHash> {
+        let best_block = self.storage.best_block();
+        let block_provider = self.storage.as_block_provider();
+        (0..best_block.number + 1)
+            .flat_map(|number| block_provider.block_transactions(BlockRef::Number(number)))
+            .flat_map(|transaction| transaction.outputs)
+            .flat_map(|output| {
+                let script: Script = output.script_pubkey.into();
+                script.extract_destinations().unwrap_or(vec![])
+            })
+            .map(|address| address.hash)
+            .collect()
     }
 
     fn load_from_key(&self, private: Private) {
         match self.wallet.write().add_keypair_from_private(private) {
+            Ok(_) => info!("Use `rescan` to find past transactions of this key"),
+            Err(err) => error!("Failed to create wallet from private: {:?}", err),
+        }
+    }
+
+    fn import_address(&self, address: Address) {
+        match self.wallet.write().import_address(address) {
+            Ok(_) => info!("Use `rescan` to find past transactions of this address"),
+            Err(err) => error!("Failed to import address: {:?}", err),
+        }
+    }
+
+    fn unlock(&self, passphrase: String) {
+        match self.wallet.write().unlock(&passphrase) {
             Ok(_) => {}
-            Err(err) => error!("Failed to create wallet from private: {}", err),
+            Err(err) => error!("Failed to unlock wallet: {:?}", err),
         }
     }
 
+    fn lock(&self) {
+        self.wallet.write().lock();
+    }
+
     fn calculate_balance(&self) {
         if !self.wallet.read().is_ready() { return; }
-        let wallet = &self.wallet;
 
-        let user_address_hash = wallet.read().keys[0].address().hash;
-        let out_points = self.storage
-            .transaction_with_output_address(&user_address_hash);
-        let balance = out_points
-            .iter()
-            .map(|out_point| self.storage.transaction_output(out_point, 0).unwrap())
-            .fold(0, |credit, outpoint| credit + outpoint.value);
+        let balance = self.transaction_helper.balance();
+        info!("wallet balance is {}", balance.confirmed);
+        info!("unconfirmed balance is {}", balance.unconfirmed);
+        info!("immature coinbase balance is {}", balance.immature);
 
-        info!("wallet balance is {}", balance);
+        // labels of address book entries which are not tracked by wallet are skipped
+        let labelled_hashes: Vec<(String, Vec<AddressHash>)> = {
+            let wallet = self.wallet.read();
+            let tracked = wallet.tracked_address_hashes();
+            wallet.labels()
+                .into_iter()
+                .map(|label| {
+                    let hashes = wallet.addresses_by_label(&label)
+                        .into_iter()
+                        .map(|address| address.hash)
+                        .filter(|hash| tracked.contains(hash))
+                        .collect();
+                    (label, hashes)
+                })
+                .collect()
+        };
+        for (label, hashes) in labelled_hashes.into_iter().filter(|&(_, ref hashes)| !hashes.is_empty()) {
+            let balance = self.transaction_helper.balance_of(&hashes);
+            info!("label {}: balance {}, unconfirmed {}, immature {}", label, balance.confirmed, balance.unconfirmed, balance.immature);
+        }
     }
 
-    //TODO needs refactoring so it not just returns in case of error
-    fn send_cash(&self, recipient: Address, amount: u64) {
+    /// Names `address` with `label`, so it can be told apart in balance and history. Empty label removes the name
+    fn set_label(&self, address: Address, label: String) {
+        match self.wallet.write().set_label(&address, label.clone()) {
+            Ok(()) if label.is_empty() => info!("Label of {} is removed", address),
+            Ok(()) => info!("Address {} is labelled {}", address, label),
+            Err(err) => error!("Failed to set label: {:?}", err),
+        }
+    }
+
+    fn rescan(&self, from_height: u32) {
+        if !self.wallet.read().is_ready() { return; }
+
+        match self.transaction_helper.rescan(from_height) {
+            Ok((start_height, stop_height)) => info!("Rescanned blocks from {} to {}", start_height, stop_height),
+            Err(err) => error!("Failed to rescan blocks: {:?}", err),
+        }
+    }
+
+    fn lock_unspent(&self, unlock: bool, out_points: Vec<OutPoint>) {
+        let result = if !unlock {
+            self.transaction_helper.lock_unspent(out_points)
+        } else if out_points.is_empty() {
+            self.transaction_helper.unlock_all_unspent();
+            Ok(())
+        } else {
+            self.transaction_helper.unlock_unspent(&out_points)
+        };
+        if let Err(err) = result {
+            error!("Failed to change output locks: {:?}", err);
+        }
+    }
+
+    fn list_lock_unspent(&self) {
+        let locked = self.transaction_helper.list_locked_unspent();
+        if locked.is_empty() {
+            info!("there are no locked outputs");
+        }
+        for out_point in locked {
+            info!("{}:{}", out_point.hash, out_point.index);
+        }
+    }
+
+    fn show_history(&self) {
         if !self.wallet.read().is_ready() { return; }
 
-        let transaction = Transaction {
-            version: 0,
-            inputs: vec![],
-            outputs: vec![
-                TransactionOutput {
-                    value: amount,
-                    script_pubkey: Builder::build_p2wpkh(&recipient.hash).to_bytes(),
-                },
-            ],
-            lock_time: 0,
+        let history = self.transaction_helper.history();
+        if history.is_empty() {
+            info!("wallet has no transactions");
+        }
+        for entry in history {
+            let fee = entry.fee.map_or("unknown".to_owned(), |fee| fee.to_string());
+            let address = entry.address.map_or("unknown".to_owned(), |address| address.to_string());
+            let height = entry.height.map_or("mempool".to_owned(), |height| height.to_string());
+            let label = entry.label.map_or(String::new(), |label| format!(" label {}", label));
+            info!("{} {:?} amount {} fee {} address {}{} height {} confirmations {}",
+                entry.hash, entry.kind, entry.amount, fee, address, label, height, entry.confirmations);
+        }
+    }
+
+    /// Makes wallet able to co-sign spending of `required` of `publics` P2WSH multisig
+    fn create_multisig(&self, required: u8, publics: Vec<Public>) {
+        if required == 0 || required as usize > publics.len() || publics.len() > 16 {
+            error!("Multisig needs from 1 to {} signatures of up to 16 public keys", publics.len());
+            return;
+        }
+
+        let witness_script = Builder::build_multisig(required, &publics).to_bytes();
+        let address = SegwitAddress::p2wsh(Network::Mainnet, &sha256(&witness_script)); //TODO check for network correctness
+        if let Err(err) = self.wallet.write().add_witness_script(witness_script.clone()) {
+            error!("Failed to store multisig script: {:?}", err);
+            return;
+        }
+
+        let wallet_hashes = self.wallet.read().address_hashes();
+        let own_keys = publics.iter()
+            .filter(|public| wallet_hashes.contains(&public.address_hash()))
+            .count();
+        info!("Multisig address is {}", address);
+        info!("Redeem script is {}", witness_script.to_hex());
+        info!("Wallet holds {} of {} keys, {} signatures are required", own_keys, publics.len(), required);
+    }
+
+    /// Adds wallet signatures to partially signed transaction and broadcasts it once it's complete
+    fn cosign(&self, raw_transaction: Bytes) {
+        if !self.wallet.read().is_ready_to_sign() { return; }
+
+        let transaction: Transaction = match deserialize(Reader::new(&raw_transaction)) {
+            Ok(transaction) => transaction,
+            Err(err) => {
+                error!("Cannot deserialize transaction: {:?}", err);
+                return;
+            }
         };
 
-        //TODO pattern match returned results
-        let funded_transaction = match self.transaction_helper.fund_transaction(transaction) {
+        match self.transaction_helper.cosign_transaction(transaction) {
+            Ok((transaction, true)) => {
+                info!("Transaction {} is completely signed and is being broadcasted", transaction.hash());
+                self.publish(transaction);
+            }
+            Ok((transaction, false)) => {
+                info!("Transaction needs more signatures. Pass it to the next cosigner:\n{}",
+                    serialize_with_flags(&transaction, SERIALIZE_TRANSACTION_WITNESS).to_hex());
+            }
+            Err(err) => error!("Failed to sign transaction: {:?}", err),
+        }
+    }
+
+    fn create_psbt(&self, raw_transaction: Bytes) {
+        let transaction: Transaction = match deserialize(Reader::new(&raw_transaction)) {
             Ok(transaction) => transaction,
             Err(err) => {
-                error!("Error funding transaction: {:?}", err);
+                error!("Cannot deserialize transaction: {:?}", err);
+                return;
+            }
+        };
+
+        match Psbt::from_unsigned_transaction(transaction) {
+            Ok(psbt) => info!("PSBT is created:\n{}", psbt),
+            Err(err) => error!("Failed to create PSBT: {:?}", err),
+        }
+    }
+
+    fn update_psbt(&self, psbt: Psbt) {
+        let psbt = self.transaction_helper.update_psbt(psbt);
+        info!("PSBT is updated:\n{}", psbt);
+    }
+
+    fn sign_psbt(&self, psbt: Psbt) {
+        if !self.wallet.read().is_ready_to_sign() { return; }
+
+        match self.transaction_helper.sign_psbt(psbt) {
+            Ok((psbt, signed)) => info!("{} signatures are added:\n{}", signed, psbt),
+            Err(err) => error!("Failed to sign PSBT: {:?}", err),
+        }
+    }
+
+    fn combine_psbt(&self, psbts: Vec<Psbt>) {
+        let mut psbts = psbts.into_iter();
+        let mut combined = match psbts.next() {
+            Some(psbt) => psbt,
+            None => return,
+        };
+        for psbt in psbts {
+            if let Err(err) = combined.combine(psbt) {
+                error!("Failed to combine PSBT: {:?}", err);
+                return;
+            }
+        }
+        info!("PSBT is combined:\n{}", combined);
+    }
+
+    fn finalize_psbt(&self, mut psbt: Psbt) {
+        if finalize_psbt(&mut psbt) {
+            info!("PSBT is finalized. Use `psbtextract` to broadcast its transaction:\n{}", psbt);
+        } else {
+            info!("Some inputs need more signatures:\n{}", psbt);
+        }
+    }
+
+    /// Broadcasts transaction of finalized PSBT
+    fn extract_psbt(&self, psbt: Psbt) {
+        match psbt.extract_transaction() {
+            Ok(transaction) => {
+                info!("Transaction {} is extracted and is being broadcasted", transaction.hash());
+                self.publish(transaction);
+            }
+            Err(err) => error!("Failed to extract transaction: {:?}", err),
+        }
+    }
+
+    /// Proves ownership of wallet `address` by signing `message` with its key
+    fn sign_message(&self, address: Address, message: String) {
+        if !self.wallet.read().is_ready_to_sign() { return; }
+
+        let wallet = self.wallet.read();
+        let keypair = match wallet.find_keypair_with_public_hash(&address.hash) {
+            Some(keypair) => keypair,
+            None => {
+                error!("Wallet has no key of address {}", address);
+                return;
+            }
+        };
+        match sign_message(keypair.private(), &message) {
+            Ok(signature) => info!("Signature is {}", signature),
+            Err(err) => error!("Failed to sign message: {:?}", err),
+        }
+    }
+
+    fn verify_message(&self, address: Address, signature: String, message: String) {
+        match verify_message(&address, &signature, &message) {
+            Ok(true) => info!("Message is signed by key of {}", address),
+            Ok(false) => info!("Signature doesn't match address or message"),
+            Err(err) => error!("Failed to verify message: {:?}", err),
+        }
+    }
+
+    /// Pays `amount` to `recipient`. Transaction which time lock is not mature yet can't be mined, so it is kept
+    /// in wallet with its inputs locked and is broadcast by `rebroadcast` once the lock expires
+    fn send_cash(&self, recipient: Recipient, amount: u64, fee_rate: u64, selection: CoinSelection, time_lock: Option<TimeLock>) {
+        if !self.wallet.read().is_ready_to_sign() { return; }
+
+        let script_pubkey = recipient.script_pubkey().to_bytes();
+        match self.transaction_helper.create_payment(script_pubkey, amount, fee_rate, selection, time_lock) {
+            Ok(signed_transaction) if !self.transaction_helper.is_time_lock_mature(&signed_transaction) => {
+                let hash = signed_transaction.hash();
+                match self.wallet.write().add_pending_transaction(&signed_transaction) {
+                    Ok(()) => info!("Transaction {} is time-locked. It will be broadcast once lock expires", hash),
+                    Err(err) => {
+                        error!("Failed to store time-locked transaction {}: {:?}", hash, err);
+                        self.transaction_helper.release_inputs(&signed_transaction);
+                    }
+                }
+            }
+            Ok(signed_transaction) => self.publish(signed_transaction),
+            Err(err) => error!("Error creating transaction: {:?}", err),
+        }
+    }
+
+    fn send_data(&self, data: Bytes, fee_rate: u64) {
+        if !self.wallet.read().is_ready_to_sign() { return; }
+
+        match self.transaction_helper.create_data_transaction(&data, fee_rate, CoinSelection::default()) {
+            Ok(signed_transaction) => {
+                info!("Data is stored by transaction {}", signed_transaction.hash());
+                self.publish(signed_transaction);
+            }
+            Err(err) => error!("Error creating transaction: {:?}", err),
+        }
+    }
+
+    fn sweep_private_key(&self, private: Private, fee_rate: u64) {
+        if !self.wallet.read().is_ready_to_sign() { return; }
+
+        match self.transaction_helper.sweep_private_key(private, fee_rate) {
+            Ok(transaction) => {
+                info!("Coins of private key are swept by transaction {}", transaction.hash());
+                self.publish(transaction);
+            }
+            Err(err) => error!("Failed to sweep private key: {:?}", err),
+        }
+    }
+
+    /// Replaces memory pool transaction with one paying higher fee and relays it to peers
+    fn bump_fee(&self, hash: H256, fee_rate: Option<u64>) {
+        if !self.wallet.read().is_ready_to_sign() { return; }
+
+        let replacement = match self.transaction_helper.bump_fee(&hash, fee_rate) {
+            Ok(replacement) => replacement,
+            Err(err) => {
+                error!("Failed to bump fee: {:?}", err);
                 return;
             }
         };
-        let signed_transaction = self.transaction_helper.sign_transaction(funded_transaction).unwrap();
+        match self.acceptor.accept_transaction(replacement).wait() {
+            Ok(replacement) => {
+                info!("Transaction {} is replaced by {}", hash, replacement.hash());
+                self.transaction_helper.track_unconfirmed(replacement.clone());
+                self.wrapper.broadcast(&Tx::with_transaction(replacement));
+            }
+            Err(err) => error!("Replacement transaction is rejected: {:?}", err),
+        }
+    }
 
+    /// Puts signed transaction to memory pool and relays it to peers once it is accepted. Outputs reserved by
+    /// funding are released, since memory pool keeps them from being spent again
+    fn publish(&self, signed_transaction: Transaction) {
         let hash = signed_transaction.hash();
         if self.mempool.read().contains(&hash) {
             error!("Exact same transaction already exists in mempool");
+            self.transaction_helper.release_inputs(&signed_transaction);
             return;
         }
 
-        let tx = Tx {
-            transaction: signed_transaction.clone(),
-        };
-        self.wrapper.broadcast(&tx);
-
         debug!("transaction to insert: {:?}", signed_transaction);
 
-        let mut mempool = self.mempool.write();
-        mempool.insert_verified(signed_transaction.into());
+        // acceptor verifies transaction and lets listeners like `walletnotify` know about it
+        let accepted = self.acceptor.accept_transaction(signed_transaction.clone()).wait();
+        self.transaction_helper.release_inputs(&signed_transaction);
+        match accepted {
+            Ok(transaction) => {
+                self.transaction_helper.track_unconfirmed(transaction.clone());
+                self.wrapper.broadcast(&Tx::with_transaction(transaction));
+            }
+            Err(err) => error!("Transaction {} is not accepted to mempool: {:?}", hash, err),
+        }
+    }
+
+    /// Relays wallet transactions which are not mined yet to peers again, so they propagate even if there were
+    /// no peers when they were published. Transactions which left memory pool are put back to it first.
+    /// Time-locked payments are published once their lock expires
+    fn rebroadcast(&self) {
+        for loaded in self.wallets.all() {
+            let pending = loaded.wallet.read().pending_transactions();
+            for transaction in pending {
+                if !loaded.transaction_helper.is_time_lock_mature(&transaction) {
+                    continue;
+                }
+                let hash = transaction.hash();
+                // user could have broadcast it with `sendrawtransaction` already
+                let published = self.mempool.read().contains(&hash) ||
+                    self.storage.as_transaction_meta_provider().transaction_meta(&hash).is_some();
+                if !published {
+                    if let Err(err) = self.acceptor.accept_transaction(transaction.clone()).wait() {
+                        error!("Time-locked transaction {} is not accepted to mempool, will retry: {:?}", hash, err);
+                        continue;
+                    }
+                    info!("Time lock of transaction {} expired and it is broadcast", hash);
+                    self.wrapper.broadcast(&Tx::with_transaction(transaction.clone()));
+                }
+                loaded.transaction_helper.release_inputs(&transaction);
+                loaded.transaction_helper.track_unconfirmed(transaction);
+                if let Err(err) = loaded.wallet.write().remove_pending_transaction(&hash) {
+                    error!("Failed to forget broadcast transaction {}: {:?}", hash, err);
+                }
+            }
+
+            for transaction in loaded.transaction_helper.unconfirmed_transactions() {
+                let hash = transaction.hash();
+                if !self.mempool.read().contains(&hash) {
+                    if let Err(err) = self.acceptor.accept_transaction(transaction.clone()).wait() {
+                        debug!("Wallet transaction {} is not accepted to mempool again: {:?}", hash, err);
+                        continue;
+                    }
+                }
+                debug!("Rebroadcasting wallet transaction {}", hash);
+                self.wrapper.broadcast(&Tx::with_transaction(transaction));
+            }
+        }
     }
 }
 
 impl Service for WalletManager {
-    type Item = Task;
+    type Item = Request;
 
     fn run(&mut self) {
+        let rebroadcast_interval = Duration::from_secs(REBROADCAST_INTERVAL);
         loop {
-            if let Ok(task) = self.receiver.recv() {
-                match task {
-                    Task::CreateWallet() => self.create_wallet(),
-                    Task::LoadWallet(private) => self.load_from_key(private),
-                    Task::CalculateBalance() => self.calculate_balance(),
-                    Task::SendCash(to, amount) => self.send_cash(to, amount),
+            if self.last_rebroadcast.elapsed() >= rebroadcast_interval {
+                self.rebroadcast();
+                self.last_rebroadcast = Instant::now();
+            }
+
+            let timeout = rebroadcast_interval - self.last_rebroadcast.elapsed().min(rebroadcast_interval);
+            match self.receiver.recv_timeout(timeout) {
+                Ok((name, task)) => {
+                    let name = name.unwrap_or_else(|| DEFAULT_WALLET.to_owned());
+                    self.run_task(&name, task);
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => {
+                    debug!("wallet manager thread ended");
+                    break;
                 }
-            } else {
-                debug!("wallet manager thread ended");
-                break;
             }
         }
     }
@@ -0,0 +1,44 @@
+use chain::Transaction;
+use primitives::hash::H256;
+use std::process::Command;
+use std::thread;
+use sync::TransactionListener;
+use wallets::WalletsRef;
+
+/// Runs user command for every accepted transaction which touches addresses of loaded wallets, the way
+/// `walletnotify` of Bitcoin Core does. `%s` in command is replaced by transaction hash and `%b` by hash
+/// of block containing it or by `unconfirmed` for memory pool transaction
+pub struct WalletNotifier {
+    command: String,
+    wallets: WalletsRef,
+}
+
+impl WalletNotifier {
+    pub fn new(command: String, wallets: WalletsRef) -> Self {
+        WalletNotifier { command, wallets }
+    }
+}
+
+impl TransactionListener for WalletNotifier {
+    fn relevant(&self, transactions: &[&Transaction]) -> Vec<bool> {
+        let mut relevant = vec![false; transactions.len()];
+        for loaded in self.wallets.all() {
+            for (is_relevant, is_mine) in relevant.iter_mut().zip(loaded.transaction_helper.relevant(transactions)) {
+                *is_relevant |= is_mine;
+            }
+        }
+        relevant
+    }
+
+    fn on_transaction(&self, transaction: &Transaction, block_hash: Option<&H256>) {
+        let command = self.command
+            .replace("%s", &transaction.hash().to_string())
+            .replace("%b", &block_hash.map_or("unconfirmed".to_owned(), |hash| hash.to_string()));
+        // command may take long, so it doesn't hold up acceptance of blocks and transactions
+        thread::spawn(move || match Command::new("sh").arg("-c").arg(&command).status() {
+            Ok(status) if !status.success() => error!("Wallet notification command `{}` failed with {}", command, status),
+            Ok(_) => {}
+            Err(err) => error!("Can't run wallet notification command `{}`: {:?}", command, err),
+        });
+    }
+}
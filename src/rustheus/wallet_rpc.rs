@@ -0,0 +1,248 @@
+use futures::Future;
+use ethcore_rpc::v1::WalletClientCoreApi;
+use ethcore_rpc::v1::types::{WalletTransaction, WalletTransactionCategory, WalletBalance, UnspentOutput, RescanResponse};
+use chain::constants::SATOSHIS_IN_COIN;
+use chain::{OutPoint, Psbt, Transaction};
+use keys::{Address, Private, sign_message};
+use message::types::Tx;
+use primitives::bytes::Bytes;
+use primitives::hash::H256;
+use sync::{AcceptorRef, MessageWrapper};
+use coin_selection::CoinSelection;
+use transaction_helper::{TransactionHelperRef, HistoryEntry, HistoryKind, DEFAULT_FEE_RATE, output_address};
+use wallet::WalletRef;
+use wallet_manager::Recipient;
+use wallets::{WalletsRef, LoadedWallet};
+
+fn to_coins(satoshis: i64) -> f64 {
+	satoshis as f64 / SATOSHIS_IN_COIN as f64
+}
+
+fn to_wallet_transaction(entry: HistoryEntry) -> WalletTransaction {
+	WalletTransaction {
+		txid: entry.hash.into(),
+		category: match entry.kind {
+			HistoryKind::Sent => WalletTransactionCategory::Send,
+			HistoryKind::Received => WalletTransactionCategory::Receive,
+			HistoryKind::Generated => WalletTransactionCategory::Generate,
+		},
+		amount: to_coins(entry.amount),
+		fee: entry.fee.map(|fee| to_coins(fee as i64)),
+		address: entry.address.map(|address| address.to_string()),
+		label: entry.label,
+		blockheight: entry.height,
+		confirmations: entry.confirmations,
+	}
+}
+
+/// Serves wallet RPC methods using one of wallets loaded by this node, the default one unless other is selected
+pub struct WalletClientCore {
+	wallets: WalletsRef,
+	wallet: WalletRef,
+	transaction_helper: TransactionHelperRef,
+	acceptor: AcceptorRef,
+	message_wrapper: MessageWrapper,
+}
+
+impl WalletClientCore {
+	pub fn new(wallets: WalletsRef, acceptor: AcceptorRef, message_wrapper: MessageWrapper) -> Self {
+		let default = wallets.default_wallet();
+		WalletClientCore::with_loaded(wallets, default, acceptor, message_wrapper)
+	}
+
+	fn with_loaded(wallets: WalletsRef, loaded: LoadedWallet, acceptor: AcceptorRef, message_wrapper: MessageWrapper) -> Self {
+		WalletClientCore {
+			wallets,
+			wallet: loaded.wallet,
+			transaction_helper: loaded.transaction_helper,
+			acceptor,
+			message_wrapper,
+		}
+	}
+
+	/// Puts wallet transaction to memory pool and relays it to peers. Outputs reserved by funding are released
+	fn publish(&self, transaction: Transaction) -> Result<H256, String> {
+		let accepted = self.acceptor.accept_transaction(transaction.clone()).wait();
+		self.transaction_helper.release_inputs(&transaction);
+		let transaction = accepted.map_err(|err| format!("{:?}", err))?;
+		let hash = transaction.hash();
+		self.transaction_helper.track_unconfirmed(transaction.clone());
+		self.message_wrapper.broadcast(&Tx::with_transaction(transaction));
+		Ok(hash)
+	}
+}
+
+impl WalletClientCoreApi for WalletClientCore {
+	fn with_wallet(&self, name: &str) -> Result<Self, String> {
+		let loaded = self.wallets.get(name).map_err(|err| format!("{:?}", err))?;
+		Ok(WalletClientCore::with_loaded(self.wallets.clone(), loaded, self.acceptor.clone(), self.message_wrapper.clone()))
+	}
+
+	fn load_wallet(&self, name: String) -> Result<(), String> {
+		self.wallets.load(&name)
+			.map(|_| ())
+			.map_err(|err| format!("{:?}", err))
+	}
+
+	fn unload_wallet(&self, name: String) -> Result<(), String> {
+		self.wallets.unload(&name).map_err(|err| format!("{:?}", err))
+	}
+
+	fn list_wallets(&self) -> Result<Vec<String>, String> {
+		Ok(self.wallets.names())
+	}
+
+	fn new_address(&self) -> Result<Address, String> {
+		self.wallet.write().new_keypair().map_err(|err| format!("{:?}", err))
+	}
+
+	fn balance(&self) -> Result<WalletBalance, String> {
+		let balance = self.transaction_helper.balance();
+		Ok(WalletBalance {
+			confirmed: to_coins(balance.confirmed as i64),
+			unconfirmed: to_coins(balance.unconfirmed as i64),
+			immature: to_coins(balance.immature as i64),
+		})
+	}
+
+	fn send_to_address(&self, address: String, amount: u64, fee_rate: Option<u64>) -> Result<H256, String> {
+		let recipient: Recipient = address.parse().map_err(|err| format!("{:?}", err))?;
+		if !self.wallet.read().is_ready_to_sign() {
+			return Err("Wallet is not ready to sign".to_owned());
+		}
+
+		let transaction = self.transaction_helper
+			.create_payment(recipient.script_pubkey().to_bytes(), amount, fee_rate.unwrap_or(DEFAULT_FEE_RATE), CoinSelection::default(), None)
+			.map_err(|err| format!("{:?}", err))?;
+		self.publish(transaction)
+	}
+
+	fn send_data(&self, data: Bytes, fee_rate: Option<u64>) -> Result<H256, String> {
+		if !self.wallet.read().is_ready_to_sign() {
+			return Err("Wallet is not ready to sign".to_owned());
+		}
+
+		let transaction = self.transaction_helper
+			.create_data_transaction(&data, fee_rate.unwrap_or(DEFAULT_FEE_RATE), CoinSelection::default())
+			.map_err(|err| format!("{:?}", err))?;
+		self.publish(transaction)
+	}
+
+	fn list_unspent(&self) -> Result<Vec<UnspentOutput>, String> {
+		Ok(self.transaction_helper
+			.list_unspent()
+			.into_iter()
+			.map(|unspent| UnspentOutput {
+				txid: unspent.out_point.hash.into(),
+				vout: unspent.out_point.index,
+				address: output_address(&unspent.output).map(|address| address.to_string()),
+				amount: to_coins(unspent.output.value as i64),
+				script_pubkey: unspent.output.script_pubkey.into(),
+				confirmations: unspent.confirmations,
+				spendable: unspent.spendable,
+			})
+			.collect())
+	}
+
+	fn dump_private_key(&self, address: Address) -> Result<Private, String> {
+		let wallet = self.wallet.read();
+		if wallet.is_locked() {
+			return Err("Wallet is locked".to_owned());
+		}
+		match wallet.find_keypair_with_public_hash(&address.hash) {
+			Some(keypair) => Ok(Private {
+				network: keypair.private().network,
+				secret: keypair.private().secret.clone(),
+				compressed: keypair.private().compressed,
+			}),
+			None => Err(format!("Wallet has no key of address {}", address)),
+		}
+	}
+
+	fn import_private_key(&self, private: Private) -> Result<(), String> {
+		self.wallet.write()
+			.add_keypair_from_private(private)
+			.map(|_| ())
+			.map_err(|err| format!("{:?}", err))
+	}
+
+	fn transaction(&self, hash: H256) -> Result<WalletTransaction, String> {
+		self.transaction_helper
+			.history_entry_by_hash(&hash)
+			.map(to_wallet_transaction)
+			.ok_or_else(|| format!("Transaction {} doesn't belong to wallet", hash))
+	}
+
+	fn rescan(&self, from_height: u32) -> Result<RescanResponse, String> {
+		if self.wallet.read().tracked_address_hashes().is_empty() {
+			return Err("Wallet has no keys or watch-only addresses".to_owned());
+		}
+		self.transaction_helper.rescan(from_height)
+			.map(|(start_height, stop_height)| RescanResponse {
+				start_height,
+				stop_height,
+			})
+			.map_err(|err| format!("{:?}", err))
+	}
+
+	fn lock_unspent(&self, unlock: bool, out_points: Vec<OutPoint>) -> Result<(), String> {
+		let result = if !unlock {
+			self.transaction_helper.lock_unspent(out_points)
+		} else if out_points.is_empty() {
+			self.transaction_helper.unlock_all_unspent();
+			Ok(())
+		} else {
+			self.transaction_helper.unlock_unspent(&out_points)
+		};
+		result.map_err(|err| format!("{:?}", err))
+	}
+
+	fn list_locked_unspent(&self) -> Result<Vec<OutPoint>, String> {
+		Ok(self.transaction_helper.list_locked_unspent())
+	}
+
+	fn list_transactions(&self) -> Result<Vec<WalletTransaction>, String> {
+		Ok(self.transaction_helper
+			.history()
+			.into_iter()
+			.map(to_wallet_transaction)
+			.collect())
+	}
+
+	fn import_address(&self, address: Address) -> Result<(), String> {
+		self.wallet.write().import_address(address).map_err(|err| format!("{:?}", err))
+	}
+
+	fn set_label(&self, address: Address, label: String) -> Result<(), String> {
+		self.wallet.write().set_label(&address, label).map_err(|err| format!("{:?}", err))
+	}
+
+	fn addresses_by_label(&self, label: String) -> Result<Vec<Address>, String> {
+		let addresses = self.wallet.read().addresses_by_label(&label);
+		if addresses.is_empty() {
+			return Err(format!("No addresses with label {}", label));
+		}
+		Ok(addresses)
+	}
+
+	fn update_psbt(&self, psbt: Psbt) -> Result<Psbt, String> {
+		Ok(self.transaction_helper.update_psbt(psbt))
+	}
+
+	fn sign_psbt(&self, psbt: Psbt) -> Result<Psbt, String> {
+		self.transaction_helper.sign_psbt(psbt)
+			.map(|(psbt, _)| psbt)
+			.map_err(|err| format!("{:?}", err))
+	}
+
+	fn sign_message(&self, address: Address, message: String) -> Result<String, String> {
+		let wallet = self.wallet.read();
+		if wallet.is_locked() {
+			return Err("Wallet is locked".to_owned());
+		}
+		match wallet.find_keypair_with_public_hash(&address.hash) {
+			Some(keypair) => sign_message(keypair.private(), &message).map_err(|err| format!("{:?}", err)),
+			None => Err(format!("Wallet has no key of address {}", address)),
+		}
+	}
+}
@@ -0,0 +1,135 @@
+use db::SharedStore;
+use memory_pool::{MemoryPoolRef, UtxoAndOutputProvider};
+use parking_lot::RwLock;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use transaction_helper::{TransactionHelper, TransactionHelperRef};
+use wallet::{Wallet, WalletRef, WalletError};
+
+pub type WalletsRef = Arc<Wallets>;
+
+/// Name of wallet which is loaded on start and stays loaded while node runs
+pub const DEFAULT_WALLET: &'static str = "default";
+
+#[derive(Debug)]
+pub enum WalletsError {
+    /// Name is empty or has characters other than ASCII letters, digits, `-` and `_`
+    InvalidName,
+    AlreadyLoaded,
+    NotLoaded,
+    /// Default wallet can't be unloaded
+    DefaultWallet,
+    WalletError(WalletError),
+}
+
+impl From<WalletError> for WalletsError {
+    fn from(err: WalletError) -> WalletsError {
+        WalletsError::WalletError(err)
+    }
+}
+
+/// Wallet together with helper which funds and signs its transactions
+#[derive(Clone)]
+pub struct LoadedWallet {
+    pub wallet: WalletRef,
+    pub transaction_helper: TransactionHelperRef,
+}
+
+/// Wallets loaded by name. Default wallet is created by node, the others are stored in `directory` as `<name>.dat`
+pub struct Wallets {
+    directory: PathBuf,
+    storage: SharedStore,
+    mempool: MemoryPoolRef,
+    default: LoadedWallet,
+    /// Wallets loaded in addition to default one in load order
+    loaded: RwLock<Vec<(String, LoadedWallet)>>,
+}
+
+impl Wallets {
+    pub fn new(directory: PathBuf, storage: SharedStore, mempool: MemoryPoolRef, default: LoadedWallet) -> Self {
+        Wallets {
+            directory,
+            storage,
+            mempool,
+            default,
+            loaded: RwLock::new(Vec::new()),
+        }
+    }
+
+    pub fn default_wallet(&self) -> LoadedWallet {
+        self.default.clone()
+    }
+
+    /// Opens wallet `name` or creates an empty one if there is no such wallet file yet
+    pub fn load(&self, name: &str) -> Result<LoadedWallet, WalletsError> {
+        if !is_valid_name(name) {
+            return Err(WalletsError::InvalidName);
+        }
+        let mut loaded = self.loaded.write();
+        if name == DEFAULT_WALLET || loaded.iter().any(|&(ref loaded_name, _)| loaded_name == name) {
+            return Err(WalletsError::AlreadyLoaded);
+        }
+
+        fs::create_dir_all(&self.directory).map_err(WalletError::from)?;
+        let wallet = Wallet::open(self.directory.join(format!("{}.dat", name)))?;
+        let wallet = Arc::new(RwLock::new(wallet));
+        let utxo_provider = UtxoAndOutputProvider::new(self.storage.clone(), self.mempool.clone());
+        let transaction_helper = Arc::new(TransactionHelper::new(
+            utxo_provider,
+            self.storage.clone(),
+            self.mempool.clone(),
+            wallet.clone(),
+        ));
+        let wallet = LoadedWallet { wallet, transaction_helper };
+        loaded.push((name.to_owned(), wallet.clone()));
+        Ok(wallet)
+    }
+
+    /// Stops serving wallet `name`. Its file stays on disk, so it may be loaded again
+    pub fn unload(&self, name: &str) -> Result<(), WalletsError> {
+        if name == DEFAULT_WALLET {
+            return Err(WalletsError::DefaultWallet);
+        }
+        let mut loaded = self.loaded.write();
+        let position = loaded.iter()
+            .position(|&(ref loaded_name, _)| loaded_name == name)
+            .ok_or(WalletsError::NotLoaded)?;
+        loaded.remove(position);
+        Ok(())
+    }
+
+    /// Finds loaded wallet by name
+    pub fn get(&self, name: &str) -> Result<LoadedWallet, WalletsError> {
+        if name == DEFAULT_WALLET {
+            return Ok(self.default_wallet());
+        }
+        self.loaded.read().iter()
+            .find(|&&(ref loaded_name, _)| loaded_name == name)
+            .map(|&(_, ref wallet)| wallet.clone())
+            .ok_or(WalletsError::NotLoaded)
+    }
+
+    /// Every loaded wallet, the default one first
+    pub fn all(&self) -> Vec<LoadedWallet> {
+        let loaded = self.loaded.read();
+        let mut wallets = Vec::with_capacity(loaded.len() + 1);
+        wallets.push(self.default_wallet());
+        wallets.extend(loaded.iter().map(|&(_, ref wallet)| wallet.clone()));
+        wallets
+    }
+
+    /// Names of loaded wallets, the default one first
+    pub fn names(&self) -> Vec<String> {
+        let loaded = self.loaded.read();
+        let mut names = Vec::with_capacity(loaded.len() + 1);
+        names.push(DEFAULT_WALLET.to_owned());
+        names.extend(loaded.iter().map(|&(ref name, _)| name.clone()));
+        names
+    }
+}
+
+/// Wallet name becomes part of file name, so it is limited to characters safe in paths
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
@@ -2,7 +2,7 @@
 
 use bytes::Bytes;
 use {Opcode, Script, Num};
-use keys::AddressHash;
+use keys::{AddressHash, Public};
 use ser::hash::H256;
 
 /// Script builder
@@ -48,11 +48,25 @@ impl Builder {
 			.into_script()
 	}
 
+	/// Builds bare multisig script requiring `required` signatures of given public keys.
+	/// Panics unless 1 <= required <= publics.len() <= 16
+	pub fn build_multisig(required: u8, publics: &[Public]) -> Script {
+		assert!(required >= 1 && required as usize <= publics.len() && publics.len() <= 16);
+		let small_number = |number: u8| Opcode::from_u8(Opcode::OP_1 as u8 + number - 1)
+			.expect("number is within [OP_1; OP_16] interval; qed");
+
+		publics.iter()
+			.fold(Builder::default().push_opcode(small_number(required)), |builder, public| builder.push_bytes(&**public))
+			.push_opcode(small_number(publics.len() as u8))
+			.push_opcode(Opcode::OP_CHECKMULTISIG)
+			.into_script()
+	}
+
 	/// Builds op_return script
 	pub fn build_nulldata(bytes: &[u8]) -> Script {
 		Builder::default()
 			.push_opcode(Opcode::OP_RETURN)
-			.push_bytes(bytes)
+			.push_data(bytes)
 			.into_script()
 	}
 
@@ -11,6 +11,7 @@ mod flags;
 mod interpreter;
 mod num;
 mod opcode;
+mod psbt;
 mod script;
 mod sign;
 mod stack;
@@ -23,6 +24,7 @@ pub use self::error::Error;
 pub use self::flags::VerificationFlags;
 pub use self::interpreter::{eval_script, verify_script};
 pub use self::opcode::Opcode;
+pub use self::psbt::{sign_psbt_input, finalize_psbt};
 pub use self::num::Num;
 pub use self::script::{Script, ScriptType, ScriptAddress, ScriptWitness, is_witness_commitment_script};
 pub use self::sign::{TransactionInputSigner, UnsignedTransactionInput, SignatureVersion, SighashBase};
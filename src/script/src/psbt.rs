@@ -0,0 +1,227 @@
+//! Signing and finalizing of partially signed transactions
+//!
+//! Inputs spending P2WPKH outputs and P2WSH multisig outputs with known witness script are supported.
+
+use bytes::Bytes;
+use chain::{Psbt, PsbtInput, TransactionOutput};
+use crypto::sha256;
+use keys::{AddressHash, KeyPair, Public};
+use builder::Builder;
+use script::Script;
+use sign::{TransactionInputSigner, SignatureVersion, SighashBase};
+
+/// Witness script of input if it's the one `script_pubkey` pays to
+fn matching_witness_script(input: &PsbtInput, script_pubkey: &Script) -> Option<Script> {
+	match input.witness_script {
+		Some(ref witness_script) if script_pubkey.is_pay_to_witness_script_hash() &&
+			sha256(witness_script)[..] == script_pubkey[2..34] => Some(witness_script.clone().into()),
+		_ => None,
+	}
+}
+
+/// Script which signatures of input commit to, together with hashes of public keys which may sign it
+fn signing_data(input: &PsbtInput, prevout: &TransactionOutput) -> Option<(Script, Vec<AddressHash>)> {
+	let script_pubkey: Script = prevout.script_pubkey.clone().into();
+	if script_pubkey.is_pay_to_witness_key_hash() {
+		let hash: AddressHash = script_pubkey[2..22].into();
+		return Some((Builder::build_p2pkh(&hash), vec![hash]));
+	}
+
+	let witness_script = match matching_witness_script(input, &script_pubkey) {
+		Some(witness_script) => witness_script,
+		None => return None,
+	};
+	let hashes = match witness_script.multisig_public_keys() {
+		Some((_, publics)) => publics.iter().map(Public::address_hash).collect(),
+		None => return None,
+	};
+	Some((witness_script, hashes))
+}
+
+/// Adds signature of `keypair` to input at `input_index` if the key can sign it and
+/// hasn't signed yet. Uses sighash type requested by input. Returns true if signature is added
+pub fn sign_psbt_input(psbt: &mut Psbt, input_index: usize, keypair: &KeyPair) -> bool {
+	if psbt.inputs[input_index].is_finalized() {
+		return false;
+	}
+	let prevout = match psbt.prevout(input_index) {
+		Some(prevout) => prevout,
+		None => return false,
+	};
+	let (script_code, hashes) = match signing_data(&psbt.inputs[input_index], &prevout) {
+		Some(data) => data,
+		None => return false,
+	};
+	if !hashes.contains(&keypair.public().address_hash()) ||
+		psbt.inputs[input_index].partial_signature(&keypair.public()[..]).is_some() {
+		return false;
+	}
+
+	let sighash = psbt.inputs[input_index].sighash_type.unwrap_or(SighashBase::All.into());
+	let signer: TransactionInputSigner = psbt.unsigned_transaction.clone().into();
+	let (signature, public) = signer.compute_signature_for_input(
+		keypair, input_index, prevout.value, &script_code, SignatureVersion::WitnessV0, sighash);
+	psbt.inputs[input_index].add_partial_signature(public, signature);
+	true
+}
+
+/// Witness spending `prevout` built from partial signatures. None if there are not enough of them
+fn final_witness(input: &PsbtInput, prevout: &TransactionOutput) -> Option<Vec<Bytes>> {
+	let script_pubkey: Script = prevout.script_pubkey.clone().into();
+	if script_pubkey.is_pay_to_witness_key_hash() {
+		return input.partial_signatures.iter()
+			.find(|&&(ref public, _)| Public::from_slice(public)
+				.map(|public| public.address_hash()[..] == script_pubkey[2..22])
+				.unwrap_or(false))
+			.map(|&(ref public, ref signature)| vec![signature.clone(), public.clone()]);
+	}
+
+	let witness_script = match matching_witness_script(input, &script_pubkey) {
+		Some(witness_script) => witness_script,
+		None => return None,
+	};
+	let (required, publics) = match witness_script.multisig_public_keys() {
+		Some(multisig) => multisig,
+		None => return None,
+	};
+	// CHECKMULTISIG expects signatures in order of their public keys
+	let signatures: Vec<Bytes> = publics.iter()
+		.filter_map(|public| input.partial_signature(&public[..]).cloned())
+		.take(required)
+		.collect();
+	if signatures.len() < required {
+		return None;
+	}
+
+	// dummy element consumed by CHECKMULTISIG
+	let mut witness = vec![Bytes::new()];
+	witness.extend(signatures);
+	witness.push(witness_script.to_bytes());
+	Some(witness)
+}
+
+/// Builds final witnesses of inputs which have enough signatures. Returns true if every input is finalized
+pub fn finalize_psbt(psbt: &mut Psbt) -> bool {
+	for index in 0..psbt.inputs.len() {
+		if psbt.inputs[index].is_finalized() {
+			continue;
+		}
+		let witness = match psbt.prevout(index) {
+			Some(prevout) => final_witness(&psbt.inputs[index], &prevout),
+			None => None,
+		};
+		if let Some(witness) = witness {
+			psbt.inputs[index].finalize(None, Some(witness));
+		}
+	}
+	psbt.is_finalized()
+}
+
+#[cfg(test)]
+mod tests {
+	use bytes::Bytes;
+	use chain::{Psbt, Transaction, TransactionInput, TransactionOutput, OutPoint};
+	use crypto::sha256;
+	use keys::{KeyPair, Private, Public};
+	use builder::Builder;
+	use flags::VerificationFlags;
+	use interpreter::verify_script;
+	use script::Script;
+	use sign::SignatureVersion;
+	use verify::TransactionSignatureChecker;
+	use super::{sign_psbt_input, finalize_psbt};
+
+	fn keypair(wif: &'static str) -> KeyPair {
+		let private: Private = wif.into();
+		KeyPair::from_private(private).unwrap()
+	}
+
+	fn psbt_spending(prevout: TransactionOutput) -> Psbt {
+		let transaction = Transaction {
+			version: 2,
+			inputs: vec![TransactionInput {
+				previous_output: OutPoint {
+					hash: "4c2ff2c8ab2a3c2cf6fc1c6a2e2d0a4b0c1ad8e5b6f4c0f5a9e8a3a2b1c0d9e8".into(),
+					index: 0,
+				},
+				script_sig: Bytes::new(),
+				sequence: 0xffffffff,
+				script_witness: vec![],
+			}],
+			outputs: vec![TransactionOutput {
+				value: 90000,
+				script_pubkey: "0014751e76e8199196d454941c45d1b3a323f1433bd6".into(),
+			}],
+			lock_time: 0,
+		};
+		let mut psbt = Psbt::from_unsigned_transaction(transaction).unwrap();
+		psbt.inputs[0].witness_utxo = Some(prevout);
+		psbt
+	}
+
+	fn verify(transaction: &Transaction, prevout: &TransactionOutput) {
+		let checker = TransactionSignatureChecker {
+			signer: transaction.clone().into(),
+			input_index: 0,
+			input_amount: prevout.value,
+		};
+		let flags = VerificationFlags::default()
+			.verify_p2sh(true)
+			.verify_witness(true);
+		let script_pubkey: Script = prevout.script_pubkey.clone().into();
+		assert_eq!(verify_script(&transaction.inputs[0].script_sig.clone().into(), &script_pubkey,
+			&transaction.inputs[0].script_witness, &flags, &checker, SignatureVersion::Base), Ok(()));
+	}
+
+	#[test]
+	fn test_sign_and_finalize_p2wpkh() {
+		let signer = keypair("KwDiBf89QgGbjEhKnhXJuH7LrciVrZi3qYjgd9M7rFU73sVHnoWn");
+		let stranger = keypair("KwDiBf89QgGbjEhKnhXJuH7LrciVrZi3qYjgd9M7rFU74NMTptX4");
+		let prevout = TransactionOutput {
+			value: 100000,
+			script_pubkey: Builder::build_p2wpkh(&signer.public().address_hash()).to_bytes(),
+		};
+		let mut psbt = psbt_spending(prevout.clone());
+
+		assert!(!sign_psbt_input(&mut psbt, 0, &stranger));
+		assert!(!finalize_psbt(&mut psbt));
+		assert!(sign_psbt_input(&mut psbt, 0, &signer));
+		assert!(!sign_psbt_input(&mut psbt, 0, &signer));
+		assert!(finalize_psbt(&mut psbt));
+		assert!(psbt.inputs[0].partial_signatures.is_empty());
+
+		verify(&psbt.extract_transaction().unwrap(), &prevout);
+	}
+
+	#[test]
+	fn test_sign_and_finalize_p2wsh_multisig() {
+		let first = keypair("KwDiBf89QgGbjEhKnhXJuH7LrciVrZi3qYjgd9M7rFU73sVHnoWn");
+		let second = keypair("KwDiBf89QgGbjEhKnhXJuH7LrciVrZi3qYjgd9M7rFU74NMTptX4");
+		let publics = vec![
+			Public::from_slice(&first.public()[..]).unwrap(),
+			Public::from_slice(&second.public()[..]).unwrap(),
+		];
+		let witness_script = Builder::build_multisig(2, &publics).to_bytes();
+		let prevout = TransactionOutput {
+			value: 100000,
+			script_pubkey: Builder::build_p2wsh(&sha256(&witness_script)).to_bytes(),
+		};
+		let mut psbt = psbt_spending(prevout.clone());
+
+		// witness script is required to sign
+		assert!(!sign_psbt_input(&mut psbt, 0, &first));
+		psbt.inputs[0].witness_script = Some(witness_script);
+
+		// signatures are made independently and combined later
+		let mut other = psbt.clone();
+		assert!(sign_psbt_input(&mut other, 0, &second));
+		assert!(sign_psbt_input(&mut psbt, 0, &first));
+		assert!(!finalize_psbt(&mut psbt));
+
+		psbt.combine(other).unwrap();
+		assert!(finalize_psbt(&mut psbt));
+		assert_eq!(psbt.inputs[0].final_script_witness.as_ref().map(Vec::len), Some(4));
+
+		verify(&psbt.extract_transaction().unwrap(), &prevout);
+	}
+}
@@ -407,6 +407,26 @@ impl Script {
 		return 1;
 	}
 
+	/// Number of required signatures and public keys of multisig script
+	pub fn multisig_public_keys(&self) -> Option<(usize, Vec<Public>)> {
+		if !self.is_multisig_script() {
+			return None;
+		}
+
+		let mut publics = vec![];
+		let mut pc = 1;
+		while pc < self.len() - 2 {
+			let instruction = self.get_instruction(pc).expect("this method depends on previous check in is_multisig_script()");
+			let data = instruction.data.expect("this method depends on previous check in is_multisig_script()");
+			match Public::from_slice(data) {
+				Ok(public) => publics.push(public),
+				Err(_) => return None,
+			}
+			pc += instruction.step;
+		}
+		Some((self.num_signatures_required() as usize, publics))
+	}
+
 	pub fn extract_destinations(&self) -> Result<Vec<ScriptAddress>, keys::Error> {
 		match self.script_type() {
 			ScriptType::NonStandard => {
@@ -447,7 +467,8 @@ impl Script {
 				Ok(vec![])
 			},
 			ScriptType::WitnessScript => {
-				unimplemented!();
+				// 32 bytes script hash can't be represented as address hash
+				Ok(vec![])
 			},
 		}
 	}
@@ -800,4 +821,15 @@ OP_ADD
 		assert_eq!(script.script_type(), ScriptType::ScriptHash);
 		assert_eq!(script.num_signatures_required(), 1);
 	}
+
+	#[test]
+	fn test_build_multisig() {
+		let publics = || vec![Public::from_slice(&[2; 33]).unwrap(), Public::from_slice(&[3; 33]).unwrap()];
+		let script = Builder::build_multisig(1, &publics());
+		assert_eq!(script.script_type(), ScriptType::Multisig);
+		assert_eq!(script.multisig_public_keys(), Some((1, publics())));
+
+		let script = Builder::build_p2pkh(&"0000000000000000000000000000000000000000".into());
+		assert_eq!(script.multisig_public_keys(), None);
+	}
 }
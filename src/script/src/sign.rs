@@ -149,8 +149,32 @@ impl TransactionInputSigner {
 		sighash: u32,
 	) -> TransactionInput {
 		match sigversion {
+			SignatureVersion::Base => self.signed_input_base(keypair, input_index, script_pubkey, sighash),
 			SignatureVersion::WitnessV0 => self.signed_input_witness(keypair, input_index, input_amount, script_pubkey, sigversion, sighash),
-			_ => panic!("Non witness signature should not be used"),
+			_ => panic!("Fork id signature should not be used"),
+		}
+	}
+
+	/// Input spending P2PKH output, which has signature and public key in script_sig
+	fn signed_input_base(
+		&self,
+		keypair: &KeyPair,
+		input_index: usize,
+		script_pubkey: &Script,
+		sighash: u32,
+	) -> TransactionInput {
+		let (signature, pubkey) = self.compute_signature_for_input(keypair, input_index, 0, script_pubkey, SignatureVersion::Base, sighash);
+		let script_sig = Builder::default()
+			.push_data(&signature)
+			.push_data(&pubkey)
+			.into_bytes();
+
+		let unsigned_input = &self.inputs[input_index];
+		TransactionInput {
+			previous_output: unsigned_input.previous_output.clone(),
+			sequence: unsigned_input.sequence,
+			script_sig,
+			script_witness: vec![],
 		}
 	}
 
@@ -346,8 +370,12 @@ mod tests {
 	use bytes::Bytes;
 	use hash::H256;
 	use keys::{KeyPair, Private, Address};
-	use chain::{OutPoint, TransactionOutput, Transaction};
+	use chain::{OutPoint, TransactionInput, TransactionOutput, Transaction};
 	use script::Script;
+	use builder::Builder;
+	use flags::VerificationFlags;
+	use interpreter::verify_script;
+	use verify::TransactionSignatureChecker;
 	use super::{Sighash, UnsignedTransactionInput, TransactionInputSigner, SighashBase, SignatureVersion};
 
 	// http://www.righto.com/2014/02/bitcoins-hard-way-using-raw-bitcoin.html
@@ -394,6 +422,47 @@ mod tests {
 		assert_eq!(hash, expected_signature_hash);
 	}
 
+	#[test]
+	fn test_signed_input_p2pkh() {
+		let private: Private = "KwDiBf89QgGbjEhKnhXJuH7LrciVrZi3qYjgd9M7rFU73sVHnoWn".into();
+		let keypair = KeyPair::from_private(private).unwrap();
+		let script_pubkey = Builder::build_p2pkh(&keypair.public().address_hash());
+		let prevout_value = 100000;
+
+		let transaction = Transaction {
+			version: 1,
+			inputs: vec![TransactionInput {
+				previous_output: OutPoint {
+					hash: "4c2ff2c8ab2a3c2cf6fc1c6a2e2d0a4b0c1ad8e5b6f4c0f5a9e8a3a2b1c0d9e8".into(),
+					index: 0,
+				},
+				script_sig: Bytes::new(),
+				sequence: 0xffffffff,
+				script_witness: vec![],
+			}],
+			outputs: vec![TransactionOutput {
+				value: 90000,
+				script_pubkey: Builder::build_p2wpkh(&keypair.public().address_hash()).to_bytes(),
+			}],
+			lock_time: 0,
+		};
+		let signer: TransactionInputSigner = transaction.clone().into();
+		let signed_input = signer.signed_input(&keypair, 0, prevout_value, &script_pubkey, SignatureVersion::Base, SighashBase::All.into());
+		assert!(signed_input.script_witness.is_empty());
+
+		let signed_transaction = Transaction {
+			inputs: vec![signed_input],
+			..transaction
+		};
+		let checker = TransactionSignatureChecker {
+			signer: signed_transaction.clone().into(),
+			input_index: 0,
+			input_amount: prevout_value,
+		};
+		assert_eq!(verify_script(&signed_transaction.inputs[0].script_sig.clone().into(), &script_pubkey,
+			&signed_transaction.inputs[0].script_witness, &VerificationFlags::default(), &checker, SignatureVersion::Base), Ok(()));
+	}
+
 	fn run_test_sighash(
 		tx: &'static str,
 		script: &'static str,
@@ -13,9 +13,19 @@ use verification::{VerificationLevel, Verify};
 use futures::done;
 use futures::prelude::*;
 use futures_cpupool::CpuPool;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 
 pub type AcceptorRef = Arc<Acceptor>;
+pub type TransactionListenerRef = Arc<TransactionListener>;
+
+/// Receives transactions accepted to memory pool or canonical chain which it is interested in
+pub trait TransactionListener: Send + Sync {
+    /// Tells for each of transactions accepted together whether listener is interested in it, eg: whether
+    /// it touches wallet addresses. Called once per block, so filter data may be prepared once for all of them
+    fn relevant(&self, transactions: &[&Transaction]) -> Vec<bool>;
+    /// Called for relevant transaction accepted to memory pool or, if `block_hash` is given, to canonized block
+    fn on_transaction(&self, transaction: &Transaction, block_hash: Option<&H256>);
+}
 
 pub struct Acceptor {
     //message_wrapper: MessageWrapper,
@@ -24,6 +34,7 @@ pub struct Acceptor {
     cpupool: CpuPool,
 
     verifier: ChainVerifier,
+    listeners: RwLock<Vec<TransactionListenerRef>>,
 }
 
 impl Acceptor {
@@ -44,6 +55,21 @@ impl Acceptor {
             store,
             verifier,
             cpupool,
+            listeners: RwLock::new(Vec::new()),
+        }
+    }
+
+    /// Makes `listener` receive relevant transactions accepted from now on
+    pub fn subscribe(&self, listener: TransactionListenerRef) {
+        self.listeners.write().expect("listeners lock is not poisoned").push(listener);
+    }
+
+    fn notify(&self, transactions: &[&Transaction], block_hash: Option<&H256>) {
+        for listener in self.listeners.read().expect("listeners lock is not poisoned").iter() {
+            let relevant = listener.relevant(transactions);
+            for (transaction, _) in transactions.iter().zip(relevant).filter(|&(_, relevant)| relevant) {
+                listener.on_transaction(transaction, block_hash);
+            }
         }
     }
 
@@ -95,10 +121,15 @@ impl Acceptor {
         match self.add_and_canonize_block(block) {
             Ok(_) => {
                 info!("Block inserted and canonized with hash {}", hash);
-                let mut mempool = self.mempool.write();
-                for transaction in transactions {
-                    mempool.remove_by_hash(&transaction.hash);
+                {
+                    let mut mempool = self.mempool.write();
+                    for transaction in &transactions {
+                        mempool.remove_by_hash(&transaction.hash);
+                    }
                 }
+                // listeners may look up memory pool, so they are notified after it is unlocked
+                let transactions: Vec<&Transaction> = transactions.iter().map(|transaction| &transaction.raw).collect();
+                self.notify(&transactions, Some(&hash));
                 return Ok(hash);
             }
             Err(err) => {
@@ -149,14 +180,16 @@ impl Acceptor {
                 // we have verified transaction, but possibly this transaction replaces
                 // existing transaction from memory pool
                 // => remove previous transactions before
-                let mut memory_pool = self.mempool.write();
-                for input in &transaction.inputs {
-                    memory_pool.remove_by_prevout(&input.previous_output);
+                {
+                    let mut memory_pool = self.mempool.write();
+                    for input in &transaction.inputs {
+                        memory_pool.remove_by_prevout(&input.previous_output);
+                    }
+                    // now insert transaction itself
+                    memory_pool.insert_verified(transaction.clone().into());
                 }
-                let transaction_clone = transaction.clone();
-                // now insert transaction itself
-                memory_pool.insert_verified(transaction.into());
-                return Ok(transaction_clone);
+                self.notify(&[&transaction], None);
+                return Ok(transaction);
             }
             Err(e) => {
                 error!(
@@ -23,4 +23,4 @@ mod responder;
 pub use message_handler::MessageHandler;
 pub use message_wrapper::MessageWrapper;
 pub use responder::Responder;
-pub use acceptor::{Acceptor, AcceptorRef};
\ No newline at end of file
+pub use acceptor::{Acceptor, AcceptorRef, TransactionListener, TransactionListenerRef};
\ No newline at end of file
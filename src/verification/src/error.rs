@@ -108,5 +108,11 @@ pub enum TransactionError {
 	ReturnReplayProtection,
 	/// Transaction with witness is received before SegWit is activated.
 	PrematureWitness,
+	/// Null data output script exceeds standard size
+	NullDataSize(usize),
+	/// Transaction has more null data outputs than it's standard
+	MultipleNullData,
+	/// Replacement of memory pool transaction pays less fee or fee rate than replaced transactions, bip125.
+	InsufficientReplacementFee,
 }
 
@@ -66,6 +66,7 @@ extern crate script;
 extern crate bitcrypto as crypto;
 
 pub mod constants;
+pub mod policy;
 mod canon;
 mod duplex_store;
 mod error;
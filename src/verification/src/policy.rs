@@ -0,0 +1,8 @@
+//! Standardness policy constants
+//!
+//! Transactions breaking these rules are valid in blocks, but are not accepted to memory pool
+
+/// Maximal size of null data (OP_RETURN) output script, including OP_RETURN and push opcodes
+pub const MAX_NULL_DATA_SIZE: usize = 83;
+/// Maximal number of null data outputs in transaction
+pub const MAX_NULL_DATA_OUTPUTS: usize = 1;
@@ -5,7 +5,9 @@ use params::{ConsensusParams, ConsensusFork};
 use duplex_store::NoopStore;
 use sigops::transaction_sigops;
 use error::TransactionError;
+use script::Script;
 use constants::{MIN_COINBASE_SIZE, MAX_COINBASE_SIZE};
+use policy::{MAX_NULL_DATA_SIZE, MAX_NULL_DATA_OUTPUTS};
 
 pub struct TransactionVerifier<'a> {
 	pub empty: TransactionEmpty<'a>,
@@ -37,6 +39,7 @@ pub struct MemoryPoolTransactionVerifier<'a> {
 	pub is_coinbase: TransactionMemoryPoolCoinbase<'a>,
 	pub size: TransactionSize<'a>,
 	pub sigops: TransactionSigops<'a>,
+	pub null_data: TransactionNullData<'a>,
 }
 
 impl<'a> MemoryPoolTransactionVerifier<'a> {
@@ -48,6 +51,7 @@ impl<'a> MemoryPoolTransactionVerifier<'a> {
 			is_coinbase: TransactionMemoryPoolCoinbase::new(transaction),
 			size: TransactionSize::new(transaction, consensus),
 			sigops: TransactionSigops::new(transaction, ConsensusFork::absolute_maximum_block_sigops()),
+			null_data: TransactionNullData::new(transaction, MAX_NULL_DATA_SIZE, MAX_NULL_DATA_OUTPUTS),
 		}
 	}
 
@@ -57,6 +61,7 @@ impl<'a> MemoryPoolTransactionVerifier<'a> {
 		try!(self.is_coinbase.check());
 		try!(self.size.check());
 		try!(self.sigops.check());
+		try!(self.null_data.check());
 		Ok(())
 	}
 }
@@ -190,3 +195,78 @@ impl<'a> TransactionSigops<'a> {
 		}
 	}
 }
+
+pub struct TransactionNullData<'a> {
+	transaction: &'a IndexedTransaction,
+	max_size: usize,
+	max_outputs: usize,
+}
+
+impl<'a> TransactionNullData<'a> {
+	fn new(transaction: &'a IndexedTransaction, max_size: usize, max_outputs: usize) -> Self {
+		TransactionNullData {
+			transaction: transaction,
+			max_size: max_size,
+			max_outputs: max_outputs,
+		}
+	}
+
+	fn check(&self) -> Result<(), TransactionError> {
+		let mut null_data_outputs = 0;
+		for output in &self.transaction.raw.outputs {
+			let script: Script = output.script_pubkey.clone().into();
+			if !script.is_null_data_script() {
+				continue;
+			}
+			if script.len() > self.max_size {
+				return Err(TransactionError::NullDataSize(script.len()));
+			}
+			null_data_outputs += 1;
+		}
+
+		if null_data_outputs > self.max_outputs {
+			Err(TransactionError::MultipleNullData)
+		} else {
+			Ok(())
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use chain::{IndexedTransaction, Transaction, TransactionOutput};
+	use script::Builder;
+	use error::TransactionError;
+	use super::TransactionNullData;
+
+	fn transaction_with_outputs(data: &[&[u8]]) -> IndexedTransaction {
+		let outputs = data.iter().map(|data| TransactionOutput {
+			value: 0,
+			script_pubkey: Builder::build_nulldata(data).to_bytes(),
+		}).collect();
+		Transaction {
+			version: 1,
+			inputs: vec![],
+			outputs: outputs,
+			lock_time: 0,
+		}.into()
+	}
+
+	#[test]
+	fn test_null_data_standard() {
+		let transaction = transaction_with_outputs(&[&[0u8; 80][..]]);
+		assert_eq!(TransactionNullData::new(&transaction, 83, 1).check(), Ok(()));
+	}
+
+	#[test]
+	fn test_null_data_too_large() {
+		let transaction = transaction_with_outputs(&[&[0u8; 81][..]]);
+		assert_eq!(TransactionNullData::new(&transaction, 83, 1).check(), Err(TransactionError::NullDataSize(84)));
+	}
+
+	#[test]
+	fn test_null_data_multiple_outputs() {
+		let transaction = transaction_with_outputs(&[&b"first"[..], &b"second"[..]]);
+		assert_eq!(TransactionNullData::new(&transaction, 83, 1).check(), Err(TransactionError::MultipleNullData));
+	}
+}